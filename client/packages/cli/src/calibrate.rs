@@ -0,0 +1,85 @@
+use color_eyre::eyre::eyre;
+use vita_reports::MainReport;
+use vita_virtual_device::{StickCalibration, StickCalibrator};
+
+/// Time the sticks are measured at rest, in microseconds.
+const CENTER_DURATION: u64 = 1_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Step {
+    /// Waiting for the user to leave the sticks at rest.
+    WaitCenter,
+    /// Measuring the rest position until the timestamp.
+    Center { until: u64 },
+    /// Measuring the range while the user rotates the sticks.
+    Range,
+    Done,
+}
+
+/// Guides the user through the calibration of both sticks.
+///
+/// The user confirms each step with Cross.
+pub struct Calibration {
+    step: Step,
+    left: StickCalibrator,
+    right: StickCalibrator,
+    cross_pressed: bool,
+}
+
+impl Calibration {
+    pub fn new() -> Self {
+        println!("Stick calibration");
+        println!("Leave both sticks centered, then press Cross");
+
+        Calibration {
+            step: Step::WaitCenter,
+            left: StickCalibrator::default(),
+            right: StickCalibrator::default(),
+            cross_pressed: false,
+        }
+    }
+
+    /// Processes a report from the Vita, returns `true` once the calibration is done.
+    pub fn push(&mut self, report: &MainReport) -> bool {
+        let confirmed = report.buttons.cross && !self.cross_pressed;
+        self.cross_pressed = report.buttons.cross;
+
+        match self.step {
+            Step::WaitCenter if confirmed => {
+                println!("Measuring the center, do not touch the sticks");
+                self.step = Step::Center {
+                    until: report.timestamp + CENTER_DURATION,
+                };
+            }
+            Step::Center { until } => {
+                self.left.add_center_sample(report.lx, report.ly);
+                self.right.add_center_sample(report.rx, report.ry);
+                if report.timestamp >= until {
+                    println!("Rotate both sticks along their edges a few times, then press Cross");
+                    self.step = Step::Range;
+                }
+            }
+            Step::Range => {
+                self.left.add_range_sample(report.lx, report.ly);
+                self.right.add_range_sample(report.rx, report.ry);
+                if confirmed {
+                    self.step = Step::Done;
+                }
+            }
+            _ => {}
+        }
+
+        self.step == Step::Done
+    }
+
+    /// Returns the calibrations of the left and right sticks.
+    pub fn finish(&self) -> color_eyre::Result<(StickCalibration, StickCalibration)> {
+        let left = self.left.finish().ok_or_else(|| {
+            eyre!("The left stick was not moved to its edges, please calibrate again")
+        })?;
+        let right = self.right.finish().ok_or_else(|| {
+            eyre!("The right stick was not moved to its edges, please calibrate again")
+        })?;
+        Ok((left, right))
+    }
+}
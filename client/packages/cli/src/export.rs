@@ -0,0 +1,79 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    time::Instant,
+};
+
+use color_eyre::eyre::{eyre, WrapErr};
+use vita_reports::{
+    export::{ExportFormat, ReportExporter},
+    MainReport,
+};
+
+use crate::record::FLUSH_INTERVAL;
+
+/// Returns the format asked for, or the one matching the extension of the output.
+pub fn format(format: Option<ExportFormat>, output: Option<&Path>) -> ExportFormat {
+    format
+        .or_else(|| output.and_then(ExportFormat::from_path))
+        .unwrap_or(ExportFormat::Csv)
+}
+
+/// Converts a recording, to the output file or to the standard output.
+pub fn export_recording(
+    input: &Path,
+    format: ExportFormat,
+    output: Option<&Path>,
+) -> color_eyre::Result<()> {
+    let (_, reports) = crate::replay::load(input)?;
+
+    let writer: Box<dyn Write> = match output {
+        Some(path) => Box::new(File::create(path).wrap_err("Failed to create export file")?),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    let mut exporter = ReportExporter::new(BufWriter::new(writer), format);
+    for recorded in &reports {
+        exporter
+            .write(recorded.received_at, &recorded.report)
+            .wrap_err("Failed to export report")?;
+    }
+    exporter.flush().wrap_err("Failed to export report")?;
+
+    if let Some(path) = output {
+        println!("{} reports exported to {}", reports.len(), path.display());
+    }
+    Ok(())
+}
+
+/// Exports the reports received from the Vita while they arrive.
+pub struct LiveExport {
+    exporter: ReportExporter<BufWriter<File>>,
+    started: Instant,
+    flushed: Instant,
+}
+
+impl LiveExport {
+    pub fn create(path: &Path) -> color_eyre::Result<Self> {
+        let format = ExportFormat::from_path(path)
+            .ok_or_else(|| eyre!("Export file should end with .csv or .jsonl"))?;
+        let file = File::create(path).wrap_err("Failed to create export file")?;
+        Ok(LiveExport {
+            exporter: ReportExporter::new(BufWriter::new(file), format),
+            started: Instant::now(),
+            flushed: Instant::now(),
+        })
+    }
+
+    pub fn push(&mut self, report: &MainReport) -> color_eyre::Result<()> {
+        let received_at = self.started.elapsed().as_micros() as u64;
+        self.exporter
+            .write(received_at, report)
+            .wrap_err("Failed to export report")?;
+        if self.flushed.elapsed() >= FLUSH_INTERVAL {
+            self.exporter.flush().wrap_err("Failed to export report")?;
+            self.flushed = Instant::now();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+use std::time::{Duration, Instant};
+
+use protocol::clock::{ClockSync, LatencyStats};
+
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+const SUMMARY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Measures how long the reports take from the Vita to the virtual device.
+///
+/// The network latency goes from the time the Vita sampled the buttons to the time
+/// the report was received, using the clock offset estimated with pings. The
+/// processing time goes from then to the report being sent to the virtual device.
+pub struct LatencyMonitor {
+    started: Instant,
+    clock: ClockSync,
+    network: LatencyStats,
+    processing: LatencyStats,
+    next_ping: Instant,
+    next_summary: Instant,
+}
+
+impl LatencyMonitor {
+    pub fn new() -> Self {
+        let started = Instant::now();
+        LatencyMonitor {
+            started,
+            clock: ClockSync::default(),
+            network: LatencyStats::default(),
+            processing: LatencyStats::default(),
+            next_ping: started,
+            next_summary: started + SUMMARY_INTERVAL,
+        }
+    }
+
+    /// Time on the client clock.
+    pub fn now(&self) -> u64 {
+        self.started.elapsed().as_micros() as u64
+    }
+
+    /// Time left until the next ping.
+    pub fn until_ping(&self) -> Duration {
+        self.next_ping.saturating_duration_since(Instant::now())
+    }
+
+    /// Returns the time to send a ping with, when one is due.
+    pub fn poll_ping(&mut self) -> Option<u64> {
+        let now = Instant::now();
+        if now < self.next_ping {
+            return None;
+        }
+        self.next_ping = now + PING_INTERVAL;
+        Some(self.now())
+    }
+
+    pub fn push_pong(&mut self, client_time: u64, server_time: u64) {
+        match self.clock.push_pong(client_time, server_time, self.now()) {
+            Some(sample) => log::debug!(
+                "Ping round trip {} us, clock offset {} us",
+                sample.round_trip,
+                sample.offset
+            ),
+            None => log::warn!("Pong received for a ping not sent yet"),
+        }
+    }
+
+    /// Forgets the clock offset, when the clock of the Vita changed.
+    pub fn reset_clock(&mut self) {
+        self.clock.reset();
+        self.next_ping = Instant::now();
+    }
+
+    /// Adds a report sampled at `timestamp` on the Vita and received at `received_at`.
+    pub fn push_report(&mut self, timestamp: u64, received_at: u64) {
+        let Some(sampled_at) = self.clock.to_client_time(timestamp) else {
+            return;
+        };
+        let latency = received_at as i64 - sampled_at;
+        self.network.push(latency);
+        log::trace!(
+            "Report latency {latency} us, jitter {:.0} us",
+            self.network.jitter()
+        );
+    }
+
+    /// Adds a report received at `received_at` and sent to the virtual device.
+    pub fn push_processed(&mut self, received_at: u64) {
+        self.processing
+            .push(self.now().saturating_sub(received_at) as i64);
+        self.print_summary();
+    }
+
+    /// Prints the statistics every few seconds.
+    fn print_summary(&mut self) {
+        if Instant::now() < self.next_summary {
+            return;
+        }
+        self.next_summary = Instant::now() + SUMMARY_INTERVAL;
+
+        let ms = |micros: f64| micros / 1000.0;
+        match (self.network.mean(), self.network.min(), self.network.max()) {
+            (Some(mean), Some(min), Some(max)) => println!(
+                "Network latency {:.1} ms (min {:.1}, max {:.1}), jitter {:.1} ms, round trip {:.1} ms",
+                ms(mean),
+                ms(min as f64),
+                ms(max as f64),
+                ms(self.network.jitter()),
+                ms(self.clock.estimate().map_or(0, |sample| sample.round_trip) as f64),
+            ),
+            _ => println!("Network latency unknown, waiting for the clock of the Vita"),
+        }
+        if let (Some(mean), Some(max)) = (self.processing.mean(), self.processing.max()) {
+            println!(
+                "Processing time {:.2} ms (max {:.2})",
+                ms(mean),
+                ms(max as f64)
+            );
+        }
+        self.network.reset_window();
+        self.processing.reset_window();
+    }
+}
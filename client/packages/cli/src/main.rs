@@ -1,20 +1,37 @@
+mod calibrate;
+mod export;
+mod latency;
+mod record;
+mod replay;
+
 use std::{
     io::{Read, Write},
     net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, TcpStream, UdpSocket},
+    path::PathBuf,
     time::{Duration, SystemTime},
 };
 
 use argh::FromArgs;
-use color_eyre::eyre::WrapErr;
+use color_eyre::eyre::{eyre, WrapErr};
 use polling::{Event, Events, Poller};
 use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
 
 use flatbuffers_structs::net_protocol::{ConfigArgs, Endpoint, HandshakeArgs};
 use protocol::connection::Connection;
-use vita_virtual_device::{VitaDevice, VitaVirtualDevice};
+use protocol::sequence::{ReportOrder, ReportSequencer};
+use vita_reports::export::ExportFormat;
+use vita_virtual_device::{
+    ConfigBuilder, MacroRecorder, VitaButton, VitaDevice, VitaVirtualDevice,
+};
 
 use vitaoxipad_config::*;
 
+use crate::calibrate::Calibration;
+use crate::export::LiveExport;
+use crate::latency::LatencyMonitor;
+use crate::record::SessionRecorder;
+use crate::replay::ReplayOptions;
+
 /// Create a virtual controller and fetch its data from a Vita
 /// over the network.
 #[derive(FromArgs)]
@@ -46,8 +63,86 @@ struct Args {
     /// print sample config file
     #[argh(switch, short = 's')]
     sample_config: bool,
+
+    /// record a macro with this name and save it to the config file,
+    /// press Select+Start to stop recording
+    #[argh(option)]
+    record_macro: Option<String>,
+
+    /// record the reports received from the Vita to this file
+    #[argh(option)]
+    record: Option<PathBuf>,
+
+    /// export the reports received from the Vita to this .csv or .jsonl file
+    #[argh(option)]
+    export: Option<PathBuf>,
+
+    /// print the latency and jitter of the reports, the Vita must run the same version
+    #[argh(switch)]
+    latency: bool,
+
+    #[argh(subcommand)]
+    command: Option<Command>,
 }
 
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum Command {
+    Calibrate(CalibrateCommand),
+    Replay(ReplayCommand),
+    Export(ExportCommand),
+}
+
+/// Measure the center and range of the sticks and save them to the config file.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "calibrate")]
+struct CalibrateCommand {}
+
+/// Send the reports of a recording to a virtual device, without a Vita.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "replay")]
+struct ReplayCommand {
+    /// recording made with --record
+    #[argh(positional)]
+    file: PathBuf,
+
+    /// playback speed (default: 1.0)
+    #[argh(option, default = "1.0")]
+    speed: f64,
+
+    /// start over at the end of the recording
+    #[argh(switch, long = "loop")]
+    looped: bool,
+
+    /// wait for Enter before each report
+    #[argh(switch)]
+    step: bool,
+
+    /// use the configuration and profile in use instead of the recorded ones
+    #[argh(switch)]
+    current_profile: bool,
+}
+
+/// Convert a recording to CSV or JSON lines, one row per report.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "export")]
+struct ExportCommand {
+    /// recording made with --record
+    #[argh(positional)]
+    file: PathBuf,
+
+    /// csv or json (default: from the output extension, or csv)
+    #[argh(option)]
+    format: Option<ExportFormat>,
+
+    /// file to write to (default: standard output)
+    #[argh(option, short = 'o')]
+    output: Option<PathBuf>,
+}
+
+/// Buttons pressed together to stop recording a macro.
+const MACRO_STOP_BUTTONS: [VitaButton; 2] = [VitaButton::Select, VitaButton::Start];
+
 fn filter_udp_nonblocking_error(
     err: std::io::Error,
 ) -> Result<(usize, SocketAddr), std::io::Error> {
@@ -58,6 +153,46 @@ fn filter_udp_nonblocking_error(
     }
 }
 
+/// Replays a recording, with the configuration and profile it was recorded with by default.
+fn run_replay(
+    command: &ReplayCommand,
+    configuration: Option<&str>,
+    profile: Option<&ConfigBuilder>,
+) -> color_eyre::Result<()> {
+    let (header, reports) = replay::load(&command.file)?;
+
+    let recorded_profile;
+    let (configuration, profile) = if command.current_profile {
+        (configuration.unwrap_or("standart"), profile)
+    } else {
+        recorded_profile = replay::recorded_profile(&header)?;
+        (header.configuration.as_str(), Some(&recorded_profile))
+    };
+    let mut device_config = vita_virtual_device::Config::from_name(configuration)
+        .ok_or_else(|| eyre!("Unknown configuration: {configuration}"))?;
+    if let Some(profile) = profile {
+        device_config.apply(profile);
+    }
+
+    let mut device = VitaDevice::create_with_config(device_config)
+        .wrap_err("Failed to create virtual device, please check uinput permissions")?;
+
+    println!(
+        "Replaying {} reports with the {configuration} configuration, press Ctrl+C to stop",
+        reports.len()
+    );
+    replay::run(
+        &mut device,
+        &header,
+        &reports,
+        &ReplayOptions {
+            speed: command.speed,
+            looped: command.looped,
+            step: command.step,
+        },
+    )
+}
+
 fn main() -> color_eyre::Result<()> {
     color_eyre::install()?;
 
@@ -97,6 +232,19 @@ fn main() -> color_eyre::Result<()> {
     }
     pretty_env_logger::init();
 
+    if let Some(Command::Replay(command)) = &args.command {
+        return run_replay(
+            command,
+            args.configuration.as_deref(),
+            config.profile.as_ref(),
+        );
+    }
+    if let Some(Command::Export(command)) = &args.command {
+        let output = command.output.as_deref();
+        let format = export::format(command.format, output);
+        return export::export_recording(&command.file, format, output);
+    }
+
     let ip_str = match &args.ip {
         Some(ip) => ip,
         _none => {
@@ -109,6 +257,9 @@ fn main() -> color_eyre::Result<()> {
     const TIMEOUT: Duration = Duration::from_secs(25);
     const BUFFER_SIZE: usize = 2048;
     const MIN_POLLING_RATE: u64 = (1 * 1000 / 250) * 1000;
+    const PAD_KEY: usize = 1;
+    const CTRL_KEY: usize = 2;
+    const CTRL_READ_TIMEOUT: Duration = Duration::from_millis(10);
 
     let remote_port = args.port.unwrap_or(NET_PORT);
     let polling_interval = args
@@ -183,7 +334,14 @@ fn main() -> color_eyre::Result<()> {
 
     let mut last_time = SystemTime::now();
 
-    let mut device = VitaDevice::create(args.configuration.as_deref().unwrap_or("standart"))
+    let configuration = args.configuration.as_deref().unwrap_or("standart");
+    let mut device_config = vita_virtual_device::Config::from_name(configuration)
+        .ok_or_else(|| eyre!("Unknown configuration: {configuration}"))?;
+    if let Some(profile) = &config.profile {
+        device_config.apply(profile);
+    }
+
+    let mut device = VitaDevice::create_with_config(device_config)
         .wrap_err("Failed to create virtual device, please check uinput permissions")?;
 
     let identfiers = device.identifiers().map(|ids| ids.join(", ".as_ref()));
@@ -216,22 +374,84 @@ fn main() -> color_eyre::Result<()> {
 
     let poller = Poller::new().wrap_err("Failed to create poller")?;
     unsafe {
-        poller
-            .add_with_mode(&pad_socket, Event::readable(1), polling::PollMode::Level)
+        poller.add_with_mode(
+            &pad_socket,
+            Event::readable(PAD_KEY),
+            polling::PollMode::Level,
+        )
     }
     .wrap_err("Failed to add socket to poller")?;
 
+    // Pongs are read from the control socket, older servers do not answer pings.
+    // It stays blocking for the writes, the timeout only guards the reads against spurious wake ups
+    let mut latency = args.latency.then(LatencyMonitor::new);
+    let mut ctrl_conn = Connection::new();
+    if latency.is_some() {
+        ctrl_socket
+            .set_read_timeout(Some(CTRL_READ_TIMEOUT))
+            .wrap_err("Failed to set read timeout on socket")?;
+        unsafe {
+            poller.add_with_mode(
+                &ctrl_socket,
+                Event::readable(CTRL_KEY),
+                polling::PollMode::Level,
+            )
+        }
+        .wrap_err("Failed to add socket to poller")?;
+    }
+
+    let mut calibration = matches!(args.command, Some(Command::Calibrate(_)))
+        .then(Calibration::new);
+
+    let mut macro_recorder = args.record_macro.as_ref().map(|name| {
+        println!("Recording macro {name:?}, press Select+Start to stop");
+        MacroRecorder::default()
+    });
+
+    let mut session_recorder = match &args.record {
+        Some(path) => {
+            let profile = read_profile().wrap_err("Failed to read profile")?;
+            let recorder = SessionRecorder::create(
+                path,
+                polling_interval,
+                heartbeat_freq,
+                configuration,
+                profile,
+            )?;
+            println!("Recording reports to {}", path.display());
+            Some(recorder)
+        }
+        None => None,
+    };
+
+    let mut live_export = args.export.as_deref().map(LiveExport::create).transpose()?;
+
     let mut events = Events::new();
-    let mut last_timestamp = 0;
+    let mut sequencer = ReportSequencer::default();
     loop {
         log::trace!("Polling");
-        let timeout = Duration::from_secs(
+        let heartbeat_timeout = Duration::from_secs(
             (heartbeat_freq.saturating_sub(5) as u64)
                 .saturating_sub(last_time.elapsed().unwrap().as_secs()),
         );
+        // Wake up for the outputs changing over time, like turbo buttons
+        let mut timeout = match device.next_tick() {
+            Some(tick) => heartbeat_timeout.min(tick),
+            None => heartbeat_timeout,
+        };
+        if let Some(latency) = &latency {
+            timeout = timeout.min(latency.until_ping());
+        }
         poller
             .wait(&mut events, Some(timeout))
             .wrap_err("Failed to poll")?;
+        let pad_readable = events.iter().any(|event| event.key == PAD_KEY);
+        let ctrl_readable = events.iter().any(|event| event.key == CTRL_KEY);
+        events.clear();
+
+        if device.next_tick().is_some_and(|tick| tick.is_zero()) {
+            device.tick().wrap_err("Failed to update virtual device")?;
+        }
 
         if last_time
             .elapsed()
@@ -249,7 +469,40 @@ fn main() -> color_eyre::Result<()> {
             log::trace!("Last time updated to {last_time:?}");
         }
 
-        if events.len() == 0 {
+        if let Some(latency) = &mut latency {
+            if let Some(client_time) = latency.poll_ping() {
+                conn.send_ping(client_time);
+                ctrl_socket
+                    .write_all(conn.retrieve_out_data().as_slice())
+                    .wrap_err("Failed to send ping to Vita")?;
+            }
+
+            if ctrl_readable {
+                // One read per wake up, the poller wakes up again while data is left
+                match ctrl_socket.read(&mut buf) {
+                    Ok(0) => return Err(eyre!("Connection closed by the Vita")),
+                    Ok(len) => ctrl_conn.receive_data(&buf[..len]),
+                    Err(e)
+                        if matches!(
+                            e.kind(),
+                            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                        ) => {}
+                    Err(e) => return Err(e).wrap_err("Failed to receive data from Vita"),
+                }
+                for event in ctrl_conn.events() {
+                    match event {
+                        Ok(protocol::events::Event::PongReceived {
+                            client_time,
+                            server_time,
+                        }) => latency.push_pong(client_time, server_time),
+                        Ok(event) => log::debug!("Event received: {event:?}"),
+                        Err(e) => eprintln!("Error when receiving data from Vita: {e}"),
+                    }
+                }
+            }
+        }
+
+        if !pad_readable {
             continue;
         }
 
@@ -258,6 +511,7 @@ fn main() -> color_eyre::Result<()> {
             .or_else(filter_udp_nonblocking_error)
             .wrap_err("Failed to receive data from Vita")?;
         log::debug!("Received {len} bytes from Vita");
+        let received_at = latency.as_ref().map(LatencyMonitor::now);
 
         let received_data = &buf[..len];
 
@@ -272,24 +526,78 @@ fn main() -> color_eyre::Result<()> {
         for event in conn.events() {
             log::debug!("Event received: {event:?}");
             match event {
-                Ok(protocol::events::Event::PadDataReceived { data }) => {
-                    if data.timestamp <= last_timestamp {
-                        log::warn!("Timestamp is not increasing, dropping packet");
-                        continue;
+                Ok(protocol::events::Event::PadDataReceived { mut data }) => {
+                    // Recorded before the sequencer, so a replay goes through it again
+                    if let Some(recorder) = &mut session_recorder {
+                        recorder.push(&data)?;
+                    }
+
+                    let vita_timestamp = data.timestamp;
+                    match sequencer.push(&mut data) {
+                        ReportOrder::Next => {}
+                        ReportOrder::Resync(reason) => {
+                            log::warn!("{reason}, resynchronising");
+                            if let Some(latency) = &mut latency {
+                                latency.reset_clock();
+                            }
+                        }
+                        ReportOrder::Stale => {
+                            log::debug!("Packet out of order, dropping it");
+                            continue;
+                        }
+                    }
+                    if let (Some(latency), Some(received_at)) = (&mut latency, received_at) {
+                        latency.push_report(vita_timestamp, received_at);
                     }
-                    last_timestamp = data.timestamp;
 
                     let report = vita_reports::MainReport::from(data);
+
+                    if let Some(export) = &mut live_export {
+                        export.push(&report)?;
+                    }
+
+                    // Reports only feed the calibration while calibrating
+                    if let Some(calibration) = &mut calibration {
+                        if calibration.push(&report) {
+                            let (left, right) = calibration.finish()?;
+                            let path = save_stick_calibration(&left, &right)
+                                .wrap_err("Failed to save calibration")?;
+                            println!("Calibration saved to {}", path.display());
+                            return Ok(());
+                        }
+                        continue;
+                    }
+
+                    if let (Some(name), Some(recorder)) = (&args.record_macro, &mut macro_recorder)
+                    {
+                        if MACRO_STOP_BUTTONS
+                            .iter()
+                            .all(|button| button.is_pressed(&report.buttons))
+                        {
+                            let recorded = std::mem::take(recorder).finish(&MACRO_STOP_BUTTONS);
+                            let path =
+                                save_macro(name, &recorded).wrap_err("Failed to save macro")?;
+                            println!(
+                                "Macro {name:?} with {} steps saved to {}",
+                                recorded.steps.len(),
+                                path.display()
+                            );
+                            return Ok(());
+                        }
+                        recorder.push(&report);
+                    }
+
                     log::trace!("Sending report to virtual device: {report:?}");
                     device
                         .send_report(report)
                         .wrap_err("Failed to send report to virtual device")?;
+                    if let (Some(latency), Some(received_at)) = (&mut latency, received_at) {
+                        latency.push_processed(received_at);
+                    }
                 }
                 Err(e) => eprintln!("Error when receiving data from Vita: {e}"),
                 _ => {}
             }
         }
-
-        events.clear();
     }
 }
@@ -0,0 +1,77 @@
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::Path,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use color_eyre::eyre::WrapErr;
+use vita_reports::{
+    recording::{RecordingHeader, RecordingWriter},
+    MainReport,
+};
+
+/// Longest time the reports received stay in memory before being written to the file.
+///
+/// The client is usually stopped with Ctrl+C, which loses what is still buffered.
+pub const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Writes the reports received from the Vita to a recording file.
+pub struct SessionRecorder {
+    writer: RecordingWriter<BufWriter<File>>,
+    started: Instant,
+    flushed: Instant,
+    count: u64,
+}
+
+impl SessionRecorder {
+    pub fn create(
+        path: &Path,
+        polling_interval: u64,
+        heartbeat_freq: u32,
+        configuration: &str,
+        profile: String,
+    ) -> color_eyre::Result<Self> {
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64);
+        let header = RecordingHeader {
+            client_version: env!("CARGO_PKG_VERSION").to_string(),
+            started_at,
+            polling_interval,
+            heartbeat_freq,
+            configuration: configuration.to_string(),
+            profile,
+        };
+
+        let file = File::create(path).wrap_err("Failed to create recording file")?;
+        let writer = RecordingWriter::new(BufWriter::new(file), &header)
+            .wrap_err("Failed to write recording header")?;
+
+        Ok(SessionRecorder {
+            writer,
+            started: Instant::now(),
+            flushed: Instant::now(),
+            count: 0,
+        })
+    }
+
+    /// Appends a report with the time it was received.
+    pub fn push(&mut self, report: &MainReport) -> color_eyre::Result<()> {
+        let received_at = self.started.elapsed().as_micros() as u64;
+        self.writer
+            .write(received_at, report)
+            .wrap_err("Failed to write report to recording")?;
+        if self.flushed.elapsed() >= FLUSH_INTERVAL {
+            self.writer
+                .flush()
+                .wrap_err("Failed to write report to recording")?;
+            self.flushed = Instant::now();
+        }
+        self.count += 1;
+        if self.count % 1000 == 0 {
+            log::debug!("{} reports recorded", self.count);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,298 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use color_eyre::eyre::{eyre, WrapErr};
+use config::{Config as ConfigLoader, File as ConfigFile, FileFormat};
+use protocol::sequence::{ReportOrder, ReportSequencer};
+use vita_reports::{
+    recording::{RecordedReport, RecordingHeader, RecordingReader},
+    MainReport,
+};
+use vita_virtual_device::{ConfigBuilder, VitaVirtualDevice};
+
+/// How the reports of a recording are played.
+pub struct ReplayOptions {
+    /// Playback speed, 2.0 plays twice as fast.
+    pub speed: f64,
+    /// Starts over at the end of the recording.
+    pub looped: bool,
+    /// Waits for Enter before each report.
+    pub step: bool,
+}
+
+/// Maps the timestamps of a recording to the ones sent during the playback.
+struct Timeline {
+    /// Timestamp of the first report.
+    start: u64,
+    /// Duration of a pass of the loop, in microseconds of the recording.
+    span: u64,
+    speed: f64,
+}
+
+impl Timeline {
+    /// Returns the timestamp sent for a recorded one, during the given pass of the loop.
+    fn retime(&self, pass: u64, timestamp: u64) -> u64 {
+        // Sample timestamps can come before the first report
+        let offset = (pass * self.span) as f64 + timestamp as f64 - self.start as f64;
+        (self.start as f64 + offset / self.speed) as u64
+    }
+
+    /// Retimes the report and the samples it carries.
+    fn retime_report(&self, pass: u64, report: &mut MainReport) {
+        report.timestamp = self.retime(pass, report.timestamp);
+        report.motion.timestamp = self.retime(pass, report.motion.timestamp);
+        for sample in &mut report.motion_samples {
+            sample.timestamp = self.retime(pass, sample.timestamp);
+        }
+        for touch in report
+            .front_touch
+            .reports
+            .iter_mut()
+            .chain(&mut report.back_touch.reports)
+        {
+            touch.timestamp = self.retime(pass, touch.timestamp);
+        }
+    }
+}
+
+/// Reads a whole recording.
+pub fn load(path: &Path) -> color_eyre::Result<(RecordingHeader, Vec<RecordedReport>)> {
+    let file = File::open(path).wrap_err("Failed to open recording")?;
+    let reader = RecordingReader::new(BufReader::new(file)).wrap_err("Failed to read recording")?;
+    let header = reader.header().clone();
+    let reports = reader
+        .collect::<std::io::Result<Vec<_>>>()
+        .wrap_err("Failed to read recording")?;
+    Ok((header, reports))
+}
+
+/// Returns the profile stored in the recording.
+pub fn recorded_profile(header: &RecordingHeader) -> color_eyre::Result<ConfigBuilder> {
+    ConfigLoader::builder()
+        .add_source(ConfigFile::from_str(&header.profile, FileFormat::Toml))
+        .build()?
+        .try_deserialize()
+        .map_err(|e| eyre!("Invalid profile in recording: {e}"))
+}
+
+/// Drops the late reports and resynchronises the timestamps, as the client did live.
+fn sequence(reports: &[RecordedReport]) -> Vec<RecordedReport> {
+    let mut sequencer = ReportSequencer::default();
+    reports
+        .iter()
+        .filter_map(|recorded| {
+            let mut recorded = recorded.clone();
+            match sequencer.push(&mut recorded.report) {
+                ReportOrder::Next => {}
+                ReportOrder::Resync(reason) => log::warn!("{reason}, resynchronising"),
+                ReportOrder::Stale => {
+                    log::debug!("Packet out of order, dropping it");
+                    return None;
+                }
+            }
+            Some(recorded)
+        })
+        .collect()
+}
+
+/// Sends the reports to the device with the timing they were received with.
+///
+/// The timestamps are scaled with the speed, so the outputs changing over time,
+/// like turbo buttons, follow the playback.
+pub fn run<C, D: VitaVirtualDevice<C>>(
+    device: &mut D,
+    header: &RecordingHeader,
+    reports: &[RecordedReport],
+    options: &ReplayOptions,
+) -> color_eyre::Result<()> {
+    let reports = &sequence(reports);
+    let (Some(first), Some(last)) = (reports.first(), reports.last()) else {
+        println!("The recording has no reports");
+        return Ok(());
+    };
+    if options.speed <= 0.0 || !options.speed.is_finite() {
+        return Err(eyre!("Speed must be positive"));
+    }
+
+    // A loop starts one polling interval after the end of the previous one
+    let interval = header.polling_interval.max(1);
+    let received_span = last.received_at - first.received_at + interval;
+    let timeline = Timeline {
+        start: first.report.timestamp,
+        span: last.report.timestamp.saturating_sub(first.report.timestamp) + interval,
+        speed: options.speed,
+    };
+    let scale = |micros: u64| (micros as f64 / options.speed) as u64;
+
+    let mut stdin = std::io::stdin().lock();
+    let mut line = String::new();
+    let start = Instant::now();
+    let mut pass = 0;
+    loop {
+        for (i, recorded) in reports.iter().enumerate() {
+            let mut report = recorded.report.clone();
+            timeline.retime_report(pass, &mut report);
+
+            if options.step {
+                println!(
+                    "Report {}/{} at {} us, press Enter to send it",
+                    i + 1,
+                    reports.len(),
+                    recorded.received_at - first.received_at
+                );
+                line.clear();
+                if stdin.read_line(&mut line)? == 0 {
+                    return Ok(());
+                }
+            } else {
+                let due = start
+                    + Duration::from_micros(scale(
+                        pass * received_span + recorded.received_at - first.received_at,
+                    ));
+                wait_until(device, due)?;
+            }
+
+            log::trace!("Sending report to virtual device: {report:?}");
+            device
+                .send_report(report)
+                .wrap_err("Failed to send report to virtual device")?;
+        }
+
+        if !options.looped {
+            return Ok(());
+        }
+        pass += 1;
+        log::debug!("Replaying the recording again, loop {pass}");
+    }
+}
+
+/// Sleeps until the instant, updating the outputs changing over time in between.
+fn wait_until<C, D: VitaVirtualDevice<C>>(device: &mut D, due: Instant) -> color_eyre::Result<()> {
+    loop {
+        let left = due.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Ok(());
+        }
+        match device.next_tick() {
+            Some(tick) if tick.is_zero() => {
+                device.tick().wrap_err("Failed to update virtual device")?;
+            }
+            Some(tick) => thread::sleep(left.min(tick)),
+            None => thread::sleep(left),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vita_reports::{MotionData, TouchReport};
+
+    use super::*;
+
+    fn report(timestamp: u64) -> MainReport {
+        let mut report = MainReport {
+            timestamp,
+            motion: MotionData {
+                timestamp: timestamp - 1_000,
+                ..Default::default()
+            },
+            motion_samples: vec![MotionData {
+                timestamp: timestamp - 1_000,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let touch = TouchReport {
+            timestamp: timestamp - 2_000,
+            ..Default::default()
+        };
+        report.front_touch.reports.push(touch.clone());
+        report.back_touch.reports.push(touch);
+        report
+    }
+
+    #[test]
+    fn test_sequence() {
+        let recorded = |session, sequence, timestamp| RecordedReport {
+            received_at: 0,
+            report: MainReport {
+                session,
+                sequence,
+                ..report(timestamp)
+            },
+        };
+        let reports = sequence(&[
+            recorded(7, 1, 1_000_000),
+            recorded(7, 3, 1_020_000),
+            recorded(7, 2, 1_010_000),
+            recorded(9, 0, 5_000),
+        ]);
+
+        let timestamps: Vec<u64> = reports
+            .iter()
+            .map(|recorded| recorded.report.timestamp)
+            .collect();
+        assert_eq!(
+            timestamps,
+            [1_000_000, 1_020_000, 1_020_001],
+            "Late report should be dropped and the new session shifted"
+        );
+    }
+
+    #[test]
+    fn test_retime_speed() {
+        let timeline = Timeline {
+            start: 1_000_000,
+            span: 100_000,
+            speed: 2.0,
+        };
+
+        let mut first = report(1_000_000);
+        timeline.retime_report(0, &mut first);
+        assert_eq!(first.timestamp, 1_000_000);
+        assert_eq!(
+            first.motion.timestamp, 999_500,
+            "Samples before the first report should stay before it"
+        );
+
+        let mut later = report(1_080_000);
+        timeline.retime_report(0, &mut later);
+        assert_eq!(later.timestamp, 1_040_000);
+        assert_eq!(later.motion.timestamp, 1_039_500);
+        assert_eq!(later.motion_samples[0].timestamp, 1_039_500);
+        assert_eq!(later.front_touch.reports[0].timestamp, 1_039_000);
+        assert_eq!(later.back_touch.reports[0].timestamp, 1_039_000);
+    }
+
+    #[test]
+    fn test_retime_loop() {
+        let timeline = Timeline {
+            start: 1_000_000,
+            span: 100_000,
+            speed: 1.0,
+        };
+        assert_eq!(timeline.retime(0, 1_090_000), 1_090_000);
+        assert_eq!(
+            timeline.retime(1, 1_000_000),
+            1_100_000,
+            "Next pass should continue after the end of the recording"
+        );
+
+        let mut report = report(1_090_000);
+        timeline.retime_report(2, &mut report);
+        assert_eq!(report.timestamp, 1_290_000);
+        assert_eq!(report.motion.timestamp, 1_289_000);
+        assert_eq!(report.front_touch.reports[0].timestamp, 1_288_000);
+
+        let faster = Timeline {
+            speed: 2.0,
+            ..timeline
+        };
+        assert_eq!(faster.retime(1, 1_000_000), 1_050_000);
+    }
+}
@@ -0,0 +1,197 @@
+//! Offset between the clocks of the client and the Vita, and latency of the reports.
+//!
+//! All the times are in microseconds.
+
+use std::collections::VecDeque;
+
+/// Number of recent pings the offset is estimated from.
+const PING_SAMPLES: usize = 8;
+
+/// Round trip of a ping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PingSample {
+    /// Time between sending the ping and receiving its pong, on the client clock.
+    pub round_trip: u64,
+    /// Vita clock minus client clock, assuming the pong took half of the round trip.
+    pub offset: i64,
+}
+
+/// Estimates the offset between the clocks from pings.
+#[derive(Debug, Clone, Default)]
+pub struct ClockSync {
+    samples: VecDeque<PingSample>,
+}
+
+impl ClockSync {
+    /// Adds the pong to a ping sent at `sent_at` and received at `received_at`, on the
+    /// client clock, with the `server_time` of the Vita clock.
+    ///
+    /// Returns `None` if the pong was received before the ping was sent.
+    pub fn push_pong(
+        &mut self,
+        sent_at: u64,
+        server_time: u64,
+        received_at: u64,
+    ) -> Option<PingSample> {
+        let round_trip = received_at.checked_sub(sent_at)?;
+        let midpoint = sent_at + round_trip / 2;
+        let sample = PingSample {
+            round_trip,
+            offset: server_time as i64 - midpoint as i64,
+        };
+
+        if self.samples.len() == PING_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+        Some(sample)
+    }
+
+    /// Estimate from the recent ping with the shortest round trip,
+    /// the one the least delayed by the network.
+    pub fn estimate(&self) -> Option<PingSample> {
+        self.samples
+            .iter()
+            .min_by_key(|sample| sample.round_trip)
+            .copied()
+    }
+
+    /// Converts a time of the Vita clock to the client clock.
+    pub fn to_client_time(&self, vita_time: u64) -> Option<i64> {
+        Some(vita_time as i64 - self.estimate()?.offset)
+    }
+
+    /// Forgets the pings, when the Vita clock changed.
+    pub fn reset(&mut self) {
+        self.samples.clear();
+    }
+}
+
+/// Statistics of the latency of the reports over a window.
+#[derive(Debug, Clone, Default)]
+pub struct LatencyStats {
+    last: Option<i64>,
+    jitter: f64,
+    sum: i64,
+    count: u64,
+    min: Option<i64>,
+    max: Option<i64>,
+}
+
+impl LatencyStats {
+    pub fn push(&mut self, latency: i64) {
+        // Smoothed like the interarrival jitter of RTP, the clock offset cancels out
+        if let Some(last) = self.last {
+            let variation = latency.abs_diff(last) as f64;
+            self.jitter += (variation - self.jitter) / 16.0;
+        }
+        self.last = Some(latency);
+
+        self.sum += latency;
+        self.count += 1;
+        self.min = Some(self.min.map_or(latency, |min| min.min(latency)));
+        self.max = Some(self.max.map_or(latency, |max| max.max(latency)));
+    }
+
+    #[inline]
+    pub fn last(&self) -> Option<i64> {
+        self.last
+    }
+
+    /// Smoothed variation of the latency from a report to the next one.
+    #[inline]
+    pub fn jitter(&self) -> f64 {
+        self.jitter
+    }
+
+    #[inline]
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum as f64 / self.count as f64)
+    }
+
+    #[inline]
+    pub fn min(&self) -> Option<i64> {
+        self.min
+    }
+
+    #[inline]
+    pub fn max(&self) -> Option<i64> {
+        self.max
+    }
+
+    /// Starts a new window, the jitter keeps going.
+    pub fn reset_window(&mut self) {
+        self.sum = 0;
+        self.count = 0;
+        self.min = None;
+        self.max = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_offset() {
+        let mut clock = ClockSync::default();
+        assert_eq!(clock.to_client_time(5_000), None);
+
+        // Vita clock 1 s ahead, 2 ms each way
+        let sample = clock.push_pong(10_000, 1_012_000, 14_000).unwrap();
+        assert_eq!(
+            sample,
+            PingSample {
+                round_trip: 4_000,
+                offset: 1_000_000,
+            }
+        );
+        // Pong delayed by 10 ms on the way back
+        clock.push_pong(20_000, 1_022_000, 34_000).unwrap();
+        assert_eq!(
+            clock.estimate(),
+            Some(sample),
+            "Shortest round trip should be used"
+        );
+        assert_eq!(clock.to_client_time(1_050_000), Some(50_000));
+
+        assert_eq!(clock.push_pong(40_000, 1_040_000, 39_000), None);
+        clock.reset();
+        assert_eq!(clock.estimate(), None);
+    }
+
+    #[test]
+    fn test_old_pings_forgotten() {
+        let mut clock = ClockSync::default();
+        clock.push_pong(0, 1_000, 1_000).unwrap();
+        for i in 1..=PING_SAMPLES as u64 {
+            clock.push_pong(i * 10_000, 500_000, i * 10_000 + 5_000);
+        }
+        assert_eq!(clock.estimate().unwrap().round_trip, 5_000);
+    }
+
+    #[test]
+    fn test_latency_stats() {
+        let mut stats = LatencyStats::default();
+        assert_eq!(stats.mean(), None);
+
+        for latency in [5_000, 7_000, 5_000, 7_000] {
+            stats.push(latency);
+        }
+        assert_eq!(stats.count(), 4);
+        assert_eq!(stats.mean(), Some(6_000.0));
+        assert_eq!((stats.min(), stats.max()), (Some(5_000), Some(7_000)));
+        assert_eq!(stats.last(), Some(7_000));
+        assert!(stats.jitter() > 0.0 && stats.jitter() < 2_000.0);
+
+        let jitter = stats.jitter();
+        stats.reset_window();
+        assert_eq!(stats.count(), 0);
+        assert_eq!(stats.min(), None);
+        assert_eq!(stats.jitter(), jitter);
+    }
+}
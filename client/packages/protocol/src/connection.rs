@@ -2,7 +2,10 @@ use std::vec::Drain;
 
 use flatbuffers_structs::{
     flatbuffers::{self, FlatBufferBuilder},
-    net_protocol::{Handshake, Config, ConfigArgs, HandshakeArgs, Packet, PacketArgs, PacketContent},
+    net_protocol::{
+        Config, ConfigArgs, Handshake, HandshakeArgs, Packet, PacketArgs, PacketContent, Ping,
+        PingArgs,
+    },
 };
 
 use crate::events::Event;
@@ -58,6 +61,23 @@ impl Connection {
             .extend_from_slice(builder.finished_data());
     }
 
+    /// Asks the server for its clock, `client_time` is sent back with the answer.
+    pub fn send_ping(&mut self, client_time: u64) {
+        let mut builder = FlatBufferBuilder::new();
+        let ping = Ping::create(&mut builder, &PingArgs { client_time });
+        let packet = Packet::create(
+            &mut builder,
+            &PacketArgs {
+                content_type: PacketContent::Ping,
+                content: Some(ping.as_union_value()),
+            },
+        );
+        builder.finish_size_prefixed(packet, None);
+
+        self.outgoing_buffer
+            .extend_from_slice(builder.finished_data());
+    }
+
     pub fn send_heartbeat(&mut self) {
         self.outgoing_buffer
             .extend_from_slice(crate::HEARTBEAT_MAGIC);
@@ -127,6 +147,13 @@ impl<'a> Iterator for ConnectionEvents<'a> {
                     data: pad.try_into().ok()?,
                 }))
             }
+            PacketContent::Pong => {
+                let pong = packet.content_as_pong()?;
+                Some(Ok(Event::PongReceived {
+                    client_time: pong.client_time(),
+                    server_time: pong.server_time(),
+                }))
+            }
             _ => None,
         }
     }
@@ -136,7 +163,7 @@ impl<'a> Iterator for ConnectionEvents<'a> {
 mod tests {
     use super::*;
     use crate::events;
-    use flatbuffers_structs::net_protocol::Endpoint;
+    use flatbuffers_structs::net_protocol::{Endpoint, Pong, PongArgs};
 
     fn create_handshake(args: HandshakeArgs) -> Vec<u8> {
         let mut builder = FlatBufferBuilder::new();
@@ -291,4 +318,42 @@ mod tests {
 
         assert_eq!(events.next(), None, "No more events should be emitted");
     }
+
+    #[test]
+    fn test_connection_ping_pong() {
+        let mut connection = Connection::new();
+
+        connection.send_ping(1234);
+        let ping = connection.retrieve_out_data().collect::<Vec<_>>();
+        let packet =
+            flatbuffers_structs::net_protocol::size_prefixed_root_as_packet(&ping).unwrap();
+        assert_eq!(packet.content_as_ping().unwrap().client_time(), 1234);
+
+        let mut builder = FlatBufferBuilder::new();
+        let pong = Pong::create(
+            &mut builder,
+            &PongArgs {
+                client_time: 1234,
+                server_time: 987_654_321,
+            },
+        );
+        let packet = Packet::create(
+            &mut builder,
+            &PacketArgs {
+                content_type: PacketContent::Pong,
+                content: Some(pong.as_union_value()),
+            },
+        );
+        builder.finish_size_prefixed(packet, None);
+        connection.receive_data(builder.finished_data());
+
+        assert_eq!(
+            connection.events().next(),
+            Some(Ok(Event::PongReceived {
+                client_time: 1234,
+                server_time: 987_654_321,
+            })),
+            "PongReceived event should be emitted"
+        );
+    }
 }
@@ -23,4 +23,6 @@ pub enum Event {
     HandshakeResponseReceived { handshake: Handshake },
     HeartbeatReceived,
     PadDataReceived { data: MainReport },
+    /// Answer to a ping, with the time of the ping and the time of the server clock.
+    PongReceived { client_time: u64, server_time: u64 },
 }
@@ -1,7 +1,9 @@
 #[cfg(feature = "codec")]
 pub mod codec;
+pub mod clock;
 pub mod connection;
 pub mod events;
+pub mod sequence;
 pub mod state;
 
 const HEARTBEAT_MAGIC: &[u8] = &[0xff, 0xff, 0xff, 0xff, 0x42, 0x54];
@@ -0,0 +1,317 @@
+//! Ordering of the reports received over UDP, across restarts of the server.
+//!
+//! Reports are ordered by their sequence number when the server sends one, by their
+//! timestamp otherwise. When the session changes or the clock of the Vita goes back,
+//! the timestamps of the following reports are shifted to continue after the last one,
+//! so the virtual device always sees increasing times. Late packets of the previous
+//! sessions are dropped.
+
+use std::fmt;
+
+use vita_reports::MainReport;
+
+/// Going back this far in time, in microseconds, is a reset of the Vita clock
+/// rather than a late packet.
+const CLOCK_RESET_THRESHOLD: u64 = 1_000_000;
+
+/// Reports dropped in a row before starting over from the next one.
+const MAX_STALE_REPORTS: u32 = 100;
+
+/// Number of previous sessions whose late packets are recognised.
+const MAX_RETIRED_SESSIONS: usize = 8;
+
+/// Why the reports were resynchronised.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResyncReason {
+    /// The server restarted or a new connection began.
+    NewSession,
+    /// The timestamps went back.
+    ClockReset,
+    /// Too many reports in a row looked older than the last one.
+    TooManyStale,
+}
+
+impl fmt::Display for ResyncReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ResyncReason::NewSession => "New session of the Vita server",
+            ResyncReason::ClockReset => "Clock of the Vita was reset",
+            ResyncReason::TooManyStale => "Too many reports out of order",
+        })
+    }
+}
+
+/// What to do with a received report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportOrder {
+    /// The report follows the last one.
+    Next,
+    /// The report starts a new timeline, with its timestamps shifted.
+    Resync(ResyncReason),
+    /// The report is a duplicate or arrived late, and should be dropped.
+    Stale,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct LastReport {
+    session: u32,
+    sequence: u32,
+    /// Timestamp as sent by the Vita.
+    vita_timestamp: u64,
+    /// Timestamp after the shift.
+    timestamp: u64,
+}
+
+/// Drops the reports arriving out of order and keeps the timestamps increasing.
+#[derive(Debug, Clone, Default)]
+pub struct ReportSequencer {
+    last: Option<LastReport>,
+    /// Added to the timestamps of the Vita since the last resync.
+    shift: i64,
+    stale: u32,
+    /// Previous sessions, oldest first.
+    retired_sessions: Vec<u32>,
+}
+
+impl ReportSequencer {
+    /// Checks a report, shifting its timestamps unless it is stale.
+    pub fn push(&mut self, report: &mut MainReport) -> ReportOrder {
+        let order = match self.last {
+            None => ReportOrder::Next,
+            Some(last) => self.order(&last, report),
+        };
+
+        match order {
+            ReportOrder::Stale => {
+                self.stale += 1;
+                return ReportOrder::Stale;
+            }
+            ReportOrder::Resync(reason) => {
+                // Continue right after the last report
+                let last = self.last.expect("Resync needs a last report");
+                self.shift = (last.timestamp + 1).wrapping_sub(report.timestamp) as i64;
+                if reason == ResyncReason::NewSession {
+                    self.retired_sessions.push(last.session);
+                    if self.retired_sessions.len() > MAX_RETIRED_SESSIONS {
+                        self.retired_sessions.remove(0);
+                    }
+                }
+            }
+            ReportOrder::Next => {}
+        }
+        self.stale = 0;
+        // A session started over after too many stale reports is current again
+        self.retired_sessions
+            .retain(|&session| session != report.session);
+
+        let vita_timestamp = report.timestamp;
+        shift_timestamps(report, self.shift);
+        self.last = Some(LastReport {
+            session: report.session,
+            sequence: report.sequence,
+            vita_timestamp,
+            timestamp: report.timestamp,
+        });
+        order
+    }
+
+    fn order(&self, last: &LastReport, report: &MainReport) -> ReportOrder {
+        let elapsed = report.timestamp.wrapping_sub(last.vita_timestamp) as i64;
+        let order = if self.retired_sessions.contains(&report.session) {
+            ReportOrder::Stale
+        } else if report.session != last.session {
+            return ReportOrder::Resync(ResyncReason::NewSession);
+        } else if report.session != 0 {
+            if report.sequence.wrapping_sub(last.sequence) as i32 <= 0 {
+                ReportOrder::Stale
+            } else if elapsed <= 0 {
+                ReportOrder::Resync(ResyncReason::ClockReset)
+            } else {
+                ReportOrder::Next
+            }
+        } else if elapsed > 0 {
+            ReportOrder::Next
+        } else if elapsed.unsigned_abs() > CLOCK_RESET_THRESHOLD {
+            ReportOrder::Resync(ResyncReason::ClockReset)
+        } else {
+            ReportOrder::Stale
+        };
+
+        if order == ReportOrder::Stale && self.stale + 1 >= MAX_STALE_REPORTS {
+            return ReportOrder::Resync(ResyncReason::TooManyStale);
+        }
+        order
+    }
+}
+
+fn shift_timestamps(report: &mut MainReport, shift: i64) {
+    let timestamps = [&mut report.timestamp, &mut report.motion.timestamp]
+        .into_iter()
+        .chain(report.motion_samples.iter_mut().map(|s| &mut s.timestamp))
+        .chain(
+            report
+                .front_touch
+                .reports
+                .iter_mut()
+                .chain(&mut report.back_touch.reports)
+                .map(|touch| &mut touch.timestamp),
+        );
+    for timestamp in timestamps {
+        *timestamp = timestamp.wrapping_add_signed(shift);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(session: u32, sequence: u32, timestamp: u64) -> MainReport {
+        let mut report = MainReport {
+            session,
+            sequence,
+            timestamp,
+            ..Default::default()
+        };
+        report.motion.timestamp = timestamp - 500;
+        report
+    }
+
+    fn push(sequencer: &mut ReportSequencer, mut report: MainReport) -> (ReportOrder, u64) {
+        let order = sequencer.push(&mut report);
+        (order, report.timestamp)
+    }
+
+    #[test]
+    fn test_sequence() {
+        let mut sequencer = ReportSequencer::default();
+        assert_eq!(
+            push(&mut sequencer, report(7, 1, 10_000)),
+            (ReportOrder::Next, 10_000)
+        );
+        assert_eq!(
+            push(&mut sequencer, report(7, 3, 22_000)),
+            (ReportOrder::Next, 22_000),
+            "Lost reports are skipped"
+        );
+        assert_eq!(
+            push(&mut sequencer, report(7, 2, 16_000)).0,
+            ReportOrder::Stale
+        );
+        assert_eq!(
+            push(&mut sequencer, report(7, 3, 22_000)).0,
+            ReportOrder::Stale
+        );
+    }
+
+    #[test]
+    fn test_sequence_wraparound() {
+        let mut sequencer = ReportSequencer::default();
+        push(&mut sequencer, report(7, u32::MAX, 10_000));
+        assert_eq!(
+            push(&mut sequencer, report(7, 0, 16_000)),
+            (ReportOrder::Next, 16_000)
+        );
+        assert_eq!(
+            push(&mut sequencer, report(7, u32::MAX, 10_000)).0,
+            ReportOrder::Stale
+        );
+    }
+
+    #[test]
+    fn test_new_session() {
+        let mut sequencer = ReportSequencer::default();
+        push(&mut sequencer, report(7, 500, 90_000_000));
+
+        let mut restarted = report(9, 0, 2_000);
+        assert_eq!(
+            sequencer.push(&mut restarted),
+            ReportOrder::Resync(ResyncReason::NewSession)
+        );
+        assert_eq!(restarted.timestamp, 90_000_001);
+        assert_eq!(
+            restarted.motion.timestamp,
+            90_000_001 - 500,
+            "Sample timestamps should be shifted too"
+        );
+        assert_eq!(
+            push(&mut sequencer, report(9, 1, 8_000)),
+            (ReportOrder::Next, 90_006_001)
+        );
+    }
+
+    #[test]
+    fn test_late_packet_of_previous_session() {
+        let mut sequencer = ReportSequencer::default();
+        push(&mut sequencer, report(7, 500, 90_000_000));
+        assert_eq!(
+            push(&mut sequencer, report(9, 0, 2_000)),
+            (ReportOrder::Resync(ResyncReason::NewSession), 90_000_001)
+        );
+
+        assert_eq!(
+            push(&mut sequencer, report(7, 501, 90_006_000)).0,
+            ReportOrder::Stale,
+            "Late packet of the previous session should be dropped"
+        );
+        assert_eq!(
+            push(&mut sequencer, report(9, 1, 8_000)),
+            (ReportOrder::Next, 90_006_001),
+            "New session should go on without a second resync"
+        );
+
+        // Restarted twice
+        assert_eq!(
+            push(&mut sequencer, report(11, 0, 1_000)).0,
+            ReportOrder::Resync(ResyncReason::NewSession)
+        );
+        for session in [7, 9] {
+            assert_eq!(
+                push(&mut sequencer, report(session, 600, 95_000_000)).0,
+                ReportOrder::Stale
+            );
+        }
+    }
+
+    #[test]
+    fn test_clock_reset() {
+        let mut sequencer = ReportSequencer::default();
+        push(&mut sequencer, report(7, 10, 50_000_000));
+        assert_eq!(
+            push(&mut sequencer, report(7, 11, 1_000)),
+            (ReportOrder::Resync(ResyncReason::ClockReset), 50_000_001)
+        );
+
+        // Without sequence numbers, from an older server
+        let mut sequencer = ReportSequencer::default();
+        push(&mut sequencer, report(0, 0, 50_000_000));
+        assert_eq!(
+            push(&mut sequencer, report(0, 0, 49_990_000)).0,
+            ReportOrder::Stale,
+            "Late packet"
+        );
+        assert_eq!(
+            push(&mut sequencer, report(0, 0, 1_000)),
+            (ReportOrder::Resync(ResyncReason::ClockReset), 50_000_001)
+        );
+        assert_eq!(
+            push(&mut sequencer, report(0, 0, 7_000)),
+            (ReportOrder::Next, 50_006_001)
+        );
+    }
+
+    #[test]
+    fn test_too_many_stale() {
+        let mut sequencer = ReportSequencer::default();
+        push(&mut sequencer, report(0, 0, 1_000_000));
+        for _ in 1..MAX_STALE_REPORTS {
+            assert_eq!(
+                push(&mut sequencer, report(0, 0, 500_000)).0,
+                ReportOrder::Stale
+            );
+        }
+        assert_eq!(
+            push(&mut sequencer, report(0, 0, 500_000)),
+            (ReportOrder::Resync(ResyncReason::TooManyStale), 1_000_001)
+        );
+    }
+}
@@ -0,0 +1,230 @@
+use std::fmt;
+use std::str::FromStr;
+
+use bitflags::bitflags;
+
+use crate::ButtonsData;
+
+bitflags! {
+    /// Set of PS Vita buttons.
+    ///
+    /// The bits follow the order of [`ButtonsData::NAMES`].
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+    pub struct VitaButtons: u16 {
+        const SELECT = 1 << 0;
+        const START = 1 << 1;
+        const UP = 1 << 2;
+        const RIGHT = 1 << 3;
+        const DOWN = 1 << 4;
+        const LEFT = 1 << 5;
+        const L = 1 << 6;
+        const R = 1 << 7;
+        const TRIANGLE = 1 << 8;
+        const CIRCLE = 1 << 9;
+        const CROSS = 1 << 10;
+        const SQUARE = 1 << 11;
+        const VOLUME_UP = 1 << 12;
+        const VOLUME_DOWN = 1 << 13;
+        const PS = 1 << 14;
+
+        const DPAD = Self::UP.bits() | Self::RIGHT.bits() | Self::DOWN.bits() | Self::LEFT.bits();
+        const FACE = Self::TRIANGLE.bits()
+            | Self::CIRCLE.bits()
+            | Self::CROSS.bits()
+            | Self::SQUARE.bits();
+    }
+}
+
+/// Names of the buttons in config files, in the order of the bits.
+const BUTTON_NAMES: [&str; 15] = [
+    "Select",
+    "Start",
+    "Up",
+    "Right",
+    "Down",
+    "Left",
+    "L",
+    "R",
+    "Triangle",
+    "Circle",
+    "Cross",
+    "Square",
+    "VolumeUp",
+    "VolumeDown",
+    "PS",
+];
+
+impl VitaButtons {
+    /// Returns the name of a single button, like `"VolumeUp"`.
+    pub fn name(self) -> Option<&'static str> {
+        if self.bits().count_ones() != 1 {
+            return None;
+        }
+        BUTTON_NAMES
+            .get(self.bits().trailing_zeros() as usize)
+            .copied()
+    }
+
+    /// Parses the name of a single button, ignoring case.
+    ///
+    /// The names of the [`ButtonsData`] fields, like `lt` or `vol_up`, are also accepted.
+    pub fn parse_name(name: &str) -> Option<Self> {
+        let name = name.trim();
+        BUTTON_NAMES
+            .iter()
+            .chain(ButtonsData::NAMES.iter())
+            .position(|candidate| candidate.eq_ignore_ascii_case(name))
+            .map(|index| Self::from_bits_retain(1 << (index % BUTTON_NAMES.len())))
+    }
+
+    /// Iterates over the names of the buttons in the set.
+    pub fn names(self) -> impl Iterator<Item = &'static str> {
+        self.iter().filter_map(VitaButtons::name)
+    }
+}
+
+/// Writes the buttons joined by `+`, like `Select+Start`.
+impl fmt::Display for VitaButtons {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, name) in self.names().enumerate() {
+            if i > 0 {
+                f.write_str("+")?;
+            }
+            f.write_str(name)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads buttons separated by `+` or `|`, like `Select+Start`.
+impl FromStr for VitaButtons {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Ok(VitaButtons::empty());
+        }
+        s.split(['+', '|'])
+            .map(|name| {
+                VitaButtons::parse_name(name).ok_or_else(|| format!("Unknown button {name:?}"))
+            })
+            .collect()
+    }
+}
+
+impl From<&ButtonsData> for VitaButtons {
+    fn from(buttons: &ButtonsData) -> Self {
+        buttons
+            .to_array()
+            .iter()
+            .enumerate()
+            .filter(|(_, &pressed)| pressed)
+            .map(|(i, _)| VitaButtons::from_bits_retain(1 << i))
+            .collect()
+    }
+}
+
+impl From<VitaButtons> for ButtonsData {
+    fn from(buttons: VitaButtons) -> Self {
+        ButtonsData::from_array(std::array::from_fn(|i| buttons.bits() & (1 << i) != 0))
+    }
+}
+
+impl From<flatbuffers_structs::net_protocol::ButtonsData> for VitaButtons {
+    fn from(buttons: flatbuffers_structs::net_protocol::ButtonsData) -> Self {
+        (&ButtonsData::from(buttons)).into()
+    }
+}
+
+impl From<VitaButtons> for flatbuffers_structs::net_protocol::ButtonsData {
+    fn from(buttons: VitaButtons) -> Self {
+        let pressed = |button| buttons.contains(button);
+        Self::new(
+            pressed(VitaButtons::SELECT),
+            pressed(VitaButtons::START),
+            pressed(VitaButtons::UP),
+            pressed(VitaButtons::RIGHT),
+            pressed(VitaButtons::DOWN),
+            pressed(VitaButtons::LEFT),
+            pressed(VitaButtons::L),
+            pressed(VitaButtons::R),
+            pressed(VitaButtons::TRIANGLE),
+            pressed(VitaButtons::CIRCLE),
+            pressed(VitaButtons::CROSS),
+            pressed(VitaButtons::SQUARE),
+            pressed(VitaButtons::VOLUME_UP),
+            pressed(VitaButtons::VOLUME_DOWN),
+            pressed(VitaButtons::PS),
+        )
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for VitaButtons {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for VitaButtons {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <std::borrow::Cow<str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buttons_data() {
+        let data = ButtonsData {
+            select: true,
+            lt: true,
+            vol_down: true,
+            ps: true,
+            ..Default::default()
+        };
+        let buttons = VitaButtons::from(&data);
+        assert_eq!(
+            buttons,
+            VitaButtons::SELECT | VitaButtons::L | VitaButtons::VOLUME_DOWN | VitaButtons::PS
+        );
+        assert_eq!(ButtonsData::from(buttons), data);
+        assert_eq!(
+            VitaButtons::from(&ButtonsData::default()),
+            VitaButtons::empty()
+        );
+    }
+
+    #[test]
+    fn test_names() {
+        let buttons = VitaButtons::START | VitaButtons::CROSS | VitaButtons::VOLUME_UP;
+        assert_eq!(
+            buttons.names().collect::<Vec<_>>(),
+            ["Start", "Cross", "VolumeUp"]
+        );
+        assert_eq!(buttons.to_string(), "Start+Cross+VolumeUp");
+        assert_eq!("Start+Cross+VolumeUp".parse(), Ok(buttons));
+        assert_eq!(" start | cross + vol_up ".parse(), Ok(buttons));
+        assert_eq!("lt+R".parse(), Ok(VitaButtons::L | VitaButtons::R));
+        assert_eq!("".parse(), Ok(VitaButtons::empty()));
+        assert!("Start+Home".parse::<VitaButtons>().is_err());
+        assert_eq!(VitaButtons::DPAD.name(), None);
+    }
+
+    #[test]
+    fn test_set_operations() {
+        let held = VitaButtons::UP | VitaButtons::LEFT | VitaButtons::CROSS;
+        assert_eq!(
+            held & VitaButtons::DPAD,
+            VitaButtons::UP | VitaButtons::LEFT
+        );
+        assert_eq!(held - VitaButtons::DPAD, VitaButtons::CROSS);
+        assert!(held.intersects(VitaButtons::FACE));
+        assert!(!held.contains(VitaButtons::FACE));
+        assert_eq!(held.iter().count(), 3);
+    }
+}
@@ -0,0 +1,227 @@
+//! Changes between two reports.
+
+use crate::{MainReport, TouchReport, VitaButtons};
+
+/// Changes of the contacts on a touch panel, matched by their id.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TouchDelta {
+    pub began: Vec<TouchReport>,
+    /// Contacts with a new position, with their previous and current report.
+    pub moved: Vec<(TouchReport, TouchReport)>,
+    pub ended: Vec<TouchReport>,
+}
+
+impl TouchDelta {
+    pub fn between(previous: &[TouchReport], current: &[TouchReport]) -> Self {
+        let find = |reports: &[TouchReport], id: u8| {
+            reports.iter().find(|report| report.id == id).cloned()
+        };
+
+        let mut delta = TouchDelta::default();
+        for report in current {
+            match find(previous, report.id) {
+                None => delta.began.push(report.clone()),
+                Some(before) if (before.x, before.y) != (report.x, report.y) => {
+                    delta.moved.push((before, report.clone()))
+                }
+                Some(_) => {}
+            }
+        }
+        delta.ended = previous
+            .iter()
+            .filter(|report| find(current, report.id).is_none())
+            .cloned()
+            .collect();
+        delta
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.began.is_empty() && self.moved.is_empty() && self.ended.is_empty()
+    }
+}
+
+/// What changed from a report to the next one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReportDelta {
+    /// Buttons pressed since the previous report.
+    pub pressed: VitaButtons,
+    /// Buttons released since the previous report.
+    pub released: VitaButtons,
+    /// New position of the left stick, if it moved past the threshold.
+    pub left_stick: Option<(u8, u8)>,
+    /// New position of the right stick, if it moved past the threshold.
+    pub right_stick: Option<(u8, u8)>,
+    pub front_touch: TouchDelta,
+    pub back_touch: TouchDelta,
+    /// New battery charge, if it changed.
+    pub charge_percent: Option<u8>,
+    /// Time between the reports, in microseconds.
+    pub elapsed: u64,
+}
+
+impl ReportDelta {
+    /// Compares two reports.
+    ///
+    /// A stick has moved when one of its axes changed by more than `stick_threshold`.
+    pub fn between(previous: &MainReport, current: &MainReport, stick_threshold: u8) -> Self {
+        let before = VitaButtons::from(&previous.buttons);
+        let after = VitaButtons::from(&current.buttons);
+        let stick = |from: (u8, u8), to: (u8, u8)| {
+            let moved =
+                from.0.abs_diff(to.0) > stick_threshold || from.1.abs_diff(to.1) > stick_threshold;
+            moved.then_some(to)
+        };
+
+        ReportDelta {
+            pressed: after - before,
+            released: before - after,
+            left_stick: stick((previous.lx, previous.ly), (current.lx, current.ly)),
+            right_stick: stick((previous.rx, previous.ry), (current.rx, current.ry)),
+            front_touch: TouchDelta::between(
+                &previous.front_touch.reports,
+                &current.front_touch.reports,
+            ),
+            back_touch: TouchDelta::between(
+                &previous.back_touch.reports,
+                &current.back_touch.reports,
+            ),
+            charge_percent: (previous.charge_percent != current.charge_percent)
+                .then_some(current.charge_percent),
+            elapsed: current.timestamp.saturating_sub(previous.timestamp),
+        }
+    }
+
+    /// Returns `true` if nothing changed, apart from the time.
+    pub fn is_empty(&self) -> bool {
+        self.pressed.is_empty()
+            && self.released.is_empty()
+            && self.left_stick.is_none()
+            && self.right_stick.is_none()
+            && self.front_touch.is_empty()
+            && self.back_touch.is_empty()
+            && self.charge_percent.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ButtonsData, TouchData};
+
+    fn touch(id: u8, x: u16, y: u16) -> TouchReport {
+        TouchReport {
+            x,
+            y,
+            id,
+            force: 0,
+            timestamp: 0,
+        }
+    }
+
+    fn report() -> MainReport {
+        MainReport {
+            lx: 128,
+            ly: 128,
+            rx: 128,
+            ry: 128,
+            charge_percent: 80,
+            timestamp: 1_000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_unchanged() {
+        let delta = ReportDelta::between(&report(), &report(), 0);
+        assert!(delta.is_empty());
+        assert_eq!(delta.elapsed, 0);
+    }
+
+    #[test]
+    fn test_buttons() {
+        let previous = MainReport {
+            buttons: ButtonsData {
+                cross: true,
+                up: true,
+                ..Default::default()
+            },
+            ..report()
+        };
+        let current = MainReport {
+            buttons: ButtonsData {
+                up: true,
+                start: true,
+                ..Default::default()
+            },
+            timestamp: 7_000,
+            ..report()
+        };
+
+        let delta = ReportDelta::between(&previous, &current, 0);
+        assert_eq!(delta.pressed, VitaButtons::START);
+        assert_eq!(delta.released, VitaButtons::CROSS);
+        assert_eq!(delta.elapsed, 6_000);
+        assert!(!delta.is_empty());
+    }
+
+    #[test]
+    fn test_stick_threshold() {
+        let current = MainReport {
+            lx: 131,
+            ry: 120,
+            ..report()
+        };
+
+        let delta = ReportDelta::between(&report(), &current, 3);
+        assert_eq!(delta.left_stick, None, "Change within the threshold");
+        assert_eq!(delta.right_stick, Some((128, 120)));
+
+        let delta = ReportDelta::between(&report(), &current, 2);
+        assert_eq!(delta.left_stick, Some((131, 128)));
+    }
+
+    #[test]
+    fn test_touches_by_id() {
+        let previous = MainReport {
+            front_touch: TouchData {
+                reports: vec![touch(1, 100, 100), touch(2, 200, 200), touch(3, 300, 300)],
+            },
+            ..report()
+        };
+        // Contacts can come in any order
+        let current = MainReport {
+            front_touch: TouchData {
+                reports: vec![touch(4, 400, 400), touch(3, 300, 300), touch(1, 110, 100)],
+            },
+            charge_percent: 79,
+            ..report()
+        };
+
+        let delta = ReportDelta::between(&previous, &current, 0);
+        assert_eq!(delta.front_touch.began, [touch(4, 400, 400)]);
+        assert_eq!(
+            delta.front_touch.moved,
+            [(touch(1, 100, 100), touch(1, 110, 100))]
+        );
+        assert_eq!(delta.front_touch.ended, [touch(2, 200, 200)]);
+        assert!(delta.back_touch.is_empty());
+        assert_eq!(delta.charge_percent, Some(79));
+    }
+
+    #[test]
+    fn test_battery() {
+        let current = MainReport {
+            charge_percent: 81,
+            timestamp: 2_000,
+            ..report()
+        };
+
+        let delta = ReportDelta::between(&report(), &current, 0);
+        assert_eq!(delta.charge_percent, Some(81));
+        assert!(!delta.is_empty());
+
+        let delta = ReportDelta::between(&current, &current, 0);
+        assert_eq!(delta.charge_percent, None, "Unchanged charge");
+    }
+}
@@ -0,0 +1,391 @@
+//! Export of reports to CSV or JSON lines, one row per report, for analysis in other tools.
+
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::{ButtonsData, MainReport, MotionData, TouchData, Vector3};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Comma separated values with a header row.
+    ///
+    /// Buttons are `0` or `1` columns, touches are space separated `id:x:y:force` values,
+    /// and motion samples are space separated
+    /// `timestamp:gyro_x:gyro_y:gyro_z:accel_x:accel_y:accel_z` values.
+    Csv,
+    /// One JSON object per line.
+    ///
+    /// The rows are flatter than the serde layout of [`MainReport`], to stay short and easy to
+    /// load in analysis tools: the pressed buttons are listed by name and the vectors are
+    /// `[x, y, z]` arrays. They are written without the `serde` feature.
+    JsonLines,
+}
+
+impl ExportFormat {
+    /// Returns the format matching the extension of the path.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "json" | "jsonl" => Ok(ExportFormat::JsonLines),
+            _ => Err(format!("Unknown export format {s:?}, expected csv or json")),
+        }
+    }
+}
+
+/// Writes reports as rows.
+///
+/// Each row includes the time since the previous report was received,
+/// to look at the packet jitter.
+pub struct ReportExporter<W: Write> {
+    writer: W,
+    format: ExportFormat,
+    previous_received_at: Option<u64>,
+    row: String,
+}
+
+impl<W: Write> ReportExporter<W> {
+    pub fn new(writer: W, format: ExportFormat) -> Self {
+        ReportExporter {
+            writer,
+            format,
+            previous_received_at: None,
+            row: String::new(),
+        }
+    }
+
+    /// Appends a report received `received_at` microseconds after the start of the session.
+    pub fn write(&mut self, received_at: u64, report: &MainReport) -> io::Result<()> {
+        let interval = self
+            .previous_received_at
+            .map(|previous| received_at.saturating_sub(previous));
+        let first = self.previous_received_at.is_none();
+        self.previous_received_at = Some(received_at);
+
+        self.row.clear();
+        match self.format {
+            ExportFormat::Csv => {
+                if first {
+                    csv_header(&mut self.row);
+                }
+                csv_row(&mut self.row, received_at, interval, report);
+            }
+            ExportFormat::JsonLines => json_row(&mut self.row, received_at, interval, report),
+        }
+        self.writer.write_all(self.row.as_bytes())
+    }
+
+    #[inline]
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+fn csv_header(out: &mut String) {
+    out.push_str("received_at,interval,timestamp");
+    for name in ButtonsData::NAMES {
+        let _ = write!(out, ",{name}");
+    }
+    out.push_str(",lx,ly,rx,ry,front_touch,back_touch");
+    out.push_str(",gyro_x,gyro_y,gyro_z,accel_x,accel_y,accel_z,motion_timestamp,charge_percent");
+    out.push_str(",motion_samples\n");
+}
+
+fn csv_touches(out: &mut String, touch: &TouchData) {
+    out.push(',');
+    for (i, report) in touch.reports.iter().enumerate() {
+        if i > 0 {
+            out.push(' ');
+        }
+        let _ = write!(
+            out,
+            "{}:{}:{}:{}",
+            report.id, report.x, report.y, report.force
+        );
+    }
+}
+
+fn csv_motion_samples(out: &mut String, samples: &[MotionData]) {
+    out.push(',');
+    for (i, sample) in samples.iter().enumerate() {
+        if i > 0 {
+            out.push(' ');
+        }
+        let _ = write!(out, "{}", sample.timestamp);
+        for vector in [sample.gyro, sample.accelerometer] {
+            let _ = write!(out, ":{}:{}:{}", vector.x, vector.y, vector.z);
+        }
+    }
+}
+
+fn csv_row(out: &mut String, received_at: u64, interval: Option<u64>, report: &MainReport) {
+    let _ = write!(out, "{received_at},");
+    if let Some(interval) = interval {
+        let _ = write!(out, "{interval}");
+    }
+    let _ = write!(out, ",{}", report.timestamp);
+    for pressed in report.buttons.to_array() {
+        let _ = write!(out, ",{}", u8::from(pressed));
+    }
+    let _ = write!(
+        out,
+        ",{},{},{},{}",
+        report.lx, report.ly, report.rx, report.ry
+    );
+    csv_touches(out, &report.front_touch);
+    csv_touches(out, &report.back_touch);
+    for vector in [report.motion.gyro, report.motion.accelerometer] {
+        let _ = write!(out, ",{},{},{}", vector.x, vector.y, vector.z);
+    }
+    let _ = write!(
+        out,
+        ",{},{}",
+        report.motion.timestamp, report.charge_percent
+    );
+    csv_motion_samples(out, &report.motion_samples);
+    out.push('\n');
+}
+
+/// JSON has no infinity or NaN.
+fn json_number(out: &mut String, value: f32) {
+    if value.is_finite() {
+        let _ = write!(out, "{value}");
+    } else {
+        out.push_str("null");
+    }
+}
+
+fn json_vector(out: &mut String, vector: Vector3) {
+    out.push('[');
+    json_number(out, vector.x);
+    out.push(',');
+    json_number(out, vector.y);
+    out.push(',');
+    json_number(out, vector.z);
+    out.push(']');
+}
+
+fn json_touches(out: &mut String, touch: &TouchData) {
+    out.push('[');
+    for (i, report) in touch.reports.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        let _ = write!(
+            out,
+            r#"{{"id":{},"x":{},"y":{},"force":{}}}"#,
+            report.id, report.x, report.y, report.force
+        );
+    }
+    out.push(']');
+}
+
+fn json_motion_samples(out: &mut String, samples: &[MotionData]) {
+    out.push('[');
+    for (i, sample) in samples.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str(r#"{"gyro":"#);
+        json_vector(out, sample.gyro);
+        out.push_str(r#","accelerometer":"#);
+        json_vector(out, sample.accelerometer);
+        let _ = write!(out, r#","timestamp":{}}}"#, sample.timestamp);
+    }
+    out.push(']');
+}
+
+fn json_row(out: &mut String, received_at: u64, interval: Option<u64>, report: &MainReport) {
+    let _ = write!(out, r#"{{"received_at":{received_at},"interval":"#);
+    match interval {
+        Some(interval) => {
+            let _ = write!(out, "{interval}");
+        }
+        None => out.push_str("null"),
+    }
+    let _ = write!(out, r#","timestamp":{},"buttons":["#, report.timestamp);
+    let pressed = ButtonsData::NAMES
+        .iter()
+        .zip(report.buttons.to_array())
+        .filter(|(_, pressed)| *pressed);
+    for (i, (name, _)) in pressed.enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        let _ = write!(out, r#""{name}""#);
+    }
+    let _ = write!(
+        out,
+        r#"],"lx":{},"ly":{},"rx":{},"ry":{},"front_touch":"#,
+        report.lx, report.ly, report.rx, report.ry
+    );
+    json_touches(out, &report.front_touch);
+    out.push_str(r#","back_touch":"#);
+    json_touches(out, &report.back_touch);
+    out.push_str(r#","gyro":"#);
+    json_vector(out, report.motion.gyro);
+    out.push_str(r#","accelerometer":"#);
+    json_vector(out, report.motion.accelerometer);
+    let _ = write!(out, r#","motion_timestamp":{}"#, report.motion.timestamp);
+    let _ = write!(out, r#","charge_percent":{}"#, report.charge_percent);
+    out.push_str(r#","motion_samples":"#);
+    json_motion_samples(out, &report.motion_samples);
+    out.push_str("}\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TouchReport;
+
+    fn export(format: ExportFormat) -> String {
+        let report = MainReport {
+            buttons: ButtonsData {
+                cross: true,
+                ps: true,
+                ..Default::default()
+            },
+            lx: 128,
+            ly: 127,
+            rx: 0,
+            ry: 255,
+            front_touch: TouchData {
+                reports: vec![
+                    TouchReport {
+                        x: 100,
+                        y: 200,
+                        id: 1,
+                        force: 64,
+                        timestamp: 900,
+                    },
+                    TouchReport {
+                        x: 300,
+                        y: 400,
+                        id: 2,
+                        force: 32,
+                        timestamp: 900,
+                    },
+                ],
+            },
+            motion: MotionData {
+                gyro: Vector3 {
+                    x: 0.5,
+                    y: f32::NAN,
+                    z: -2.0,
+                },
+                accelerometer: Vector3 {
+                    x: 0.0,
+                    y: 0.25,
+                    z: -1.0,
+                },
+                timestamp: 950,
+            },
+            timestamp: 1000,
+            charge_percent: 90,
+            motion_samples: vec![
+                MotionData {
+                    gyro: Vector3 {
+                        x: 0.25,
+                        y: 0.0,
+                        z: 0.0,
+                    },
+                    accelerometer: Vector3 {
+                        x: 0.0,
+                        y: 0.0,
+                        z: -1.0,
+                    },
+                    timestamp: 700,
+                },
+                MotionData {
+                    gyro: Vector3 {
+                        x: 0.5,
+                        y: 0.0,
+                        z: 0.0,
+                    },
+                    accelerometer: Vector3 {
+                        x: 0.0,
+                        y: 0.0,
+                        z: -1.0,
+                    },
+                    timestamp: 950,
+                },
+            ],
+            ..Default::default()
+        };
+        let mut exporter = ReportExporter::new(Vec::new(), format);
+        exporter.write(100, &report).unwrap();
+        exporter.write(6_200, &report).unwrap();
+        String::from_utf8(exporter.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn test_csv() {
+        let csv = export(ExportFormat::Csv);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3, "Header and one line per report");
+        assert_eq!(
+            lines[0],
+            "received_at,interval,timestamp,select,start,up,right,down,left,lt,rt,triangle,\
+             circle,cross,square,vol_up,vol_down,ps,lx,ly,rx,ry,front_touch,back_touch,\
+             gyro_x,gyro_y,gyro_z,accel_x,accel_y,accel_z,motion_timestamp,charge_percent,\
+             motion_samples"
+        );
+        assert_eq!(
+            lines[2],
+            "6200,6100,1000,0,0,0,0,0,0,0,0,0,0,1,0,0,0,1,128,127,0,255,\
+             1:100:200:64 2:300:400:32,,0.5,NaN,-2,0,0.25,-1,950,90,\
+             700:0.25:0:0:0:0:-1 950:0.5:0:0:0:0:-1"
+        );
+        let columns = lines[0].split(',').count();
+        assert!(lines.iter().all(|line| line.split(',').count() == columns));
+        assert!(
+            lines[1].starts_with("100,,1000,"),
+            "First report should have no interval"
+        );
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let json = export(ExportFormat::JsonLines);
+        let lines: Vec<&str> = json.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[1],
+            r#"{"received_at":6200,"interval":6100,"timestamp":1000,"buttons":["cross","ps"],"#
+                .to_owned()
+                + r#""lx":128,"ly":127,"rx":0,"ry":255,"#
+                + r#""front_touch":[{"id":1,"x":100,"y":200,"force":64},{"id":2,"x":300,"y":400,"force":32}],"#
+                + r#""back_touch":[],"gyro":[0.5,null,-2],"accelerometer":[0,0.25,-1],"motion_timestamp":950,"charge_percent":90,"#
+                + r#""motion_samples":[{"gyro":[0.25,0,0],"accelerometer":[0,0,-1],"timestamp":700},"#
+                + r#"{"gyro":[0.5,0,0],"accelerometer":[0,0,-1],"timestamp":950}]}"#
+        );
+        assert!(lines[0].contains(r#""interval":null"#));
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(
+            ExportFormat::from_path(Path::new("session.CSV")),
+            Some(ExportFormat::Csv)
+        );
+        assert_eq!(
+            ExportFormat::from_path(Path::new("session.jsonl")),
+            Some(ExportFormat::JsonLines)
+        );
+        assert_eq!(ExportFormat::from_path(Path::new("session.voxr")), None);
+    }
+}
@@ -1,4 +1,45 @@
-#[derive(Debug, Clone, PartialEq, Eq)]
+//! Reports received from the PS Vita.
+//!
+//! With the `serde` feature, the report types implement `Serialize` and `Deserialize`.
+//! The fields are serialized with their names, so a [`MainReport`] in JSON looks like:
+//!
+//! ```json
+//! {
+//!   "buttons": {
+//!     "select": false, "start": false, "up": false, "right": false, "down": false,
+//!     "left": false, "lt": false, "rt": false, "triangle": false, "circle": false,
+//!     "cross": true, "square": false, "vol_up": false, "vol_down": false, "ps": false
+//!   },
+//!   "lx": 128, "ly": 128, "rx": 128, "ry": 128,
+//!   "front_touch": {
+//!     "reports": [{ "x": 960, "y": 544, "id": 0, "force": 64, "timestamp": 123456000 }]
+//!   },
+//!   "back_touch": { "reports": [] },
+//!   "motion": {
+//!     "gyro": { "x": 0.0, "y": 0.0, "z": 0.0 },
+//!     "accelerometer": { "x": 0.0, "y": 0.0, "z": -1.0 },
+//!     "timestamp": 123455000
+//!   },
+//!   "timestamp": 123456789,
+//!   "charge_percent": 100,
+//!   "motion_samples": [],
+//!   "session": 305419896,
+//!   "sequence": 42
+//! }
+//! ```
+//!
+//! Fields are only added to this layout, existing ones keep their name and type.
+//! Added fields may be missing when deserializing.
+
+mod buttons;
+pub mod delta;
+pub mod export;
+pub mod recording;
+
+pub use buttons::VitaButtons;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ButtonsData {
     pub select: bool,
     pub start: bool,
@@ -18,6 +59,55 @@ pub struct ButtonsData {
     // timestamp: u64;
 }
 
+impl ButtonsData {
+    /// Names of the buttons, in the order of [`ButtonsData::to_array`].
+    pub const NAMES: [&'static str; 15] = [
+        "select", "start", "up", "right", "down", "left", "lt", "rt", "triangle", "circle",
+        "cross", "square", "vol_up", "vol_down", "ps",
+    ];
+
+    /// Returns whether each button is pressed.
+    pub fn to_array(&self) -> [bool; 15] {
+        [
+            self.select,
+            self.start,
+            self.up,
+            self.right,
+            self.down,
+            self.left,
+            self.lt,
+            self.rt,
+            self.triangle,
+            self.circle,
+            self.cross,
+            self.square,
+            self.vol_up,
+            self.vol_down,
+            self.ps,
+        ]
+    }
+
+    pub fn from_array(pressed: [bool; 15]) -> Self {
+        Self {
+            select: pressed[0],
+            start: pressed[1],
+            up: pressed[2],
+            right: pressed[3],
+            down: pressed[4],
+            left: pressed[5],
+            lt: pressed[6],
+            rt: pressed[7],
+            triangle: pressed[8],
+            circle: pressed[9],
+            cross: pressed[10],
+            square: pressed[11],
+            vol_up: pressed[12],
+            vol_down: pressed[13],
+            ps: pressed[14],
+        }
+    }
+}
+
 impl From<flatbuffers_structs::net_protocol::ButtonsData> for ButtonsData {
     fn from(buttons: flatbuffers_structs::net_protocol::ButtonsData) -> Self {
         Self {
@@ -41,7 +131,8 @@ impl From<flatbuffers_structs::net_protocol::ButtonsData> for ButtonsData {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vector3 {
     pub x: f32,
     pub y: f32,
@@ -58,43 +149,40 @@ impl From<&flatbuffers_structs::net_protocol::Vector3> for Vector3 {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MotionData {
     pub gyro: Vector3,
     pub accelerometer: Vector3,
-    // timestamp: u64,
+    /// Time the sample was taken, in microseconds.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub timestamp: u64,
 }
 
-impl From<flatbuffers_structs::net_protocol::MotionData> for MotionData {
-    fn from(motion: flatbuffers_structs::net_protocol::MotionData) -> Self {
+impl From<flatbuffers_structs::net_protocol::MotionSample> for MotionData {
+    fn from(sample: flatbuffers_structs::net_protocol::MotionSample) -> Self {
         Self {
-            gyro: motion.gyro().into(),
-            accelerometer: motion.accelerometer().into(),
-            // timestamp: motion.timestamp(),
+            gyro: sample.motion().gyro().into(),
+            accelerometer: sample.motion().accelerometer().into(),
+            timestamp: sample.timestamp(),
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TouchReport {
     pub x: u16,
     pub y: u16,
     pub id: u8,
     pub force: u8,
+    /// Time the touch panel was sampled, in microseconds.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub timestamp: u64,
 }
 
-impl From<flatbuffers_structs::net_protocol::TouchReport> for TouchReport {
-    fn from(touch: flatbuffers_structs::net_protocol::TouchReport) -> Self {
-        Self {
-            x: touch.x(),
-            y: touch.y(),
-            id: touch.id(),
-            force: touch.pressure(),
-        }
-    }
-}
-
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TouchData {
     pub reports: Vec<TouchReport>,
 }
@@ -106,14 +194,20 @@ impl<'a> From<flatbuffers_structs::net_protocol::TouchData<'a>> for TouchData {
                 .reports()
                 .unwrap_or_default()
                 .iter()
-                .cloned()
-                .map(Into::into)
+                .map(|report| TouchReport {
+                    x: report.x(),
+                    y: report.y(),
+                    id: report.id(),
+                    force: report.pressure(),
+                    timestamp: touch.timestamp(),
+                })
                 .collect(),
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MainReport {
     pub buttons: ButtonsData,
     pub lx: u8,
@@ -125,6 +219,17 @@ pub struct MainReport {
     pub motion: MotionData,
     pub timestamp: u64,
     pub charge_percent: u8,
+    /// Motion samples taken since the previous report, oldest first.
+    ///
+    /// Empty when the server does not send them.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub motion_samples: Vec<MotionData>,
+    /// Random id of the server connection, 0 when the server does not send it.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub session: u32,
+    /// Number of the packet in the session, wrapping around.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sequence: u32,
 }
 
 impl<'a> TryFrom<flatbuffers_structs::net_protocol::Pad<'a>> for MainReport {
@@ -140,17 +245,149 @@ impl<'a> TryFrom<flatbuffers_structs::net_protocol::Pad<'a>> for MainReport {
             .ok_or_else(|| "Back touch data is missing")?;
         let motion = *packet.motion().ok_or_else(|| "Motion data is missing")?;
 
-        Ok(Self {
+        let mut report = Self {
             buttons: buttons.into(),
             front_touch: front_touch.into(),
             back_touch: back_touch.into(),
-            motion: motion.into(),
+            motion: MotionData {
+                gyro: motion.gyro().into(),
+                accelerometer: motion.accelerometer().into(),
+                timestamp: packet.motion_timestamp(),
+            },
             timestamp: packet.timestamp(),
             lx: packet.lx(),
             ly: packet.ly(),
             rx: packet.rx(),
             ry: packet.ry(),
             charge_percent: packet.charge_percent(),
-        })
+            motion_samples: packet
+                .motion_samples()
+                .unwrap_or_default()
+                .iter()
+                .cloned()
+                .map(Into::into)
+                .collect(),
+            session: packet.session(),
+            sequence: packet.sequence(),
+        };
+
+        // Older servers only send the time of the packet
+        let sample_timestamps = [&mut report.motion.timestamp].into_iter().chain(
+            report
+                .front_touch
+                .reports
+                .iter_mut()
+                .chain(&mut report.back_touch.reports)
+                .map(|touch| &mut touch.timestamp),
+        );
+        for timestamp in sample_timestamps.filter(|timestamp| **timestamp == 0) {
+            *timestamp = report.timestamp;
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serde_layout() {
+        let report = MainReport {
+            buttons: ButtonsData {
+                cross: true,
+                ..Default::default()
+            },
+            lx: 1,
+            ly: 2,
+            rx: 3,
+            ry: 4,
+            front_touch: TouchData {
+                reports: vec![TouchReport {
+                    x: 960,
+                    y: 544,
+                    id: 0,
+                    force: 64,
+                    timestamp: 123456000,
+                }],
+            },
+            motion: MotionData {
+                gyro: Vector3 {
+                    x: 0.5,
+                    y: 0.0,
+                    z: 0.0,
+                },
+                accelerometer: Vector3 {
+                    x: 0.0,
+                    y: 0.0,
+                    z: -1.0,
+                },
+                timestamp: 123455000,
+            },
+            timestamp: 123456789,
+            charge_percent: 100,
+            session: 305419896,
+            sequence: 42,
+            ..Default::default()
+        };
+
+        let value = serde_json::to_value(&report).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "buttons": {
+                    "select": false, "start": false, "up": false, "right": false, "down": false,
+                    "left": false, "lt": false, "rt": false, "triangle": false, "circle": false,
+                    "cross": true, "square": false, "vol_up": false, "vol_down": false, "ps": false
+                },
+                "lx": 1, "ly": 2, "rx": 3, "ry": 4,
+                "front_touch": {
+                    "reports": [{ "x": 960, "y": 544, "id": 0, "force": 64, "timestamp": 123456000 }]
+                },
+                "back_touch": { "reports": [] },
+                "motion": {
+                    "gyro": { "x": 0.5, "y": 0.0, "z": 0.0 },
+                    "accelerometer": { "x": 0.0, "y": 0.0, "z": -1.0 },
+                    "timestamp": 123455000
+                },
+                "timestamp": 123456789,
+                "charge_percent": 100,
+                "motion_samples": [],
+                "session": 305419896,
+                "sequence": 42
+            })
+        );
+        assert_eq!(
+            serde_json::from_value::<MainReport>(value.clone()).unwrap(),
+            report
+        );
+
+        let mut older = value;
+        for (parent, field) in [
+            ("", "motion_samples"),
+            ("", "session"),
+            ("", "sequence"),
+            ("/motion", "timestamp"),
+            ("/front_touch/reports/0", "timestamp"),
+        ] {
+            older
+                .pointer_mut(parent)
+                .and_then(|parent| parent.as_object_mut())
+                .unwrap()
+                .remove(field);
+        }
+        let mut expected = MainReport {
+            session: 0,
+            sequence: 0,
+            ..report
+        };
+        expected.motion.timestamp = 0;
+        expected.front_touch.reports[0].timestamp = 0;
+        assert_eq!(
+            serde_json::from_value::<MainReport>(older).unwrap(),
+            expected,
+            "Added fields can be missing"
+        );
     }
 }
@@ -0,0 +1,521 @@
+//! Recordings of the reports received from the Vita.
+//!
+//! A recording starts with the magic bytes `VOXR`, the format version and the header,
+//! followed by the reports. Every report is prefixed by its length. Newer versions only
+//! add fields at the end of the reports, so readers skip the fields they do not know
+//! and read recordings of any version. All the numbers are little endian.
+//!
+//! Version 2 adds the sample timestamps of the motion and touches at the end of a report,
+//! the timestamp of the report is used instead in older recordings. Version 3 adds the
+//! batched motion samples after them, they are empty in older recordings. Version 4 adds
+//! the session and the sequence number of the packet, 0 in older recordings.
+//!
+//! Reports are recorded as they were received, before dropping the late ones and
+//! resynchronising the timestamps, so a replay goes through the same steps.
+
+use std::io::{self, Read, Write};
+
+use crate::{MainReport, MotionData, TouchData, TouchReport, Vector3, VitaButtons};
+
+const MAGIC: &[u8; 4] = b"VOXR";
+
+/// Version of the format written by [`RecordingWriter`].
+pub const FORMAT_VERSION: u16 = 4;
+
+/// Session details stored at the start of a recording.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecordingHeader {
+    /// Version of the client that made the recording.
+    pub client_version: String,
+    /// Start of the recording, in milliseconds since the Unix epoch.
+    pub started_at: u64,
+    /// Polling interval requested from the Vita, in microseconds.
+    pub polling_interval: u64,
+    /// Heartbeat frequency from the handshake, in seconds.
+    pub heartbeat_freq: u32,
+    /// Name of the built-in configuration.
+    pub configuration: String,
+    /// Profile overrides as TOML.
+    pub profile: String,
+}
+
+/// Report of a recording.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RecordedReport {
+    /// Time the report was received, in microseconds since the start of the recording.
+    pub received_at: u64,
+    pub report: MainReport,
+}
+
+#[inline]
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn write_string(out: &mut Vec<u8>, value: &str) -> io::Result<()> {
+    let len = u32::try_from(value.len()).map_err(|_| invalid_data("String is too long"))?;
+    out.extend_from_slice(&len.to_le_bytes());
+    out.extend_from_slice(value.as_bytes());
+    Ok(())
+}
+
+fn write_touches(out: &mut Vec<u8>, touch: &TouchData) {
+    // The Vita reports at most 6 contacts per panel
+    let count = touch.reports.len().min(u8::MAX as usize);
+    out.push(count as u8);
+    for report in &touch.reports[..count] {
+        out.extend_from_slice(&report.x.to_le_bytes());
+        out.extend_from_slice(&report.y.to_le_bytes());
+        out.push(report.id);
+        out.push(report.force);
+    }
+}
+
+fn write_vector(out: &mut Vec<u8>, vector: &Vector3) {
+    for value in [vector.x, vector.y, vector.z] {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+/// Writes reports to a recording.
+pub struct RecordingWriter<W: Write> {
+    writer: W,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> RecordingWriter<W> {
+    /// Writes the start of the recording.
+    pub fn new(mut writer: W, header: &RecordingHeader) -> io::Result<Self> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        write_string(&mut out, &header.client_version)?;
+        out.extend_from_slice(&header.started_at.to_le_bytes());
+        out.extend_from_slice(&header.polling_interval.to_le_bytes());
+        out.extend_from_slice(&header.heartbeat_freq.to_le_bytes());
+        write_string(&mut out, &header.configuration)?;
+        write_string(&mut out, &header.profile)?;
+        writer.write_all(&out)?;
+
+        Ok(RecordingWriter {
+            writer,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Appends a report received `received_at` microseconds after the start of the recording.
+    pub fn write(&mut self, received_at: u64, report: &MainReport) -> io::Result<()> {
+        let out = &mut self.buffer;
+        out.clear();
+        // Length placeholder
+        out.extend_from_slice(&[0, 0]);
+        out.extend_from_slice(&received_at.to_le_bytes());
+        out.extend_from_slice(&report.timestamp.to_le_bytes());
+        out.extend_from_slice(&VitaButtons::from(&report.buttons).bits().to_le_bytes());
+        out.extend_from_slice(&[report.lx, report.ly, report.rx, report.ry]);
+        write_touches(out, &report.front_touch);
+        write_touches(out, &report.back_touch);
+        write_vector(out, &report.motion.gyro);
+        write_vector(out, &report.motion.accelerometer);
+        out.push(report.charge_percent);
+        out.extend_from_slice(&report.motion.timestamp.to_le_bytes());
+        for touch in [&report.front_touch, &report.back_touch] {
+            for touch_report in touch.reports.iter().take(u8::MAX as usize) {
+                out.extend_from_slice(&touch_report.timestamp.to_le_bytes());
+            }
+        }
+        let samples = &report.motion_samples[..report.motion_samples.len().min(u8::MAX as usize)];
+        out.push(samples.len() as u8);
+        for sample in samples {
+            write_vector(out, &sample.gyro);
+            write_vector(out, &sample.accelerometer);
+            out.extend_from_slice(&sample.timestamp.to_le_bytes());
+        }
+        out.extend_from_slice(&report.session.to_le_bytes());
+        out.extend_from_slice(&report.sequence.to_le_bytes());
+
+        let len = u16::try_from(out.len() - 2).map_err(|_| invalid_data("Report is too long"))?;
+        out[..2].copy_from_slice(&len.to_le_bytes());
+        self.writer.write_all(out)
+    }
+
+    #[inline]
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Reads the fields of a record, failing if it ends too early.
+struct Fields<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Fields<'a> {
+    fn take<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        if self.data.len() < N {
+            return Err(invalid_data("Truncated report"));
+        }
+        let (bytes, rest) = self.data.split_at(N);
+        self.data = rest;
+        Ok(bytes.try_into().expect("Length is checked"))
+    }
+
+    #[inline]
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    #[inline]
+    fn u16(&mut self) -> io::Result<u16> {
+        self.take().map(u16::from_le_bytes)
+    }
+
+    #[inline]
+    fn u32(&mut self) -> io::Result<u32> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    #[inline]
+    fn u64(&mut self) -> io::Result<u64> {
+        self.take().map(u64::from_le_bytes)
+    }
+
+    #[inline]
+    fn f32(&mut self) -> io::Result<f32> {
+        self.take().map(f32::from_le_bytes)
+    }
+
+    fn touches(&mut self) -> io::Result<TouchData> {
+        let count = self.u8()?;
+        let reports = (0..count)
+            .map(|_| {
+                Ok(TouchReport {
+                    x: self.u16()?,
+                    y: self.u16()?,
+                    id: self.u8()?,
+                    force: self.u8()?,
+                    timestamp: 0,
+                })
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(TouchData { reports })
+    }
+
+    fn vector(&mut self) -> io::Result<Vector3> {
+        Ok(Vector3 {
+            x: self.f32()?,
+            y: self.f32()?,
+            z: self.f32()?,
+        })
+    }
+}
+
+fn read_array<R: Read, const N: usize>(reader: &mut R) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_string<R: Read>(reader: &mut R) -> io::Result<String> {
+    let len = u32::from_le_bytes(read_array(reader)?);
+    let mut bytes = Vec::new();
+    reader.take(len.into()).read_to_end(&mut bytes)?;
+    if bytes.len() != len as usize {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    String::from_utf8(bytes).map_err(|_| invalid_data("Invalid UTF-8 string"))
+}
+
+/// Reads the reports of a recording.
+///
+/// Iterating over the reader returns the reports in the order they were received.
+pub struct RecordingReader<R: Read> {
+    reader: R,
+    header: RecordingHeader,
+    version: u16,
+    buffer: Vec<u8>,
+}
+
+impl<R: Read> RecordingReader<R> {
+    /// Reads the start of the recording.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let magic: [u8; 4] = read_array(&mut reader)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("Not a VitaOxiPad recording"));
+        }
+        let version = u16::from_le_bytes(read_array(&mut reader)?);
+        if version == 0 {
+            return Err(invalid_data("Unsupported recording version"));
+        }
+
+        let header = RecordingHeader {
+            client_version: read_string(&mut reader)?,
+            started_at: u64::from_le_bytes(read_array(&mut reader)?),
+            polling_interval: u64::from_le_bytes(read_array(&mut reader)?),
+            heartbeat_freq: u32::from_le_bytes(read_array(&mut reader)?),
+            configuration: read_string(&mut reader)?,
+            profile: read_string(&mut reader)?,
+        };
+
+        Ok(RecordingReader {
+            reader,
+            header,
+            version,
+            buffer: Vec::new(),
+        })
+    }
+
+    #[inline]
+    pub fn header(&self) -> &RecordingHeader {
+        &self.header
+    }
+
+    /// Format version of the recording.
+    #[inline]
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    /// Reads the next report, `None` at the end of the recording.
+    pub fn read_report(&mut self) -> io::Result<Option<RecordedReport>> {
+        let mut len = [0; 2];
+        match self.reader.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        self.buffer.resize(u16::from_le_bytes(len).into(), 0);
+        self.reader.read_exact(&mut self.buffer)?;
+
+        let mut fields = Fields { data: &self.buffer };
+        let received_at = fields.u64()?;
+        let timestamp = fields.u64()?;
+        let buttons = VitaButtons::from_bits_truncate(fields.u16()?).into();
+        let [lx, ly, rx, ry] = fields.take()?;
+        let mut front_touch = fields.touches()?;
+        let mut back_touch = fields.touches()?;
+        let mut motion = MotionData {
+            gyro: fields.vector()?,
+            accelerometer: fields.vector()?,
+            timestamp,
+        };
+        let charge_percent = fields.u8()?;
+        let touch_reports = front_touch
+            .reports
+            .iter_mut()
+            .chain(&mut back_touch.reports);
+        if self.version >= 2 {
+            motion.timestamp = fields.u64()?;
+            for touch_report in touch_reports {
+                touch_report.timestamp = fields.u64()?;
+            }
+        } else {
+            for touch_report in touch_reports {
+                touch_report.timestamp = timestamp;
+            }
+        }
+        let mut motion_samples = Vec::new();
+        if self.version >= 3 {
+            let count = fields.u8()?;
+            for _ in 0..count {
+                motion_samples.push(MotionData {
+                    gyro: fields.vector()?,
+                    accelerometer: fields.vector()?,
+                    timestamp: fields.u64()?,
+                });
+            }
+        }
+        let (session, sequence) = if self.version >= 4 {
+            (fields.u32()?, fields.u32()?)
+        } else {
+            (0, 0)
+        };
+
+        Ok(Some(RecordedReport {
+            received_at,
+            report: MainReport {
+                buttons,
+                lx,
+                ly,
+                rx,
+                ry,
+                front_touch,
+                back_touch,
+                motion,
+                timestamp,
+                charge_percent,
+                motion_samples,
+                session,
+                sequence,
+            },
+        }))
+    }
+}
+
+impl<R: Read> Iterator for RecordingReader<R> {
+    type Item = io::Result<RecordedReport>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_report().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ButtonsData;
+
+    fn header() -> RecordingHeader {
+        RecordingHeader {
+            client_version: "1.3.0".to_string(),
+            started_at: 1_700_000_000_000,
+            polling_interval: 6000,
+            heartbeat_freq: 30,
+            configuration: "standart".to_string(),
+            profile: "[left_stick]\ninner_deadzone = 0.1\n".to_string(),
+        }
+    }
+
+    fn report() -> MainReport {
+        MainReport {
+            buttons: ButtonsData {
+                cross: true,
+                ps: true,
+                ..Default::default()
+            },
+            lx: 1,
+            ly: 2,
+            rx: 254,
+            ry: 255,
+            front_touch: TouchData {
+                reports: vec![TouchReport {
+                    x: 1919,
+                    y: 1087,
+                    id: 3,
+                    force: 64,
+                    timestamp: 123_450_000,
+                }],
+            },
+            motion: MotionData {
+                gyro: Vector3 {
+                    x: 0.5,
+                    y: -1.25,
+                    z: 35.0,
+                },
+                accelerometer: Vector3 {
+                    x: 0.0,
+                    y: 0.0,
+                    z: -1.0,
+                },
+                timestamp: 123_455_000,
+            },
+            timestamp: 123_456_789,
+            charge_percent: 87,
+            session: 0x1234_5678,
+            sequence: 42,
+            ..Default::default()
+        }
+    }
+
+    /// Reads `report()` written with an older version, without the fields added later.
+    fn read_older_version(version: u16, added_len: u16) -> MainReport {
+        let mut writer = RecordingWriter::new(Vec::new(), &header()).unwrap();
+        let start = writer.writer.len();
+        writer.write(0, &report()).unwrap();
+        let mut data = writer.into_inner();
+
+        data[4..6].copy_from_slice(&version.to_le_bytes());
+        data.truncate(data.len() - added_len as usize);
+        let len = u16::from_le_bytes([data[start], data[start + 1]]) - added_len;
+        data[start..start + 2].copy_from_slice(&len.to_le_bytes());
+
+        RecordingReader::new(data.as_slice())
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .report
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let header = header();
+        let report = MainReport {
+            motion_samples: vec![
+                MotionData {
+                    timestamp: 123_451_000,
+                    ..report().motion
+                },
+                report().motion,
+            ],
+            ..report()
+        };
+        let mut writer = RecordingWriter::new(Vec::new(), &header).unwrap();
+        writer.write(0, &MainReport::default()).unwrap();
+        writer.write(6_000, &report).unwrap();
+        let data = writer.into_inner();
+
+        let mut reader = RecordingReader::new(data.as_slice()).unwrap();
+        assert_eq!(reader.header(), &header);
+        assert_eq!(reader.version(), FORMAT_VERSION);
+        let reports: Vec<RecordedReport> = reader.by_ref().collect::<io::Result<_>>().unwrap();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[1].received_at, 6_000);
+        assert_eq!(reports[1].report, report);
+
+        assert!(
+            RecordingReader::new(&data[..3]).is_err(),
+            "Truncated header should be rejected"
+        );
+        let truncated = RecordingReader::new(&data[..data.len() - 1]).unwrap();
+        assert!(
+            truncated.last().unwrap().is_err(),
+            "Truncated report should be an error"
+        );
+    }
+
+    #[test]
+    fn test_newer_version() {
+        let mut writer = RecordingWriter::new(Vec::new(), &header()).unwrap();
+        let start = writer.writer.len();
+        writer.write(0, &report()).unwrap();
+        let mut data = writer.into_inner();
+
+        // Newer version with a field added at the end of the report
+        data[4..6].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        data.extend_from_slice(&[0xAB; 5]);
+        let len = u16::from_le_bytes([data[start], data[start + 1]]) + 5;
+        data[start..start + 2].copy_from_slice(&len.to_le_bytes());
+
+        let mut reader = RecordingReader::new(data.as_slice()).unwrap();
+        assert_eq!(reader.version(), FORMAT_VERSION + 1);
+        assert_eq!(reader.next().unwrap().unwrap().report, report());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_older_versions() {
+        let unsequenced = MainReport {
+            session: 0,
+            sequence: 0,
+            ..report()
+        };
+        // Without the session and the sequence number
+        assert_eq!(read_older_version(3, 8), unsequenced);
+        // Without the count of motion samples either
+        assert_eq!(read_older_version(2, 9), unsequenced);
+
+        // Without the motion and touch timestamps either
+        let recorded = read_older_version(1, 25);
+        assert_eq!(recorded.motion.timestamp, 123_456_789);
+        assert_eq!(recorded.front_touch.reports[0].timestamp, 123_456_789);
+        assert_eq!(recorded.motion.gyro, report().motion.gyro);
+        assert!(recorded.motion_samples.is_empty());
+    }
+}
@@ -1,11 +1,47 @@
 use std::ffi::OsString;
+use std::time::Duration;
 
 mod virtual_button;
+mod virtual_button_mode;
 mod virtual_config;
+mod virtual_flick;
+mod virtual_gesture;
+mod virtual_gyro;
+mod virtual_key;
+mod virtual_macro;
+mod virtual_mirror;
+mod virtual_motion;
+mod virtual_mouse;
+mod virtual_stick;
+mod virtual_tablet;
 mod virtual_touch;
+mod virtual_touch_filter;
 mod virtual_utils;
 
-pub use virtual_touch::Point;
+pub use virtual_button::{Button, DpadDirection, VitaButton};
+pub use virtual_button_mode::ButtonMode;
+pub use virtual_config::{
+    Config, ConfigBuilder, EdgeBands, TabletConfig, TouchConfig, TouchMouseConfig,
+    TouchRejectionConfig,
+};
+pub use virtual_flick::{FlickSnap, FlickStickConfig};
+pub use virtual_gesture::{Gesture, GestureConfig, GestureDetector, TiltDirection};
+pub use virtual_gyro::{
+    GyroActivation, GyroMouseConfig, GyroSpace, GyroStickConfig, GyroStickInput, StickMix,
+};
+pub use virtual_key::{KeyCombo, KeyboardKey};
+pub use virtual_macro::{Macro, MacroRecorder, MacroStep};
+pub use virtual_mirror::MirrorConfig;
+pub use virtual_motion::{
+    MotionConfig, MotionLayout, MotionProcessor, MotionState, OrientationFilter, Quaternion,
+    SignedAxis,
+};
+pub use virtual_mouse::MouseButton;
+pub use virtual_stick::{
+    DeadzoneShape, Stick, StickCalibration, StickCalibrator, StickConfig, StickCurve,
+};
+pub use virtual_tablet::TabletButton;
+pub use virtual_touch::{Point, TouchAction};
 
 // Error handling that includes platform-specific errors
 #[derive(thiserror::Error, Debug)]
@@ -31,6 +67,11 @@ pub trait VitaVirtualDevice<ConfigSetter: ?Sized>: Sized {
     fn identifiers(&self) -> Option<&[OsString]>;
     fn set_config(&mut self, config: ConfigSetter) -> Result<()>;
     fn send_report(&mut self, report: vita_reports::MainReport) -> Result<()>;
+    /// Returns how long to wait before calling [`VitaVirtualDevice::tick`],
+    /// or `None` if no output changes over time.
+    fn next_tick(&self) -> Option<Duration>;
+    /// Updates the outputs that change over time, like turbo buttons, between reports.
+    fn tick(&mut self) -> Result<()>;
 }
 
 /// Helper function to convert a `f32` value to `i16` within specified bounds.
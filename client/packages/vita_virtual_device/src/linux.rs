@@ -6,19 +6,36 @@ use std::{
     os::fd::AsRawFd,
 };
 
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use input_linux::{
     sys::{input_event, BUS_VIRTUAL},
     AbsoluteAxis, AbsoluteEvent, AbsoluteInfo, AbsoluteInfoSetup, EventKind, EventTime, InputEvent,
-    InputId, InputProperty, Key, KeyEvent, KeyState, SynchronizeEvent, UInputHandle,
+    InputId, InputProperty, Key, KeyEvent, KeyState, MiscEvent, MiscKind, RelativeAxis,
+    RelativeEvent, SynchronizeEvent, UInputHandle,
 };
 
 use crate::virtual_button::{Button, DpadDirection};
-use crate::virtual_config::{Config, ConfigBuilder, TouchConfig, TouchpadSource};
-use crate::virtual_touch::{Point, TouchAction};
-use crate::virtual_utils::{compute_dpad_direction, get_pressed_buttons};
-use crate::{f32_to_i16, VitaVirtualDevice, FRONT_TOUCHPAD_RECT, REAR_TOUCHPAD_RECT};
+use crate::virtual_config::{Config, ConfigBuilder, TabletConfig, TouchConfig, TouchpadSource};
+use crate::virtual_mouse::{MouseButton, MouseOutput, TouchMouse};
+use crate::virtual_tablet::{
+    compute_tablet_state, TabletState, TabletTool, FRONT_TOUCHPAD_SIZE_MM, TABLET_MAX_PRESSURE,
+};
+use crate::virtual_button_mode::ButtonModes;
+use crate::virtual_flick::FlickStick;
+use crate::virtual_gesture::GestureDetector;
+use crate::virtual_gyro::{GyroMouse, GyroStick};
+use crate::virtual_key::{HeldKeys, KeyboardKey};
+use crate::virtual_macro::MacroPlayer;
+use crate::virtual_motion::{MotionConfig, MotionProcessor};
+use crate::virtual_stick::{process_stick, StickConfig};
+use crate::virtual_touch::TouchAction;
+use crate::virtual_touch_filter::TouchFilter;
+use crate::virtual_utils::{
+    compute_dpad_direction, get_pressed_buttons, process_touch_reports, take_binding_actions,
+    LastReport,
+};
+use crate::{VitaVirtualDevice, FRONT_TOUCHPAD_RECT, REAR_TOUCHPAD_RECT};
 
 type TrackingId = u8;
 
@@ -43,6 +60,13 @@ const REAR_TOUCHPAD_MAX_X: i32 = REAR_TOUCHPAD_RECT.1 .0 - 1;
 const REAR_TOUCHPAD_MAX_Y: i32 = REAR_TOUCHPAD_RECT.1 .1 - 1;
 const REAR_TOUCHPAD_MAX_SLOTS: usize = 4;
 
+const DEVICE_ID: InputId = InputId {
+    bustype: BUS_VIRTUAL,
+    vendor: 0x054C,
+    product: 0x9CC,
+    version: 0x8111,
+};
+
 fn get_current_event_time() -> EventTime {
     let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
     EventTime::new(now.as_secs() as i64, now.subsec_nanos() as i64)
@@ -66,6 +90,195 @@ fn map_button_to_ds4(button: Button) -> Key {
     }
 }
 
+fn map_keyboard_key(key: KeyboardKey) -> Key {
+    match key {
+        KeyboardKey::A => Key::A,
+        KeyboardKey::B => Key::B,
+        KeyboardKey::C => Key::C,
+        KeyboardKey::D => Key::D,
+        KeyboardKey::E => Key::E,
+        KeyboardKey::F => Key::F,
+        KeyboardKey::G => Key::G,
+        KeyboardKey::H => Key::H,
+        KeyboardKey::I => Key::I,
+        KeyboardKey::J => Key::J,
+        KeyboardKey::K => Key::K,
+        KeyboardKey::L => Key::L,
+        KeyboardKey::M => Key::M,
+        KeyboardKey::N => Key::N,
+        KeyboardKey::O => Key::O,
+        KeyboardKey::P => Key::P,
+        KeyboardKey::Q => Key::Q,
+        KeyboardKey::R => Key::R,
+        KeyboardKey::S => Key::S,
+        KeyboardKey::T => Key::T,
+        KeyboardKey::U => Key::U,
+        KeyboardKey::V => Key::V,
+        KeyboardKey::W => Key::W,
+        KeyboardKey::X => Key::X,
+        KeyboardKey::Y => Key::Y,
+        KeyboardKey::Z => Key::Z,
+        KeyboardKey::Num0 => Key::Num0,
+        KeyboardKey::Num1 => Key::Num1,
+        KeyboardKey::Num2 => Key::Num2,
+        KeyboardKey::Num3 => Key::Num3,
+        KeyboardKey::Num4 => Key::Num4,
+        KeyboardKey::Num5 => Key::Num5,
+        KeyboardKey::Num6 => Key::Num6,
+        KeyboardKey::Num7 => Key::Num7,
+        KeyboardKey::Num8 => Key::Num8,
+        KeyboardKey::Num9 => Key::Num9,
+        KeyboardKey::F1 => Key::F1,
+        KeyboardKey::F2 => Key::F2,
+        KeyboardKey::F3 => Key::F3,
+        KeyboardKey::F4 => Key::F4,
+        KeyboardKey::F5 => Key::F5,
+        KeyboardKey::F6 => Key::F6,
+        KeyboardKey::F7 => Key::F7,
+        KeyboardKey::F8 => Key::F8,
+        KeyboardKey::F9 => Key::F9,
+        KeyboardKey::F10 => Key::F10,
+        KeyboardKey::F11 => Key::F11,
+        KeyboardKey::F12 => Key::F12,
+        KeyboardKey::F13 => Key::F13,
+        KeyboardKey::F14 => Key::F14,
+        KeyboardKey::F15 => Key::F15,
+        KeyboardKey::F16 => Key::F16,
+        KeyboardKey::F17 => Key::F17,
+        KeyboardKey::F18 => Key::F18,
+        KeyboardKey::F19 => Key::F19,
+        KeyboardKey::F20 => Key::F20,
+        KeyboardKey::F21 => Key::F21,
+        KeyboardKey::F22 => Key::F22,
+        KeyboardKey::F23 => Key::F23,
+        KeyboardKey::F24 => Key::F24,
+        KeyboardKey::Escape => Key::Esc,
+        KeyboardKey::Enter => Key::Enter,
+        KeyboardKey::Tab => Key::Tab,
+        KeyboardKey::Space => Key::Space,
+        KeyboardKey::Backspace => Key::Backspace,
+        KeyboardKey::Delete => Key::Delete,
+        KeyboardKey::Insert => Key::Insert,
+        KeyboardKey::Home => Key::Home,
+        KeyboardKey::End => Key::End,
+        KeyboardKey::PageUp => Key::PageUp,
+        KeyboardKey::PageDown => Key::PageDown,
+        KeyboardKey::Up => Key::Up,
+        KeyboardKey::Down => Key::Down,
+        KeyboardKey::Left => Key::Left,
+        KeyboardKey::Right => Key::Right,
+        KeyboardKey::Minus => Key::Minus,
+        KeyboardKey::Equal => Key::Equal,
+        KeyboardKey::LeftBracket => Key::LeftBrace,
+        KeyboardKey::RightBracket => Key::RightBrace,
+        KeyboardKey::Backslash => Key::Backslash,
+        KeyboardKey::Semicolon => Key::Semicolon,
+        KeyboardKey::Apostrophe => Key::Apostrophe,
+        KeyboardKey::Grave => Key::Grave,
+        KeyboardKey::Comma => Key::Comma,
+        KeyboardKey::Period => Key::Dot,
+        KeyboardKey::Slash => Key::Slash,
+        KeyboardKey::CapsLock => Key::CapsLock,
+        KeyboardKey::PrintScreen => Key::Sysrq,
+        KeyboardKey::ScrollLock => Key::ScrollLock,
+        KeyboardKey::Pause => Key::Pause,
+        KeyboardKey::LeftCtrl => Key::LeftCtrl,
+        KeyboardKey::RightCtrl => Key::RightCtrl,
+        KeyboardKey::LeftShift => Key::LeftShift,
+        KeyboardKey::RightShift => Key::RightShift,
+        KeyboardKey::LeftAlt => Key::LeftAlt,
+        KeyboardKey::RightAlt => Key::RightAlt,
+        KeyboardKey::LeftMeta => Key::LeftMeta,
+        KeyboardKey::RightMeta => Key::RightMeta,
+        KeyboardKey::VolumeUp => Key::VolumeUp,
+        KeyboardKey::VolumeDown => Key::VolumeDown,
+        KeyboardKey::Mute => Key::Mute,
+        KeyboardKey::PlayPause => Key::PlayPause,
+        KeyboardKey::NextTrack => Key::NextSong,
+        KeyboardKey::PreviousTrack => Key::PreviousSong,
+        KeyboardKey::Stop => Key::StopCD,
+    }
+}
+
+fn map_mouse_button(button: MouseButton) -> Key {
+    match button {
+        MouseButton::Left => Key::ButtonLeft,
+        MouseButton::Right => Key::ButtonRight,
+        MouseButton::Middle => Key::ButtonMiddle,
+    }
+}
+
+#[inline]
+fn key_state(pressed: bool) -> KeyState {
+    if pressed {
+        KeyState::PRESSED
+    } else {
+        KeyState::RELEASED
+    }
+}
+
+/// Creates the pen tablet device, its axes cover the whole screen.
+fn create_tablet_device<F: AsRawFd>(
+    tablet_handle: &UInputHandle<F>,
+    config: &TabletConfig,
+) -> std::io::Result<()> {
+    tablet_handle.set_evbit(EventKind::Key)?;
+    tablet_handle.set_evbit(EventKind::Absolute)?;
+    tablet_handle.set_propbit(InputProperty::Pointer)?;
+    for key in [
+        Key::ButtonToolPen,
+        Key::ButtonToolRubber,
+        Key::ButtonTouch,
+        Key::ButtonStylus,
+        Key::ButtonStylus2,
+    ] {
+        tablet_handle.set_keybit(key)?;
+    }
+
+    let (width, height) = config.screen_size;
+    let tablet_axes = [
+        AbsoluteInfoSetup {
+            info: AbsoluteInfo {
+                minimum: 0,
+                maximum: width - 1,
+                resolution: (width / FRONT_TOUCHPAD_SIZE_MM.0).max(1),
+                ..Default::default()
+            },
+            axis: AbsoluteAxis::X,
+        },
+        AbsoluteInfoSetup {
+            info: AbsoluteInfo {
+                minimum: 0,
+                maximum: height - 1,
+                resolution: (height / FRONT_TOUCHPAD_SIZE_MM.1).max(1),
+                ..Default::default()
+            },
+            axis: AbsoluteAxis::Y,
+        },
+        AbsoluteInfoSetup {
+            info: AbsoluteInfo {
+                minimum: 0,
+                maximum: TABLET_MAX_PRESSURE,
+                ..Default::default()
+            },
+            axis: AbsoluteAxis::Pressure,
+        },
+    ];
+
+    tablet_handle.create(&DEVICE_ID, b"PS Vita VitaOxiPad (Tablet)", 0, &tablet_axes)
+}
+
+/// Rejects the parts of the configuration this backend can not output.
+fn check_config(config: &Config) -> Result<(), Error> {
+    if let Some(TouchConfig::Tablet(_)) = config.rear_touch_config {
+        return Err(Error::InvalidConfig(
+            "tablet mode is only supported on the front touch panel".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
 /// Converts DpadDirection to axis values suitable for uinput.
 pub fn dpad_direction_to_axis_values(direction: DpadDirection) -> (i32, i32) {
     match direction {
@@ -81,37 +294,37 @@ pub fn dpad_direction_to_axis_values(direction: DpadDirection) -> (i32, i32) {
     }
 }
 
-/// Processes touch reports and returns a list of touch actions.
-pub fn process_touch_reports(
-    touch_reports: &[vita_reports::TouchReport],
-    touch_config: &Option<TouchConfig>,
-) -> Vec<TouchAction> {
-    let mut actions = Vec::new();
-    if let Some(TouchConfig::Zones(zones)) = touch_config {
-        for touch in touch_reports {
-            if let Some(zone) = zones.locate_at_point(&Point(touch.x.into(), touch.y.into())) {
-                if let Some(action) = zone.action {
-                    actions.push(action);
-                }
-            }
-        }
-    }
-    actions
-}
-
 pub struct VitaDevice<F: AsRawFd> {
     config: Config,
     main_handle: UInputHandle<F>,
     touchpad_handle: UInputHandle<F>,
     sensor_handle: UInputHandle<F>,
     keyboard_handle: UInputHandle<F>,
+    mouse_handle: UInputHandle<F>,
+    /// Handle on the uinput file of the tablet, the device is created when the tablet is used.
+    tablet_handle: Option<UInputHandle<F>>,
+    /// Screen size the tablet device was created for, `None` until it is created.
+    tablet_screen_size: Option<(i32, i32)>,
     previous_front_touches: Vec<Option<TrackingId>>,
     previous_rear_touches: Vec<Option<TrackingId>>,
+    front_touch_filter: TouchFilter,
+    rear_touch_filter: TouchFilter,
+    front_touch_mouse: TouchMouse,
+    rear_touch_mouse: TouchMouse,
     touch_state: bool,
     ids: Option<Vec<OsString>>,
     previous_buttons: HashSet<Button>,
     previous_hat_x: i32,
     previous_hat_y: i32,
+    held_keys: HeldKeys,
+    macro_player: MacroPlayer,
+    button_modes: ButtonModes,
+    last_report: LastReport,
+    motion_processor: MotionProcessor,
+    gyro_mouse: GyroMouse,
+    gyro_stick: GyroStick,
+    flick_stick: FlickStick,
+    gesture_detector: GestureDetector,
 }
 
 impl<F: AsRawFd> VitaDevice<F> {
@@ -120,15 +333,12 @@ impl<F: AsRawFd> VitaDevice<F> {
         uinput_sensor_file: F,
         uinput_touchpad_file: F,
         uinput_keyboard_file: F,
+        uinput_mouse_file: F,
+        uinput_tablet_file: Option<F>,
         config: Config,
     ) -> std::io::Result<Self> {
         let main_handle = UInputHandle::new(uinput_file);
-        let id = InputId {
-            bustype: BUS_VIRTUAL,
-            vendor: 0x054C,
-            product: 0x9CC,
-            version: 0x8111,
-        };
+        let id = DEVICE_ID;
 
         // Configure main device
         main_handle.set_evbit(EventKind::Key)?;
@@ -151,8 +361,9 @@ impl<F: AsRawFd> VitaDevice<F> {
         }
         main_handle.set_evbit(EventKind::Absolute)?;
 
-        let joystick_abs_info = AbsoluteInfo {
-            flat: 128,
+        // Processed sticks already have their deadzone
+        let joystick_abs_info = |stick: &Option<StickConfig>| AbsoluteInfo {
+            flat: if stick.is_some() { 0 } else { 128 },
             fuzz: 0,
             maximum: 255,
             minimum: 0,
@@ -171,19 +382,19 @@ impl<F: AsRawFd> VitaDevice<F> {
         // Sticks
         let joystick_axes = [
             AbsoluteInfoSetup {
-                info: joystick_abs_info,
+                info: joystick_abs_info(&config.left_stick),
                 axis: AbsoluteAxis::X,
             },
             AbsoluteInfoSetup {
-                info: joystick_abs_info,
+                info: joystick_abs_info(&config.left_stick),
                 axis: AbsoluteAxis::Y,
             },
             AbsoluteInfoSetup {
-                info: joystick_abs_info,
+                info: joystick_abs_info(&config.right_stick),
                 axis: AbsoluteAxis::RX,
             },
             AbsoluteInfoSetup {
-                info: joystick_abs_info,
+                info: joystick_abs_info(&config.right_stick),
                 axis: AbsoluteAxis::RY,
             },
         ];
@@ -300,15 +511,25 @@ impl<F: AsRawFd> VitaDevice<F> {
         let sensor_handle = UInputHandle::new(uinput_sensor_file);
 
         sensor_handle.set_evbit(EventKind::Absolute)?;
+        sensor_handle.set_evbit(EventKind::Misc)?;
+        sensor_handle.set_mscbit(MiscKind::Timestamp)?;
         sensor_handle.set_propbit(InputProperty::Accelerometer)?;
 
+        // Resolutions in units per g and per degree per second
+        let accel_range = config.motion.accelerometer_range.abs().max(f32::EPSILON);
+        let gyro_range = config.motion.gyro_range.abs().to_degrees().max(f32::EPSILON);
+
         let accel_abs_info = AbsoluteInfo {
             minimum: -32768,
             maximum: 32768,
+            resolution: (32768.0 / accel_range) as i32,
             ..Default::default()
         };
 
-        let gyro_abs_info = accel_abs_info;
+        let gyro_abs_info = AbsoluteInfo {
+            resolution: (32768.0 / gyro_range) as i32,
+            ..accel_abs_info
+        };
 
         let sensor_axes = [
             AbsoluteInfoSetup {
@@ -341,49 +562,205 @@ impl<F: AsRawFd> VitaDevice<F> {
 
         let keyboard_handle = UInputHandle::new(uinput_keyboard_file);
         keyboard_handle.set_evbit(EventKind::Key)?;
-        keyboard_handle.set_keybit(Key::VolumeUp)?;
-        keyboard_handle.set_keybit(Key::VolumeDown)?;
+        for &key in KeyboardKey::ALL {
+            keyboard_handle.set_keybit(map_keyboard_key(key))?;
+        }
         keyboard_handle.create(&id, b"PS Vita VitaOxiPad (Virtual Keyboard)", 0, &[])?;
 
+        // Configure mouse device
+        let mouse_handle = UInputHandle::new(uinput_mouse_file);
+        mouse_handle.set_evbit(EventKind::Key)?;
+        mouse_handle.set_evbit(EventKind::Relative)?;
+        for button in [MouseButton::Left, MouseButton::Right, MouseButton::Middle] {
+            mouse_handle.set_keybit(map_mouse_button(button))?;
+        }
+        for axis in [
+            RelativeAxis::X,
+            RelativeAxis::Y,
+            RelativeAxis::Wheel,
+            RelativeAxis::HorizontalWheel,
+        ] {
+            mouse_handle.set_relbit(axis)?;
+        }
+        mouse_handle.create(&id, b"PS Vita VitaOxiPad (Mouse)", 0, &[])?;
+
         let ids = main_handle
             .evdev_name()
             .ok()
             .zip(touchpad_handle.evdev_name().ok())
             .zip(sensor_handle.evdev_name().ok())
             .zip(keyboard_handle.evdev_name().ok())
-            .map(|(((main, touchpad), sensor), keyboard)| [main, touchpad, sensor, keyboard].to_vec());
+            .zip(mouse_handle.evdev_name().ok())
+            .map(|((((main, touchpad), sensor), keyboard), mouse)| {
+                [main, touchpad, sensor, keyboard, mouse].to_vec()
+            });
 
-        Ok(VitaDevice {
+        let tablet_config = config.tablet_config().cloned();
+        let mut device = VitaDevice {
             config,
             main_handle,
             touchpad_handle,
             sensor_handle,
             keyboard_handle,
+            mouse_handle,
+            tablet_handle: uinput_tablet_file.map(UInputHandle::new),
+            tablet_screen_size: None,
             previous_front_touches: vec![None; FRONT_TOUCHPAD_MAX_SLOTS],
             previous_rear_touches: vec![None; REAR_TOUCHPAD_MAX_SLOTS],
+            front_touch_filter: TouchFilter::default(),
+            rear_touch_filter: TouchFilter::default(),
+            front_touch_mouse: TouchMouse::default(),
+            rear_touch_mouse: TouchMouse::default(),
             touch_state: false,
             ids,
             previous_buttons: HashSet::new(),
             previous_hat_x: 0,
             previous_hat_y: 0,
-        })
+            held_keys: HeldKeys::default(),
+            macro_player: MacroPlayer::default(),
+            button_modes: ButtonModes::default(),
+            last_report: LastReport::default(),
+            motion_processor: MotionProcessor::default(),
+            gyro_mouse: GyroMouse::default(),
+            gyro_stick: GyroStick::default(),
+            flick_stick: FlickStick::default(),
+            gesture_detector: GestureDetector::default(),
+        };
+        device.update_tablet_device(tablet_config.as_ref())?;
+
+        Ok(device)
+    }
+
+    /// Creates the tablet device for the tablet configuration about to be used,
+    /// it is created again when the screen size changes.
+    fn update_tablet_device(
+        &mut self,
+        tablet_config: Option<&TabletConfig>,
+    ) -> std::io::Result<()> {
+        let (Some(tablet_handle), Some(tablet_config)) = (&self.tablet_handle, tablet_config)
+        else {
+            return Ok(());
+        };
+        if self.tablet_screen_size == Some(tablet_config.screen_size) {
+            return Ok(());
+        }
+
+        if self.tablet_screen_size.take().is_some() {
+            let name = tablet_handle.evdev_name().ok();
+            if let Some(ids) = &mut self.ids {
+                ids.retain(|id| Some(id) != name.as_ref());
+            }
+            tablet_handle.dev_destroy()?;
+        }
+
+        create_tablet_device(tablet_handle, tablet_config)?;
+        self.tablet_screen_size = Some(tablet_config.screen_size);
+        if let Some(ids) = &mut self.ids {
+            ids.extend(tablet_handle.evdev_name().ok());
+        }
+
+        Ok(())
+    }
+
+    /// Writes relative motion and clicks to the mouse device.
+    fn write_mouse_output(&self, output: &MouseOutput) -> Result<(), Error> {
+        if output.is_empty() {
+            return Ok(());
+        }
+
+        let syn_event = *SynchronizeEvent::report(get_current_event_time())
+            .as_event()
+            .as_raw();
+
+        let mut events: Vec<InputEvent> = [
+            (RelativeAxis::X, output.dx),
+            (RelativeAxis::Y, output.dy),
+            (RelativeAxis::Wheel, output.wheel),
+            (RelativeAxis::HorizontalWheel, output.hwheel),
+        ]
+        .into_iter()
+        .filter(|&(_, value)| value != 0)
+        .map(|(axis, value)| RelativeEvent::new(get_current_event_time(), axis, value).into())
+        .collect();
+
+        events.extend(output.clicks.iter().map(|&button| -> InputEvent {
+            KeyEvent::new(
+                get_current_event_time(),
+                map_mouse_button(button),
+                KeyState::PRESSED,
+            )
+            .into()
+        }));
+
+        let events: Vec<input_event> = events.iter().map(|ev| *ev.as_raw()).collect();
+        self.mouse_handle
+            .write(&events)
+            .map_err(Error::WriteEventFailed)?;
+        self.mouse_handle
+            .write(&[syn_event])
+            .map_err(Error::WriteEventFailed)?;
+
+        // Release clicked buttons in a separate frame so they are not merged with the press
+        if !output.clicks.is_empty() {
+            let events: Vec<input_event> = output
+                .clicks
+                .iter()
+                .map(|&button| {
+                    *InputEvent::from(KeyEvent::new(
+                        get_current_event_time(),
+                        map_mouse_button(button),
+                        KeyState::RELEASED,
+                    ))
+                    .as_raw()
+                })
+                .collect();
+            self.mouse_handle
+                .write(&events)
+                .map_err(Error::WriteEventFailed)?;
+            self.mouse_handle
+                .write(&[syn_event])
+                .map_err(Error::WriteEventFailed)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes gamepad events to the main and keyboard devices.
+    fn write_main_events(&self, events: &[InputEvent]) -> Result<(), Error> {
+        let syn_event = *SynchronizeEvent::report(get_current_event_time())
+            .as_event()
+            .as_raw();
+        let events: Vec<input_event> = events.iter().map(|ev| *ev.as_raw()).collect();
+
+        self.main_handle
+            .write(&events)
+            .map_err(Error::WriteEventFailed)?;
+        self.main_handle
+            .write(&[syn_event])
+            .map_err(Error::WriteEventFailed)?;
+
+        self.keyboard_handle
+            .write(&events)
+            .map_err(Error::WriteEventFailed)?;
+        self.keyboard_handle
+            .write(&[syn_event])
+            .map_err(Error::WriteEventFailed)?;
+
+        Ok(())
     }
 }
 
 impl VitaDevice<File> {
     pub fn create(config_name: &str) -> crate::Result<Self> {
         // Select the configuration depending on the name
-        let config = match config_name {
-            "standart" => Config::rear_rl2_front_rl3(),
-            "alt_triggers" => Config::rear_rl1_front_rl3_vitatriggers_rl2(),
-            "rear_touchpad" => Config::front_top_rl2_bottom_rl3_rear_touchpad(),
-            "front_touchpad" => Config::rear_top_rl2_bottom_rl3_front_touchpad(),
-            _ => {
-                return Err(crate::Error::Linux(Error::InvalidConfig(
-                    config_name.to_string(),
-                )))
-            }
-        };
+        let config = Config::from_name(config_name)
+            .ok_or_else(|| Error::InvalidConfig(config_name.to_string()))?;
+
+        Self::create_with_config(config)
+    }
+
+    pub fn create_with_config(config: Config) -> crate::Result<Self> {
+        check_config(&config)?;
 
         let uinput_file = OpenOptions::new()
             .read(true)
@@ -409,11 +786,26 @@ impl VitaDevice<File> {
             .open("/dev/uinput")
             .map_err(Error::DeviceCreationFailed)?;
 
+        let uinput_mouse_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/uinput")
+            .map_err(Error::DeviceCreationFailed)?;
+
+        // The tablet device is only created when it is used, also after a configuration change
+        let uinput_tablet_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/uinput")
+            .map_err(Error::DeviceCreationFailed)?;
+
         let device = Self::new(
             uinput_file,
             uinput_sensor_file,
             uinput_touchpad_file,
             uinput_keyboard_file,
+            uinput_mouse_file,
+            Some(uinput_tablet_file),
             config,
         )
         .map_err(Error::DeviceCreationFailed)?;
@@ -422,40 +814,282 @@ impl VitaDevice<File> {
     }
 }
 
-impl<F: AsRawFd + Write> VitaVirtualDevice<&ConfigBuilder> for VitaDevice<F> {
-    type Config = Config;
+impl<F: AsRawFd + Write> VitaDevice<F> {
+    fn process_report(&mut self, mut report: vita_reports::MainReport) -> crate::Result<()> {
+        // Drop unintended contacts before any touch processing
+        report.front_touch.reports = self.front_touch_filter.apply(
+            report.front_touch.reports,
+            report.timestamp,
+            &self.config.front_touch_rejection,
+            FRONT_TOUCHPAD_RECT,
+        );
+        report.back_touch.reports = self.rear_touch_filter.apply(
+            report.back_touch.reports,
+            report.timestamp,
+            &self.config.rear_touch_rejection,
+            REAR_TOUCHPAD_RECT,
+        );
 
-    fn identifiers(&self) -> Option<&[OsString]> {
-        self.ids.as_ref().map(|ids| ids.as_slice())
-    }
+        // Left-handed transforms, the calibrations stay with the physical sticks
+        self.config.mirror.apply(&mut report);
+        let (left_calibration, right_calibration) = if self.config.mirror.swap_sticks {
+            (
+                &self.config.right_stick_calibration,
+                &self.config.left_stick_calibration,
+            )
+        } else {
+            (
+                &self.config.left_stick_calibration,
+                &self.config.right_stick_calibration,
+            )
+        };
 
-    #[inline]
-    fn get_config(&self) -> &Self::Config {
-        &self.config
-    }
+        // Calibration, deadzones and curves of the sticks, before anything sets their position
+        process_stick(
+            &mut report.lx,
+            &mut report.ly,
+            left_calibration,
+            &self.config.left_stick,
+        );
+        process_stick(
+            &mut report.rx,
+            &mut report.ry,
+            right_calibration,
+            &self.config.right_stick,
+        );
 
-    #[inline]
-    fn set_config(&mut self, config: &ConfigBuilder) -> crate::Result<()> {
-        if let Some(front_touch_config) = &config.front_touch_config {
-            self.config.front_touch_config = front_touch_config.clone();
-        }
+        // Motion outputs use the gyro rates without their bias
+        let motion = self
+            .motion_processor
+            .update_report(&mut report, &self.config.motion);
+
+        // Gyro aiming, before its activation button reaches the other outputs
+        let mut aim_output = match &self.config.gyro_mouse {
+            Some(gyro_mouse_config) => self.gyro_mouse.update(
+                &motion,
+                &mut report.buttons,
+                report.motion.timestamp,
+                &self.config.motion,
+                gyro_mouse_config,
+            ),
+            None => MouseOutput::default(),
+        };
 
-        if let Some(rear_touch_config) = &config.rear_touch_config {
-            self.config.rear_touch_config = rear_touch_config.clone();
+        // Gyro stick, mixed with the physical stick
+        if let Some(gyro_stick_config) = &self.config.gyro_stick {
+            self.gyro_stick.update(
+                &motion,
+                &mut report,
+                &self.config.motion,
+                gyro_stick_config,
+            );
         }
 
-        if let Some(trigger_config) = config.trigger_config {
-            self.config.trigger_config = trigger_config;
+        // Flick stick, combined with the gyro aiming
+        if let Some(flick_stick_config) = &self.config.flick_stick {
+            aim_output.merge(self.flick_stick.update(&mut report, flick_stick_config));
         }
 
-        Ok(())
-    }
+        // Motion gestures trigger their actions like bound buttons
+        let gesture_actions = if self.config.gestures.bindings.is_empty() {
+            Vec::new()
+        } else {
+            let gestures = self.gesture_detector.update(
+                &motion,
+                report.timestamp,
+                &self.config.motion,
+                &self.config.gestures,
+            );
+            self.config.gestures.actions(&gestures)
+        };
+
+        // Tablet buttons have to be taken before the gamepad buttons are processed
+        let tablet_state = self.config.tablet_config().map(|tablet_config| {
+            compute_tablet_state(
+                &report.front_touch.reports,
+                &mut report.buttons,
+                FRONT_TOUCHPAD_RECT,
+                tablet_config,
+            )
+        });
+
+        // The outputs that change over time are updated from this report until the next one
+        self.last_report.set(&report);
+        let button_events = self.update_buttons(&mut report, gesture_actions);
 
-    fn send_report(&mut self, report: vita_reports::MainReport) -> crate::Result<()> {
         let syn_event = *SynchronizeEvent::report(get_current_event_time())
             .as_event()
             .as_raw();
 
+        let mut volume_events: Vec<InputEvent> = Vec::new();
+        if report.buttons.vol_up {
+            volume_events.push(KeyEvent::new(get_current_event_time(), Key::VolumeUp, KeyState::PRESSED).into());
+            volume_events.push(KeyEvent::new(get_current_event_time(), Key::VolumeUp, KeyState::RELEASED).into());
+        }
+        if report.buttons.vol_down {
+            volume_events.push(KeyEvent::new(get_current_event_time(), Key::VolumeDown, KeyState::PRESSED).into());
+            volume_events.push(KeyEvent::new(get_current_event_time(), Key::VolumeDown, KeyState::RELEASED).into());
+        }
+
+        // Create stick events (always send)
+        let stick_events = create_stick_events(&report);
+
+        // Write main device events
+        let events: Vec<InputEvent> = button_events
+            .into_iter()
+            .chain(stick_events)
+            .chain(volume_events)
+            .collect();
+        self.write_main_events(&events)?;
+
+        match self.config.touchpad_source {
+            Some(TouchpadSource::Front) => {
+                // Handle front touch events
+                let touch_events = create_touch_events(
+                    &report.front_touch.reports,
+                    &mut self.previous_front_touches,
+                    6,
+                    &mut self.touch_state,
+                );
+
+                let events: Vec<input_event> = touch_events
+                    .iter()
+                    .map(|ev| (*ev).into())
+                    .map(|ev: InputEvent| *ev.as_raw())
+                    .collect();
+
+                self.touchpad_handle
+                    .write(&events)
+                    .map_err(Error::WriteEventFailed)?;
+                self.touchpad_handle
+                    .write(&[syn_event])
+                    .map_err(Error::WriteEventFailed)?;
+            }
+            Some(TouchpadSource::Rear) => {
+                // Handle rear touch events
+                let touch_events = create_touch_events(
+                    &report.back_touch.reports,
+                    &mut self.previous_rear_touches,
+                    4,
+                    &mut self.touch_state,
+                );
+
+                let events: Vec<input_event> = touch_events
+                    .iter()
+                    .map(|ev| (*ev).into())
+                    .map(|ev: InputEvent| *ev.as_raw())
+                    .collect();
+
+                self.touchpad_handle
+                    .write(&events)
+                    .map_err(Error::WriteEventFailed)?;
+                self.touchpad_handle
+                    .write(&[syn_event])
+                    .map_err(Error::WriteEventFailed)?;
+            }
+            None => {
+                // Do nothing
+            }
+        }
+
+        // Handle touch panels used as a mouse
+        let mut mouse_output = aim_output;
+        if let Some(TouchConfig::Mouse(mouse_config)) = &self.config.front_touch_config {
+            mouse_output.merge(self.front_touch_mouse.update(
+                &report.front_touch.reports,
+                report.timestamp,
+                mouse_config,
+            ));
+        }
+        if let Some(TouchConfig::Mouse(mouse_config)) = &self.config.rear_touch_config {
+            mouse_output.merge(self.rear_touch_mouse.update(
+                &report.back_touch.reports,
+                report.timestamp,
+                mouse_config,
+            ));
+        }
+        self.write_mouse_output(&mouse_output)?;
+
+        // Handle the front touch panel used as a pen tablet
+        if let (Some(tablet_handle), Some(tablet_state)) = (&self.tablet_handle, &tablet_state) {
+            let events: Vec<input_event> = create_tablet_events(tablet_state)
+                .iter()
+                .map(|ev| *ev.as_raw())
+                .collect();
+
+            tablet_handle
+                .write(&events)
+                .map_err(Error::WriteEventFailed)?;
+            tablet_handle
+                .write(&[syn_event])
+                .map_err(Error::WriteEventFailed)?;
+        }
+        // Handle motion sensor events, one report per sample with its own timestamp,
+        // the samples already written are not kept in the report
+        for sample in &report.motion_samples {
+            let motion_events = create_motion_events(sample, &self.config.motion);
+
+            let events: Vec<input_event> = motion_events
+                .iter()
+                .map(|ev| (*ev).into())
+                .map(|ev: InputEvent| *ev.as_raw())
+                .collect();
+
+            self.sensor_handle
+                .write(&events)
+                .map_err(Error::WriteEventFailed)?;
+            self.sensor_handle
+                .write(&[syn_event])
+                .map_err(Error::WriteEventFailed)?;
+        }
+
+        Ok(())
+    }
+
+    /// Applies the button modes, bindings, touch zones and macros to the buttons of the report,
+    /// and returns the events of the buttons, D-Pad and keys that changed.
+    fn update_buttons(
+        &mut self,
+        report: &mut vita_reports::MainReport,
+        gesture_actions: Vec<TouchAction>,
+    ) -> Vec<InputEvent> {
+        // Turbo, toggle and long press buttons, before they reach their bindings
+        let mode_actions = self.button_modes.update(
+            &mut report.buttons,
+            report.timestamp,
+            &self.config.button_modes,
+        );
+
+        // Bound buttons are replaced by their actions
+        let binding_actions =
+            take_binding_actions(&mut report.buttons, &self.config.button_bindings);
+
+        // Process touch actions
+        let front_touch_actions = process_touch_reports(
+            &self
+                .config
+                .mirror
+                .touch_zone_reports(&report.front_touch.reports, FRONT_TOUCHPAD_RECT),
+            &self.config.front_touch_config,
+        );
+        let rear_touch_actions = process_touch_reports(
+            &self
+                .config
+                .mirror
+                .touch_zone_reports(&report.back_touch.reports, REAR_TOUCHPAD_RECT),
+            &self.config.rear_touch_config,
+        );
+        let actions: Vec<TouchAction> = front_touch_actions
+            .into_iter()
+            .chain(rear_touch_actions)
+            .chain(mode_actions)
+            .chain(binding_actions)
+            .chain(gesture_actions)
+            .collect();
+
+        // Playing macros press their inputs on top of the physical ones
+        self.macro_player.update(&actions, report, &self.config.macros);
+
         // Calculate D-Pad direction
         let dpad_direction = compute_dpad_direction(&report.buttons);
         let (mut hat_x_value, mut hat_y_value) = dpad_direction_to_axis_values(dpad_direction);
@@ -464,13 +1098,8 @@ impl<F: AsRawFd + Write> VitaVirtualDevice<&ConfigBuilder> for VitaDevice<F> {
         let pressed_buttons = get_pressed_buttons(&report.buttons, self.config.trigger_config);
         let mut pressed_buttons_set: HashSet<Button> = pressed_buttons.iter().cloned().collect();
 
-        // Process touch actions
-        let front_touch_actions =
-            process_touch_reports(&report.front_touch.reports, &self.config.front_touch_config);
-        let rear_touch_actions =
-            process_touch_reports(&report.back_touch.reports, &self.config.rear_touch_config);
-
-        for action in front_touch_actions.into_iter().chain(rear_touch_actions) {
+        let mut keys = Vec::new();
+        for action in actions {
             match action {
                 TouchAction::Button(button) => {
                     pressed_buttons_set.insert(button);
@@ -480,6 +1109,9 @@ impl<F: AsRawFd + Write> VitaVirtualDevice<&ConfigBuilder> for VitaDevice<F> {
                     hat_x_value = x;
                     hat_y_value = y;
                 }
+                TouchAction::Key(combo) => keys.extend(combo.0),
+                // Already handled by the macro player
+                TouchAction::Macro(_) => {}
             }
         }
 
@@ -536,134 +1168,154 @@ impl<F: AsRawFd + Write> VitaVirtualDevice<&ConfigBuilder> for VitaDevice<F> {
             self.previous_hat_y = hat_y_value;
         }
 
-        let mut volume_events: Vec<InputEvent> = Vec::new();
-        if report.buttons.vol_up {
-            volume_events.push(KeyEvent::new(get_current_event_time(), Key::VolumeUp, KeyState::PRESSED).into());
-            volume_events.push(KeyEvent::new(get_current_event_time(), Key::VolumeUp, KeyState::RELEASED).into());
-        }
-        if report.buttons.vol_down {
-            volume_events.push(KeyEvent::new(get_current_event_time(), Key::VolumeDown, KeyState::PRESSED).into());
-            volume_events.push(KeyEvent::new(get_current_event_time(), Key::VolumeDown, KeyState::RELEASED).into());
-        }
-
-        // Create stick events (always send)
-        let stick_events = create_stick_events(&report);
-
-        // Write main device events
-        let events: Vec<input_event> = button_press_events
-            .iter()
-            .chain(button_release_events.iter())
-            .chain(dpad_events.iter())
-            .chain(stick_events.iter())
-            .chain(volume_events.iter())
-            .map(|ev| (*ev).into())
-            .map(|ev: InputEvent| *ev.as_raw())
+        // Create keyboard events for held key actions
+        let (keys_to_press, keys_to_release) = self.held_keys.update(keys);
+        let key_events: Vec<InputEvent> = keys_to_release
+            .into_iter()
+            .map(|key| (key, KeyState::RELEASED))
+            .chain(keys_to_press.into_iter().map(|key| (key, KeyState::PRESSED)))
+            .map(|(key, state)| {
+                KeyEvent::new(get_current_event_time(), map_keyboard_key(key), state).into()
+            })
             .collect();
 
-        self.main_handle
-            .write(&events)
-            .map_err(Error::WriteEventFailed)?;
-        self.main_handle
-            .write(&[syn_event])
-            .map_err(Error::WriteEventFailed)?;
+        button_press_events
+            .into_iter()
+            .chain(button_release_events)
+            .chain(dpad_events)
+            .chain(key_events)
+            .collect()
+    }
 
-        self.keyboard_handle
-            .write(&events)
-            .map_err(Error::WriteEventFailed)?;
-        self.keyboard_handle
-            .write(&[syn_event])
-            .map_err(Error::WriteEventFailed)?;
+    /// Updates the outputs that change over time between reports.
+    ///
+    /// Only the changes of the buttons, D-Pad and keys and the mouse motion are written,
+    /// the outputs of the report itself were written when it was received.
+    fn process_tick(&mut self, mut report: vita_reports::MainReport) -> crate::Result<()> {
+        let aim_output = match &self.config.flick_stick {
+            Some(flick_stick_config) => self.flick_stick.tick(report.timestamp, flick_stick_config),
+            None => MouseOutput::default(),
+        };
+        let gesture_actions = self
+            .config
+            .gestures
+            .actions(&self.gesture_detector.active(report.timestamp));
+
+        let events = self.update_buttons(&mut report, gesture_actions);
+        if !events.is_empty() {
+            self.write_main_events(&events)?;
+        }
+        self.write_mouse_output(&aim_output)?;
 
-        match self.config.touchpad_source {
-            Some(TouchpadSource::Front) => {
-                // Handle front touch events
-                let touch_events = create_touch_events(
-                    &report.front_touch.reports,
-                    &mut self.previous_front_touches,
-                    6,
-                    &mut self.touch_state,
-                );
+        Ok(())
+    }
+}
 
-                let events: Vec<input_event> = touch_events
-                    .iter()
-                    .map(|ev| (*ev).into())
-                    .map(|ev: InputEvent| *ev.as_raw())
-                    .collect();
+impl<F: AsRawFd + Write> VitaVirtualDevice<&ConfigBuilder> for VitaDevice<F> {
+    type Config = Config;
 
-                self.touchpad_handle
-                    .write(&events)
-                    .map_err(Error::WriteEventFailed)?;
-                self.touchpad_handle
-                    .write(&[syn_event])
-                    .map_err(Error::WriteEventFailed)?;
-            }
-            Some(TouchpadSource::Rear) => {
-                // Handle rear touch events
-                let touch_events = create_touch_events(
-                    &report.back_touch.reports,
-                    &mut self.previous_rear_touches,
-                    4,
-                    &mut self.touch_state,
-                );
+    fn identifiers(&self) -> Option<&[OsString]> {
+        self.ids.as_ref().map(|ids| ids.as_slice())
+    }
 
-                let events: Vec<input_event> = touch_events
-                    .iter()
-                    .map(|ev| (*ev).into())
-                    .map(|ev: InputEvent| *ev.as_raw())
-                    .collect();
+    #[inline]
+    fn get_config(&self) -> &Self::Config {
+        &self.config
+    }
 
-                self.touchpad_handle
-                    .write(&events)
-                    .map_err(Error::WriteEventFailed)?;
-                self.touchpad_handle
-                    .write(&[syn_event])
-                    .map_err(Error::WriteEventFailed)?;
-            }
-            None => {
-                // Do nothing
-            }
+    #[inline]
+    fn set_config(&mut self, config: &ConfigBuilder) -> crate::Result<()> {
+        let mut new_config = self.config.clone();
+        new_config.apply(config);
+        check_config(&new_config)?;
+        if new_config.tablet_config().is_some() && self.tablet_handle.is_none() {
+            return Err(crate::Error::Linux(Error::InvalidConfig(
+                "the tablet device is not available".to_string(),
+            )));
         }
-        // Handle motion sensor events
-        let motion_events = create_motion_events(&report);
 
-        let events: Vec<input_event> = motion_events
-            .iter()
-            .map(|ev| (*ev).into())
-            .map(|ev: InputEvent| *ev.as_raw())
-            .collect();
-
-        self.sensor_handle
-            .write(&events)
-            .map_err(Error::WriteEventFailed)?;
-        self.sensor_handle
-            .write(&[syn_event])
-            .map_err(Error::WriteEventFailed)?;
+        // The configuration is only applied once its tablet device exists
+        self.update_tablet_device(new_config.tablet_config())
+            .map_err(Error::DeviceCreationFailed)?;
+        self.config = new_config;
 
         Ok(())
     }
-}
 
-fn create_motion_events(report: &vita_reports::MainReport) -> Vec<InputEvent> {
-    // Convert the vita accel range [-4.0, 4.0] to the range [-32768, 32768]
-    let accel_x_i16 = f32_to_i16(-report.motion.accelerometer.x, -4.0, 4.0); // inverted
-    let accel_y_i16 = f32_to_i16(report.motion.accelerometer.y, -4.0, 4.0);
-    let accel_z_i16 = f32_to_i16(-report.motion.accelerometer.z, -4.0, 4.0); // inverted
+    fn send_report(&mut self, report: vita_reports::MainReport) -> crate::Result<()> {
+        self.process_report(report)
+    }
+
+    fn next_tick(&self) -> Option<Duration> {
+        let timestamp = self.last_report.now()?.timestamp;
+        let deadline = [
+            self.button_modes.next_deadline(timestamp, &self.config.button_modes),
+            self.macro_player.next_deadline(timestamp, &self.config.macros),
+            self.config
+                .flick_stick
+                .as_ref()
+                .and_then(|config| self.flick_stick.next_deadline(config)),
+            self.gesture_detector.next_deadline(timestamp),
+        ]
+        .into_iter()
+        .flatten()
+        .min()?;
+        Some(self.last_report.until(deadline))
+    }
+
+    fn tick(&mut self) -> crate::Result<()> {
+        match self.last_report.now() {
+            Some(report) => self.process_tick(report),
+            None => Ok(()),
+        }
+    }
+}
 
-    // Convert the vita gyro range [-35.0, 35.0] to the range [-32768, 32768]
-    let gyro_x_i16 = f32_to_i16(report.motion.gyro.x, -35.0, 35.0);
-    let gyro_y_i16 = f32_to_i16(-report.motion.gyro.y, -35.0, 35.0); // inverted
-    let gyro_z_i16 = f32_to_i16(report.motion.gyro.z, -35.0, 35.0);
+fn create_motion_events(motion: &vita_reports::MotionData, config: &MotionConfig) -> Vec<InputEvent> {
+    let ([accel_x, accel_y, accel_z], [gyro_x, gyro_y, gyro_z]) = config.controller_axes(motion);
 
     vec![
-        AbsoluteEvent::new(get_current_event_time(), AbsoluteAxis::X, accel_x_i16 as i32).into(),
-        AbsoluteEvent::new(get_current_event_time(), AbsoluteAxis::Y, accel_z_i16 as i32).into(),
-        AbsoluteEvent::new(get_current_event_time(), AbsoluteAxis::Z, accel_y_i16 as i32).into(),
-        AbsoluteEvent::new(get_current_event_time(), AbsoluteAxis::RX, gyro_x_i16 as i32).into(),
-        AbsoluteEvent::new(get_current_event_time(), AbsoluteAxis::RY, gyro_z_i16 as i32).into(),
-        AbsoluteEvent::new(get_current_event_time(), AbsoluteAxis::RZ, gyro_y_i16 as i32).into(),
+        AbsoluteEvent::new(get_current_event_time(), AbsoluteAxis::X, accel_x as i32).into(),
+        AbsoluteEvent::new(get_current_event_time(), AbsoluteAxis::Y, accel_y as i32).into(),
+        AbsoluteEvent::new(get_current_event_time(), AbsoluteAxis::Z, accel_z as i32).into(),
+        AbsoluteEvent::new(get_current_event_time(), AbsoluteAxis::RX, gyro_x as i32).into(),
+        AbsoluteEvent::new(get_current_event_time(), AbsoluteAxis::RY, gyro_y as i32).into(),
+        AbsoluteEvent::new(get_current_event_time(), AbsoluteAxis::RZ, gyro_z as i32).into(),
+        // Microseconds, wrapping around like the hardware counters
+        MiscEvent::new(
+            get_current_event_time(),
+            MiscKind::Timestamp,
+            motion.timestamp as u32 as i32,
+        )
+        .into(),
     ]
 }
 
+fn create_tablet_events(state: &TabletState) -> Vec<InputEvent> {
+    let mut events = Vec::new();
+
+    // Keep the last position while the pen is out of proximity
+    if state.tool.is_some() {
+        events.push(AbsoluteEvent::new(get_current_event_time(), AbsoluteAxis::X, state.x).into());
+        events.push(AbsoluteEvent::new(get_current_event_time(), AbsoluteAxis::Y, state.y).into());
+    }
+    events.push(
+        AbsoluteEvent::new(get_current_event_time(), AbsoluteAxis::Pressure, state.pressure).into(),
+    );
+
+    for (key, pressed) in [
+        (Key::ButtonToolPen, state.tool == Some(TabletTool::Pen)),
+        (Key::ButtonToolRubber, state.tool == Some(TabletTool::Eraser)),
+        (Key::ButtonTouch, state.touching),
+        (Key::ButtonStylus, state.stylus),
+        (Key::ButtonStylus2, state.stylus2),
+    ] {
+        events.push(KeyEvent::new(get_current_event_time(), key, key_state(pressed)).into());
+    }
+
+    events
+}
+
 fn create_stick_events(report: &vita_reports::MainReport) -> Vec<InputEvent> {
     vec![
         AbsoluteEvent::new(get_current_event_time(), AbsoluteAxis::X, report.lx as i32).into(),
@@ -764,3 +1416,220 @@ fn create_touch_events(
 
     events
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::virtual_button::VitaButton;
+    use crate::virtual_button_mode::ButtonMode;
+
+    /// Events written by a virtual device, to a file instead of uinput.
+    struct Output(PathBuf);
+
+    impl Output {
+        fn create(test: &str, name: &str) -> (Self, UInputHandle<File>) {
+            let path = std::env::temp_dir()
+                .join(format!("vita_device_{}_{test}_{name}", std::process::id()));
+            let file = File::create(&path).expect("Failed to create the output file");
+            (Output(path), UInputHandle::new(file))
+        }
+
+        /// Returns the written events, without the synchronization events.
+        fn events(&self) -> Vec<input_event> {
+            let bytes = std::fs::read(&self.0).expect("Failed to read the output file");
+            bytes
+                .chunks_exact(std::mem::size_of::<input_event>())
+                .map(|chunk| unsafe {
+                    std::ptr::read_unaligned(chunk.as_ptr().cast::<input_event>())
+                })
+                .filter(|event| event.type_ != EventKind::Synchronize as u16)
+                .collect()
+        }
+    }
+
+    impl Drop for Output {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    struct Outputs {
+        main: Output,
+        sensor: Output,
+        mouse: Output,
+        tablet: Output,
+        _others: [Output; 2],
+    }
+
+    fn test_device(test: &str, config: Config) -> (VitaDevice<File>, Outputs) {
+        let (main, main_handle) = Output::create(test, "main");
+        let (touchpad, touchpad_handle) = Output::create(test, "touchpad");
+        let (sensor, sensor_handle) = Output::create(test, "sensor");
+        let (keyboard, keyboard_handle) = Output::create(test, "keyboard");
+        let (mouse, mouse_handle) = Output::create(test, "mouse");
+        let (tablet, tablet_handle) = Output::create(test, "tablet");
+
+        let device = VitaDevice {
+            config,
+            main_handle,
+            touchpad_handle,
+            sensor_handle,
+            keyboard_handle,
+            mouse_handle,
+            tablet_handle: Some(tablet_handle),
+            tablet_screen_size: None,
+            previous_front_touches: vec![None; FRONT_TOUCHPAD_MAX_SLOTS],
+            previous_rear_touches: vec![None; REAR_TOUCHPAD_MAX_SLOTS],
+            front_touch_filter: TouchFilter::default(),
+            rear_touch_filter: TouchFilter::default(),
+            front_touch_mouse: TouchMouse::default(),
+            rear_touch_mouse: TouchMouse::default(),
+            touch_state: false,
+            ids: None,
+            previous_buttons: HashSet::new(),
+            previous_hat_x: 0,
+            previous_hat_y: 0,
+            held_keys: HeldKeys::default(),
+            macro_player: MacroPlayer::default(),
+            button_modes: ButtonModes::default(),
+            last_report: LastReport::default(),
+            motion_processor: MotionProcessor::default(),
+            gyro_mouse: GyroMouse::default(),
+            gyro_stick: GyroStick::default(),
+            flick_stick: FlickStick::default(),
+            gesture_detector: GestureDetector::default(),
+        };
+        let outputs = Outputs {
+            main,
+            sensor,
+            mouse,
+            tablet,
+            _others: [touchpad, keyboard],
+        };
+        (device, outputs)
+    }
+
+    #[test]
+    fn test_tick() {
+        let config = Config {
+            button_modes: vec![(VitaButton::Cross, ButtonMode::Turbo { rate: 10.0 })],
+            ..Config::default()
+        };
+        let (mut device, outputs) = test_device("tick", config);
+
+        let mut report = vita_reports::MainReport {
+            timestamp: 1_000_000,
+            ..Default::default()
+        };
+        report.buttons.cross = true;
+        report.buttons.vol_up = true;
+        report.motion.accelerometer.z = -1.0;
+        report.motion.timestamp = 999_000;
+        device.send_report(report).unwrap();
+
+        let written = outputs.main.events().len();
+        assert_eq!(outputs.sensor.events().len(), 7);
+
+        // Turbo releases the button half a period later
+        let mut replayed = device.last_report.now().unwrap();
+        replayed.timestamp = 1_050_000;
+        device.process_tick(replayed.clone()).unwrap();
+        let events = outputs.main.events();
+        assert_eq!(
+            events[written..]
+                .iter()
+                .map(|event| (event.type_, event.code, event.value))
+                .collect::<Vec<_>>(),
+            vec![(EventKind::Key as u16, Key::ButtonSouth as u16, 0)],
+            "Only the released button should be written"
+        );
+
+        device.process_tick(replayed).unwrap();
+        assert_eq!(outputs.main.events().len(), events.len());
+        assert_eq!(
+            outputs.sensor.events().len(),
+            7,
+            "Motion sample should not be written again"
+        );
+        assert!(outputs.mouse.events().is_empty());
+    }
+
+    #[test]
+    fn test_replayed_motion() {
+        let (mut device, outputs) = test_device("replayed_motion", Config::default());
+
+        let mut report = vita_reports::MainReport {
+            timestamp: 1_000_000,
+            ..Default::default()
+        };
+        report.motion.accelerometer.z = -1.0;
+        report.motion.timestamp = 999_000;
+        device.send_report(report.clone()).unwrap();
+        assert_eq!(outputs.sensor.events().len(), 7);
+
+        // Same motion sample in a report sent again
+        report.timestamp = 1_005_000;
+        device.send_report(report).unwrap();
+        assert_eq!(
+            outputs.sensor.events().len(),
+            7,
+            "Replayed sample should not be written"
+        );
+    }
+
+    #[test]
+    fn test_tablet_config() {
+        let (mut device, outputs) = test_device("tablet_config", Config::default());
+
+        let rear_tablet = ConfigBuilder {
+            rear_touch_config: Some(Some(TouchConfig::Tablet(TabletConfig::default()))),
+            ..Default::default()
+        };
+        assert!(device.set_config(&rear_tablet).is_err());
+        assert!(
+            device.config.rear_touch_config.is_none(),
+            "Rejected configuration should not be applied"
+        );
+
+        let front_tablet = ConfigBuilder {
+            front_touch_config: Some(Some(TouchConfig::Tablet(TabletConfig::default()))),
+            ..Default::default()
+        };
+        let tablet_handle = device.tablet_handle.take();
+        assert!(device.set_config(&front_tablet).is_err());
+        assert!(
+            device.config.tablet_config().is_none(),
+            "Tablet configuration should not be applied without its device"
+        );
+
+        // The uinput device of the tablet can not be created on a file,
+        // so it is marked as created for the current screen size
+        device.tablet_handle = tablet_handle;
+        device.tablet_screen_size = Some(TabletConfig::default().screen_size);
+        device.set_config(&front_tablet).unwrap();
+        assert!(device.config.tablet_config().is_some());
+
+        let mut report = vita_reports::MainReport {
+            timestamp: 1_000_000,
+            ..Default::default()
+        };
+        report.front_touch.reports.push(vita_reports::TouchReport {
+            x: 960,
+            y: 544,
+            force: 64,
+            ..Default::default()
+        });
+        device.send_report(report).unwrap();
+        let events: Vec<_> = outputs
+            .tablet
+            .events()
+            .iter()
+            .map(|event| (event.type_, event.code, event.value))
+            .collect();
+        assert!(events.contains(&(EventKind::Key as u16, Key::ButtonToolPen as u16, 1)));
+        assert!(events.contains(&(EventKind::Key as u16, Key::ButtonTouch as u16, 1)));
+        assert!(events.contains(&(EventKind::Absolute as u16, AbsoluteAxis::X as u16, 960)));
+    }
+}
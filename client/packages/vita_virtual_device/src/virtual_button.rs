@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use vita_reports::{ButtonsData, VitaButtons};
 
 #[derive(Clone, Debug, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub enum Button {
@@ -29,3 +30,133 @@ pub enum DpadDirection {
     NorthWest,
     None,
 }
+
+/// Physical buttons of the PS Vita.
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum VitaButton {
+    Select,
+    Start,
+    Up,
+    Right,
+    Down,
+    Left,
+    L,
+    R,
+    Triangle,
+    Circle,
+    Cross,
+    Square,
+    VolumeUp,
+    VolumeDown,
+    PS,
+}
+
+impl VitaButton {
+    pub const ALL: [VitaButton; 15] = [
+        VitaButton::Select,
+        VitaButton::Start,
+        VitaButton::Up,
+        VitaButton::Right,
+        VitaButton::Down,
+        VitaButton::Left,
+        VitaButton::L,
+        VitaButton::R,
+        VitaButton::Triangle,
+        VitaButton::Circle,
+        VitaButton::Cross,
+        VitaButton::Square,
+        VitaButton::VolumeUp,
+        VitaButton::VolumeDown,
+        VitaButton::PS,
+    ];
+
+    fn state_mut(self, buttons: &mut ButtonsData) -> &mut bool {
+        match self {
+            VitaButton::Select => &mut buttons.select,
+            VitaButton::Start => &mut buttons.start,
+            VitaButton::Up => &mut buttons.up,
+            VitaButton::Right => &mut buttons.right,
+            VitaButton::Down => &mut buttons.down,
+            VitaButton::Left => &mut buttons.left,
+            VitaButton::L => &mut buttons.lt,
+            VitaButton::R => &mut buttons.rt,
+            VitaButton::Triangle => &mut buttons.triangle,
+            VitaButton::Circle => &mut buttons.circle,
+            VitaButton::Cross => &mut buttons.cross,
+            VitaButton::Square => &mut buttons.square,
+            VitaButton::VolumeUp => &mut buttons.vol_up,
+            VitaButton::VolumeDown => &mut buttons.vol_down,
+            VitaButton::PS => &mut buttons.ps,
+        }
+    }
+
+    pub fn is_pressed(self, buttons: &ButtonsData) -> bool {
+        match self {
+            VitaButton::Select => buttons.select,
+            VitaButton::Start => buttons.start,
+            VitaButton::Up => buttons.up,
+            VitaButton::Right => buttons.right,
+            VitaButton::Down => buttons.down,
+            VitaButton::Left => buttons.left,
+            VitaButton::L => buttons.lt,
+            VitaButton::R => buttons.rt,
+            VitaButton::Triangle => buttons.triangle,
+            VitaButton::Circle => buttons.circle,
+            VitaButton::Cross => buttons.cross,
+            VitaButton::Square => buttons.square,
+            VitaButton::VolumeUp => buttons.vol_up,
+            VitaButton::VolumeDown => buttons.vol_down,
+            VitaButton::PS => buttons.ps,
+        }
+    }
+
+    /// Marks the button as released, so it is not processed any further.
+    #[inline]
+    pub fn release(self, buttons: &mut ButtonsData) {
+        *self.state_mut(buttons) = false;
+    }
+
+    #[inline]
+    pub fn press(self, buttons: &mut ButtonsData) {
+        *self.state_mut(buttons) = true;
+    }
+}
+
+impl From<VitaButton> for VitaButtons {
+    fn from(button: VitaButton) -> Self {
+        match button {
+            VitaButton::Select => VitaButtons::SELECT,
+            VitaButton::Start => VitaButtons::START,
+            VitaButton::Up => VitaButtons::UP,
+            VitaButton::Right => VitaButtons::RIGHT,
+            VitaButton::Down => VitaButtons::DOWN,
+            VitaButton::Left => VitaButtons::LEFT,
+            VitaButton::L => VitaButtons::L,
+            VitaButton::R => VitaButtons::R,
+            VitaButton::Triangle => VitaButtons::TRIANGLE,
+            VitaButton::Circle => VitaButtons::CIRCLE,
+            VitaButton::Cross => VitaButtons::CROSS,
+            VitaButton::Square => VitaButtons::SQUARE,
+            VitaButton::VolumeUp => VitaButtons::VOLUME_UP,
+            VitaButton::VolumeDown => VitaButtons::VOLUME_DOWN,
+            VitaButton::PS => VitaButtons::PS,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vita_buttons() {
+        for button in VitaButton::ALL {
+            let buttons = VitaButtons::from(button);
+            assert_eq!(buttons.name(), Some(format!("{button:?}").as_str()));
+
+            let mut data = ButtonsData::default();
+            button.press(&mut data);
+            assert_eq!(VitaButtons::from(&data), buttons);
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use vita_reports::ButtonsData;
+
+use crate::virtual_button::VitaButton;
+use crate::virtual_touch::TouchAction;
+
+/// Duration of the button press sent after a short press of a long press button, in microseconds.
+const TAP_DURATION: u64 = 50_000;
+
+/// Behaviour of a Vita button that changes how its presses are output.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum ButtonMode {
+    /// Repeats the button at `rate` presses per second while it is held.
+    Turbo { rate: f32 },
+    /// Each press switches the button between held and released.
+    Toggle,
+    /// Holding the button for `delay` milliseconds triggers `action` instead of the button,
+    /// a shorter press taps the button when it is released.
+    LongPress { delay: u64, action: TouchAction },
+}
+
+impl ButtonMode {
+    /// Half of the turbo period in microseconds.
+    #[inline]
+    fn turbo_half_period(rate: f32) -> u64 {
+        ((500_000.0 / rate.max(0.1)) as u64).max(1)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct ModeState {
+    /// Timestamp of the current physical press.
+    held_since: Option<u64>,
+    toggled: bool,
+    /// End of the tap sent after a short press.
+    tap_until: Option<u64>,
+}
+
+/// Applies the button modes of the configuration to the physical buttons.
+///
+/// The output depends on time, so it has to be updated between reports
+/// at the timestamps returned by [`ButtonModes::next_deadline`].
+#[derive(Clone, Debug, Default)]
+pub struct ButtonModes {
+    states: HashMap<VitaButton, ModeState>,
+}
+
+impl ButtonModes {
+    /// Replaces the state of the buttons with a mode by their output,
+    /// and returns the actions of the long pressed buttons.
+    pub fn update(
+        &mut self,
+        buttons: &mut ButtonsData,
+        timestamp: u64,
+        modes: &[(VitaButton, ButtonMode)],
+    ) -> Vec<TouchAction> {
+        let mut actions = Vec::new();
+
+        for (button, mode) in modes {
+            let state = self.states.entry(*button).or_default();
+            let pressed = button.is_pressed(buttons);
+            let just_pressed = pressed && state.held_since.is_none();
+            let released_since = if pressed {
+                state.held_since.get_or_insert(timestamp);
+                None
+            } else {
+                state.held_since.take()
+            };
+
+            let output = match mode {
+                ButtonMode::Turbo { rate } => state.held_since.is_some_and(|start| {
+                    let half_period = ButtonMode::turbo_half_period(*rate);
+                    (timestamp.saturating_sub(start) / half_period) % 2 == 0
+                }),
+                ButtonMode::Toggle => {
+                    if just_pressed {
+                        state.toggled = !state.toggled;
+                    }
+                    state.toggled
+                }
+                ButtonMode::LongPress { delay, action } => {
+                    let long = |start: u64| timestamp.saturating_sub(start) >= delay * 1000;
+                    if state.held_since.is_some_and(long) {
+                        actions.push(action.clone());
+                    }
+                    if released_since.is_some_and(|start| !long(start)) {
+                        state.tap_until = Some(timestamp + TAP_DURATION);
+                    }
+                    state.tap_until.is_some_and(|end| timestamp < end)
+                }
+            };
+
+            if output {
+                button.press(buttons);
+            } else {
+                button.release(buttons);
+            }
+        }
+
+        actions
+    }
+
+    /// Returns the next timestamp at which the output changes without any input change.
+    pub fn next_deadline(&self, timestamp: u64, modes: &[(VitaButton, ButtonMode)]) -> Option<u64> {
+        modes
+            .iter()
+            .filter_map(|(button, mode)| {
+                let state = self.states.get(button)?;
+                match mode {
+                    ButtonMode::Turbo { rate } => state.held_since.map(|start| {
+                        let half_period = ButtonMode::turbo_half_period(*rate);
+                        start + (timestamp.saturating_sub(start) / half_period + 1) * half_period
+                    }),
+                    ButtonMode::Toggle => None,
+                    ButtonMode::LongPress { delay, .. } => state
+                        .held_since
+                        .map(|start| start + delay * 1000)
+                        .into_iter()
+                        .chain(state.tap_until)
+                        .filter(|&deadline| deadline > timestamp)
+                        .min(),
+                }
+            })
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buttons(pressed: &[VitaButton]) -> ButtonsData {
+        let mut buttons = ButtonsData::default();
+        for button in pressed {
+            button.press(&mut buttons);
+        }
+        buttons
+    }
+
+    #[test]
+    fn test_turbo_and_toggle() {
+        let modes = [
+            (VitaButton::Cross, ButtonMode::Turbo { rate: 10.0 }),
+            (VitaButton::Circle, ButtonMode::Toggle),
+        ];
+        let mut button_modes = ButtonModes::default();
+        let held = [VitaButton::Cross, VitaButton::Circle];
+
+        let mut output = buttons(&held);
+        button_modes.update(&mut output, 0, &modes);
+        assert!(output.cross && output.circle);
+        assert_eq!(button_modes.next_deadline(0, &modes), Some(50_000));
+
+        let mut output = buttons(&held);
+        button_modes.update(&mut output, 60_000, &modes);
+        assert!(
+            !output.cross,
+            "Turbo should release the button half of the time"
+        );
+        assert!(output.circle);
+
+        let mut output = buttons(&[]);
+        button_modes.update(&mut output, 120_000, &modes);
+        assert!(output.circle, "Toggled button should stay pressed");
+        assert_eq!(button_modes.next_deadline(120_000, &modes), None);
+
+        let mut output = buttons(&[VitaButton::Circle]);
+        button_modes.update(&mut output, 130_000, &modes);
+        assert!(
+            !output.circle,
+            "Second press should release the toggled button"
+        );
+    }
+
+    #[test]
+    fn test_long_press() {
+        let modes = [(
+            VitaButton::Triangle,
+            ButtonMode::LongPress {
+                delay: 500,
+                action: TouchAction::Macro("long".to_string()),
+            },
+        )];
+        let mut button_modes = ButtonModes::default();
+
+        let mut output = buttons(&[VitaButton::Triangle]);
+        assert!(button_modes.update(&mut output, 0, &modes).is_empty());
+        assert!(
+            !output.triangle,
+            "Button should wait for the release or the delay"
+        );
+
+        let mut output = buttons(&[]);
+        button_modes.update(&mut output, 100_000, &modes);
+        assert!(output.triangle, "Short press should tap the button");
+        assert_eq!(button_modes.next_deadline(100_000, &modes), Some(150_000));
+
+        let mut output = buttons(&[]);
+        button_modes.update(&mut output, 150_000, &modes);
+        assert!(!output.triangle);
+
+        let mut output = buttons(&[VitaButton::Triangle]);
+        button_modes.update(&mut output, 200_000, &modes);
+        let mut output = buttons(&[VitaButton::Triangle]);
+        let actions = button_modes.update(&mut output, 700_000, &modes);
+        assert_eq!(actions, [TouchAction::Macro("long".to_string())]);
+        assert!(!output.triangle);
+
+        let mut output = buttons(&[]);
+        button_modes.update(&mut output, 800_000, &modes);
+        assert!(!output.triangle, "Long press should not tap the button");
+    }
+}
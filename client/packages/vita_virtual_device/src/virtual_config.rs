@@ -1,7 +1,18 @@
+use std::collections::HashMap;
+
 use rstar::RTree;
 use serde::{Deserialize, Serialize};
 
-use crate::virtual_button::Button;
+use crate::virtual_button::{Button, VitaButton};
+use crate::virtual_button_mode::ButtonMode;
+use crate::virtual_flick::FlickStickConfig;
+use crate::virtual_gesture::GestureConfig;
+use crate::virtual_gyro::{GyroMouseConfig, GyroStickConfig};
+use crate::virtual_macro::Macro;
+use crate::virtual_mirror::MirrorConfig;
+use crate::virtual_motion::MotionConfig;
+use crate::virtual_stick::{StickCalibration, StickConfig};
+use crate::virtual_tablet::TabletButton;
 use crate::virtual_touch::{Point, TouchAction, TouchZone};
 use crate::{FRONT_TOUCHPAD_RECT, REAR_TOUCHPAD_RECT};
 
@@ -11,6 +22,9 @@ use crate::{FRONT_TOUCHPAD_RECT, REAR_TOUCHPAD_RECT};
 pub enum TouchConfig {
     Zones(RTree<TouchZone>),
     Touchpad,
+    Mouse(TouchMouseConfig),
+    /// Only supported for the front touch panel.
+    Tablet(TabletConfig),
 }
 
 impl TouchConfig {
@@ -24,6 +38,75 @@ impl TouchConfig {
     pub fn touchpad() -> Self {
         TouchConfig::Touchpad
     }
+
+    /// Creates a `TouchConfig` using the panel as a relative mouse.
+    #[inline]
+    pub fn mouse() -> Self {
+        TouchConfig::Mouse(TouchMouseConfig::default())
+    }
+}
+
+/// Configuration for using a touch panel as an absolute pen tablet.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TabletConfig {
+    /// Size of the screen the desktop maps the tablet to.
+    pub screen_size: (i32, i32),
+    /// Part of the screen the panel is mapped to, the whole screen if not set.
+    pub region: Option<(Point, Point)>,
+    /// Force above which the pen touches the surface.
+    pub touch_threshold: u8,
+    /// Exponent applied to the normalized force, values above 1 make light strokes lighter.
+    pub pressure_curve: f32,
+    /// Vita buttons sent to the tablet instead of the gamepad.
+    pub buttons: Vec<(VitaButton, TabletButton)>,
+}
+
+impl Default for TabletConfig {
+    #[inline]
+    fn default() -> Self {
+        TabletConfig {
+            screen_size: (1920, 1080),
+            region: None,
+            touch_threshold: 0,
+            pressure_curve: 1.0,
+            buttons: Vec::new(),
+        }
+    }
+}
+
+/// Configuration for using a touch panel as a relative mouse.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TouchMouseConfig {
+    /// Pointer movement per panel unit for slow finger movement.
+    pub sensitivity: f32,
+    /// Extra gain per panel unit/ms of finger speed.
+    pub acceleration: f32,
+    /// Upper limit of the speed dependent gain multiplier.
+    pub max_acceleration: f32,
+    /// Wheel steps per panel unit of two finger movement.
+    pub scroll_speed: f32,
+    pub natural_scroll: bool,
+    /// Longest contact in milliseconds still recognized as a tap.
+    pub tap_time: u64,
+    /// Longest travel in panel units still recognized as a tap.
+    pub tap_distance: f32,
+}
+
+impl Default for TouchMouseConfig {
+    #[inline]
+    fn default() -> Self {
+        TouchMouseConfig {
+            sensitivity: 0.5,
+            acceleration: 0.5,
+            max_acceleration: 4.0,
+            scroll_speed: 0.02,
+            natural_scroll: false,
+            tap_time: 180,
+            tap_distance: 40.0,
+        }
+    }
 }
 
 /// Configuration for trigger inputs.
@@ -46,6 +129,105 @@ impl Config {
         ConfigBuilder::default()
     }
 
+    /// Returns the ready-made configuration with the given name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "standart" => Some(Config::rear_rl2_front_rl3()),
+            "alt_triggers" => Some(Config::rear_rl1_front_rl3_vitatriggers_rl2()),
+            "rear_touchpad" => Some(Config::front_top_rl2_bottom_rl3_rear_touchpad()),
+            "front_touchpad" => Some(Config::rear_top_rl2_bottom_rl3_front_touchpad()),
+            _ => None,
+        }
+    }
+
+    /// Overrides the fields set in `config`.
+    pub fn apply(&mut self, config: &ConfigBuilder) {
+        if let Some(front_touch_config) = &config.front_touch_config {
+            self.front_touch_config = front_touch_config.clone();
+        }
+
+        if let Some(rear_touch_config) = &config.rear_touch_config {
+            self.rear_touch_config = rear_touch_config.clone();
+        }
+
+        if let Some(trigger_config) = config.trigger_config {
+            self.trigger_config = trigger_config;
+        }
+
+        if let Some(touchpad_source) = &config.touchpad_source {
+            self.touchpad_source = touchpad_source.clone();
+        }
+
+        if let Some(front_touch_rejection) = &config.front_touch_rejection {
+            self.front_touch_rejection = front_touch_rejection.clone();
+        }
+
+        if let Some(rear_touch_rejection) = &config.rear_touch_rejection {
+            self.rear_touch_rejection = rear_touch_rejection.clone();
+        }
+
+        if let Some(button_bindings) = &config.button_bindings {
+            self.button_bindings = button_bindings.clone();
+        }
+
+        if let Some(left_stick) = &config.left_stick {
+            self.left_stick = left_stick.clone();
+        }
+
+        if let Some(right_stick) = &config.right_stick {
+            self.right_stick = right_stick.clone();
+        }
+
+        if let Some(left_stick_calibration) = &config.left_stick_calibration {
+            self.left_stick_calibration = *left_stick_calibration;
+        }
+
+        if let Some(right_stick_calibration) = &config.right_stick_calibration {
+            self.right_stick_calibration = *right_stick_calibration;
+        }
+
+        if let Some(motion) = &config.motion {
+            self.motion = motion.clone();
+        }
+
+        if let Some(gyro_mouse) = &config.gyro_mouse {
+            self.gyro_mouse = gyro_mouse.clone();
+        }
+
+        if let Some(gyro_stick) = &config.gyro_stick {
+            self.gyro_stick = gyro_stick.clone();
+        }
+
+        if let Some(flick_stick) = &config.flick_stick {
+            self.flick_stick = flick_stick.clone();
+        }
+
+        if let Some(gestures) = &config.gestures {
+            self.gestures = gestures.clone();
+        }
+
+        if let Some(mirror) = &config.mirror {
+            self.mirror = *mirror;
+        }
+
+        if let Some(button_modes) = &config.button_modes {
+            self.button_modes = button_modes.clone();
+        }
+
+        if let Some(macros) = &config.macros {
+            self.macros = macros.clone();
+        }
+    }
+
+    /// Returns the tablet configuration if the front touch panel is used as a tablet.
+    #[inline]
+    pub fn tablet_config(&self) -> Option<&TabletConfig> {
+        match &self.front_touch_config {
+            Some(TouchConfig::Tablet(tablet_config)) => Some(tablet_config),
+            _ => None,
+        }
+    }
+
     #[inline]
     pub fn rear_rl2_front_rl3() -> Self {
         Config {
@@ -83,6 +265,7 @@ impl Config {
             ])),
             trigger_config: TriggerConfig::Trigger,
             touchpad_source: None,
+            ..Default::default()
         }
     }
 
@@ -123,6 +306,7 @@ impl Config {
             ])),
             trigger_config: TriggerConfig::Shoulder,
             touchpad_source: None,
+            ..Default::default()
         }
     }
 
@@ -162,6 +346,7 @@ impl Config {
             rear_touch_config: Some(TouchConfig::Touchpad),
             trigger_config: TriggerConfig::Trigger,
             touchpad_source: Some(TouchpadSource::Rear),
+            ..Default::default()
         }
     }
 
@@ -201,6 +386,7 @@ impl Config {
             ])),
             trigger_config: TriggerConfig::Trigger,
             touchpad_source: Some(TouchpadSource::Front),
+            ..Default::default()
         }
     }
 }
@@ -213,14 +399,123 @@ pub enum TouchpadSource {
     Rear,
 }
 
+/// Width of the ignored bands along each edge of a touch panel, in panel units.
+#[derive(Clone, Debug, Copy, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct EdgeBands {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+impl EdgeBands {
+    /// Checks if the point lies in one of the bands of the panel `rect`.
+    pub fn contains(&self, rect: (Point, Point), point: Point) -> bool {
+        point.x() < rect.0.x() + self.left
+            || point.x() > rect.1.x() - self.right
+            || point.y() < rect.0.y() + self.top
+            || point.y() > rect.1.y() - self.bottom
+    }
+}
+
+/// Configuration for rejecting unintended contacts (palm, grip, edges) on a touch panel.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TouchRejectionConfig {
+    /// Contacts starting inside these bands are ignored.
+    pub edges: EdgeBands,
+    /// Contacts pressing harder than this are treated as a palm.
+    /// The Vita does not report the contact area, but the force grows with it.
+    pub max_force: Option<u8>,
+    /// Contacts that stay within `stationary_radius` of their starting point
+    /// for longer than this many milliseconds are treated as a resting finger.
+    pub stationary_timeout: Option<u64>,
+    pub stationary_radius: u16,
+    /// Ignore contacts that are already present on the first received report.
+    pub ignore_initial_contacts: bool,
+}
+
+impl Default for TouchRejectionConfig {
+    #[inline]
+    fn default() -> Self {
+        TouchRejectionConfig {
+            edges: EdgeBands::default(),
+            max_force: None,
+            stationary_timeout: None,
+            stationary_radius: 30,
+            ignore_initial_contacts: true,
+        }
+    }
+}
+
 /// Overall configuration for the virtual device.
 #[derive(Clone, Debug, Deserialize, Serialize, derive_builder::Builder)]
-#[builder(field(public))]
+#[builder(field(public), derive(Debug, Deserialize))]
 pub struct Config {
     pub front_touch_config: Option<TouchConfig>,
     pub rear_touch_config: Option<TouchConfig>,
     pub trigger_config: TriggerConfig,
     pub touchpad_source: Option<TouchpadSource>,
+    #[builder(default)]
+    #[serde(default)]
+    pub front_touch_rejection: Option<TouchRejectionConfig>,
+    #[builder(default)]
+    #[serde(default)]
+    pub rear_touch_rejection: Option<TouchRejectionConfig>,
+    /// Vita buttons replaced by an action instead of their default function.
+    #[builder(default)]
+    #[serde(default)]
+    pub button_bindings: Vec<(VitaButton, TouchAction)>,
+    /// Macros by name, played by [`TouchAction::Macro`] actions.
+    #[builder(default)]
+    #[serde(default)]
+    pub macros: HashMap<String, Macro>,
+    /// Turbo, toggle and long press behaviours of the Vita buttons,
+    /// applied before the button bindings.
+    #[builder(default)]
+    #[serde(default)]
+    pub button_modes: Vec<(VitaButton, ButtonMode)>,
+    /// Deadzones and response curve of the left stick, the raw values are used if not set.
+    #[builder(default)]
+    #[serde(default)]
+    pub left_stick: Option<StickConfig>,
+    /// Deadzones and response curve of the right stick, the raw values are used if not set.
+    #[builder(default)]
+    #[serde(default)]
+    pub right_stick: Option<StickConfig>,
+    /// Measured range of the left stick, applied before `left_stick`.
+    #[builder(default)]
+    #[serde(default)]
+    pub left_stick_calibration: Option<StickCalibration>,
+    /// Measured range of the right stick, applied before `right_stick`.
+    #[builder(default)]
+    #[serde(default)]
+    pub right_stick_calibration: Option<StickCalibration>,
+    /// Gyro bias correction and orientation estimation.
+    #[builder(default)]
+    #[serde(default)]
+    pub motion: MotionConfig,
+    /// Gyro aiming with the mouse, disabled if not set.
+    #[builder(default)]
+    #[serde(default)]
+    pub gyro_mouse: Option<GyroMouseConfig>,
+    /// Stick moved by the tilt or rotation of the Vita, disabled if not set.
+    #[builder(default)]
+    #[serde(default)]
+    pub gyro_stick: Option<GyroStickConfig>,
+    /// Stick turning the camera with the mouse, disabled if not set.
+    #[builder(default)]
+    #[serde(default)]
+    pub flick_stick: Option<FlickStickConfig>,
+    /// Motion gestures and their actions.
+    #[builder(default)]
+    #[serde(default)]
+    pub gestures: GestureConfig,
+    /// Left-handed transforms of the sticks, buttons and touch zones.
+    #[builder(default)]
+    #[serde(default)]
+    pub mirror: MirrorConfig,
 }
 
 impl Default for Config {
@@ -231,6 +526,21 @@ impl Default for Config {
             rear_touch_config: None,
             trigger_config: TriggerConfig::default(),
             touchpad_source: None,
+            front_touch_rejection: None,
+            rear_touch_rejection: None,
+            button_bindings: Vec::new(),
+            macros: HashMap::new(),
+            button_modes: Vec::new(),
+            left_stick: None,
+            right_stick: None,
+            left_stick_calibration: None,
+            right_stick_calibration: None,
+            motion: MotionConfig::default(),
+            gyro_mouse: None,
+            gyro_stick: None,
+            flick_stick: None,
+            gestures: GestureConfig::default(),
+            mirror: MirrorConfig::default(),
         }
     }
 }
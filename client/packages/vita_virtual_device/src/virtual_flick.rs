@@ -0,0 +1,248 @@
+use serde::{Deserialize, Serialize};
+use vita_reports::MainReport;
+
+use crate::virtual_mouse::{take_whole, MouseOutput};
+use crate::virtual_stick::{to_deflection, Stick};
+
+/// Part of the flick threshold the stick has to stay above to keep turning with it.
+const RELEASE_RATIO: f32 = 0.9;
+
+/// Directions the flick turns are snapped to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum FlickSnap {
+    #[default]
+    None,
+    /// Forward, right, back and left.
+    Four,
+    /// The four directions and the diagonals.
+    Eight,
+}
+
+impl FlickSnap {
+    fn apply(self, angle: f32) -> f32 {
+        let step = match self {
+            FlickSnap::None => return angle,
+            FlickSnap::Four => 90.0,
+            FlickSnap::Eight => 45.0,
+        };
+        (angle / step).round() * step
+    }
+}
+
+/// Configuration for turning the camera with a stick: pushing the stick turns towards
+/// its direction at once, then rotating the stick keeps turning with it.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct FlickStickConfig {
+    pub stick: Stick,
+    /// Mouse units turning the game camera by one degree.
+    pub mouse_per_degree: f32,
+    /// Deflection from 0 to 1 above which the stick flicks.
+    pub threshold: f32,
+    /// Time the flick turn is spread over, in milliseconds.
+    pub flick_time: u64,
+    pub snap: FlickSnap,
+}
+
+impl Default for FlickStickConfig {
+    fn default() -> Self {
+        FlickStickConfig {
+            stick: Stick::Right,
+            mouse_per_degree: 10.0,
+            threshold: 0.9,
+            flick_time: 100,
+            snap: FlickSnap::None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Flick {
+    start: u64,
+    /// Turn of the flick, in degrees.
+    angle: f32,
+    /// Part of the turn already output, from 0 to 1.
+    progress: f32,
+}
+
+/// Converts the direction of a stick into camera turns with the mouse.
+///
+/// Flicks last `flick_time`, so the processor has to be updated until the timestamp
+/// returned by [`FlickStick::next_deadline`].
+#[derive(Clone, Debug, Default)]
+pub struct FlickStick {
+    flick: Option<Flick>,
+    /// Direction of the stick while it is held past the threshold, in degrees.
+    stick_angle: Option<f32>,
+    remainder: f32,
+}
+
+impl FlickStick {
+    /// Centers the stick in the report and returns the turn it makes.
+    pub fn update(&mut self, report: &mut MainReport, config: &FlickStickConfig) -> MouseOutput {
+        let timestamp = report.timestamp;
+        let (stick_x, stick_y) = config.stick.position(report);
+        let (x, y) = (to_deflection(*stick_x), to_deflection(*stick_y));
+        (*stick_x, *stick_y) = (128, 128);
+
+        let mut degrees = 0.0;
+        let threshold = if self.stick_angle.is_some() {
+            config.threshold * RELEASE_RATIO
+        } else {
+            config.threshold
+        };
+
+        if (x * x + y * y).sqrt() >= threshold {
+            // Clockwise from forward
+            let angle = x.atan2(-y).to_degrees();
+            match self.stick_angle {
+                Some(previous) => degrees += (angle - previous + 540.0).rem_euclid(360.0) - 180.0,
+                None => {
+                    // A new flick finishes the turn of the previous one at once
+                    if let Some(flick) = self.flick.take() {
+                        degrees += flick.angle * (1.0 - flick.progress);
+                    }
+                    self.flick = Some(Flick {
+                        start: timestamp,
+                        angle: config.snap.apply(angle),
+                        progress: 0.0,
+                    });
+                }
+            }
+            self.stick_angle = Some(angle);
+        } else {
+            self.stick_angle = None;
+        }
+
+        degrees += self.advance_flick(timestamp, config);
+        self.mouse_output(degrees, config)
+    }
+
+    /// Continues the current flick between reports, without a new position of the stick.
+    pub fn tick(&mut self, timestamp: u64, config: &FlickStickConfig) -> MouseOutput {
+        let degrees = self.advance_flick(timestamp, config);
+        self.mouse_output(degrees, config)
+    }
+
+    /// Returns the part of the current flick turned since the last update.
+    fn advance_flick(&mut self, timestamp: u64, config: &FlickStickConfig) -> f32 {
+        let Some(flick) = &mut self.flick else {
+            return 0.0;
+        };
+        let duration = config.flick_time * 1000;
+        let t = if duration == 0 {
+            1.0
+        } else {
+            (timestamp.saturating_sub(flick.start) as f32 / duration as f32).min(1.0)
+        };
+        // Fast start and smooth end of the turn
+        let eased = 1.0 - (1.0 - t) * (1.0 - t);
+        let degrees = flick.angle * (eased - flick.progress);
+        flick.progress = eased;
+        if t >= 1.0 {
+            self.flick = None;
+        }
+        degrees
+    }
+
+    fn mouse_output(&mut self, degrees: f32, config: &FlickStickConfig) -> MouseOutput {
+        MouseOutput {
+            dx: take_whole(degrees * config.mouse_per_degree, &mut self.remainder),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the timestamp at which the current flick ends.
+    pub fn next_deadline(&self, config: &FlickStickConfig) -> Option<u64> {
+        self.flick
+            .as_ref()
+            .map(|flick| flick.start + config.flick_time * 1000)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(rx: u8, ry: u8, timestamp: u64) -> MainReport {
+        MainReport {
+            rx,
+            ry,
+            timestamp,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_flick() {
+        let config = FlickStickConfig {
+            mouse_per_degree: 1.0,
+            snap: FlickSnap::Four,
+            ..Default::default()
+        };
+        let mut flick_stick = FlickStick::default();
+
+        // Stick pushed right, slightly down
+        let mut pushed = report(255, 150, 0);
+        let output = flick_stick.update(&mut pushed, &config);
+        assert_eq!(
+            (pushed.rx, pushed.ry),
+            (128, 128),
+            "Stick should be consumed"
+        );
+        assert_eq!(output.dx, 0);
+        assert_eq!(flick_stick.next_deadline(&config), Some(100_000));
+
+        let output = flick_stick.update(&mut report(255, 150, 50_000), &config);
+        assert_eq!(output.dx, 67, "Flick should turn fast at first");
+        let output = flick_stick.update(&mut report(255, 150, 100_000), &config);
+        assert_eq!(output.dx, 23, "Flick should be snapped to 90 degrees");
+        assert_eq!(flick_stick.next_deadline(&config), None);
+    }
+
+    #[test]
+    fn test_tick() {
+        let config = FlickStickConfig {
+            mouse_per_degree: 1.0,
+            snap: FlickSnap::Four,
+            ..Default::default()
+        };
+        let mut flick_stick = FlickStick::default();
+        flick_stick.update(&mut report(255, 150, 0), &config);
+
+        // The flick goes on between reports, with the same turns
+        assert_eq!(flick_stick.tick(50_000, &config).dx, 67);
+        assert_eq!(flick_stick.tick(100_000, &config).dx, 23);
+        assert_eq!(flick_stick.next_deadline(&config), None);
+        assert_eq!(flick_stick.tick(150_000, &config).dx, 0);
+
+        let output = flick_stick.update(&mut report(255, 150, 150_000), &config);
+        assert_eq!(output.dx, 0, "Held stick should not flick again");
+    }
+
+    #[test]
+    fn test_rotation_tracking() {
+        let config = FlickStickConfig {
+            mouse_per_degree: 1.0,
+            flick_time: 0,
+            ..Default::default()
+        };
+        let mut flick_stick = FlickStick::default();
+
+        let output = flick_stick.update(&mut report(128, 0, 0), &config);
+        assert_eq!(output.dx, 0, "Forward flick should not turn");
+
+        let output = flick_stick.update(&mut report(255, 128, 10_000), &config);
+        assert_eq!(output.dx, 90, "Rotating the stick should turn with it");
+
+        let output = flick_stick.update(&mut report(128, 0, 20_000), &config);
+        assert!((output.dx + 90).abs() <= 1);
+
+        flick_stick.update(&mut report(128, 128, 30_000), &config);
+        let output = flick_stick.update(&mut report(0, 128, 40_000), &config);
+        assert!(
+            (output.dx + 90).abs() <= 1,
+            "Stick pushed again should flick"
+        );
+    }
+}
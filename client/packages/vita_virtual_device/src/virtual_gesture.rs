@@ -0,0 +1,396 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+use vita_reports::Vector3;
+
+use crate::virtual_motion::{length, MotionConfig, MotionState};
+use crate::virtual_touch::TouchAction;
+
+/// Angle in degrees the tilt has to go back under its threshold to end.
+const TILT_HYSTERESIS: f32 = 5.0;
+
+/// Time constant of the acceleration baseline the taps are measured from, in seconds.
+const TAP_BASELINE_TIME: f32 = 0.1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum TiltDirection {
+    Left,
+    Right,
+    Forward,
+    Back,
+}
+
+/// Motion of the Vita that can trigger an action.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Gesture {
+    /// Quick back and forth movements.
+    Shake,
+    /// Tap on the back of the Vita.
+    BackTap,
+    /// Vita tilted past the tilt angle, active until it comes back.
+    Tilt(TiltDirection),
+}
+
+/// Thresholds of the motion gestures and their actions.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GestureConfig {
+    /// Difference between the acceleration and 1 g counted as a shake movement, in g.
+    pub shake_threshold: f32,
+    /// Movements needed within the shake window.
+    pub shake_count: u32,
+    /// In milliseconds.
+    pub shake_window: u64,
+    /// Sudden acceleration across the screen counted as a tap, in g.
+    pub tap_threshold: f32,
+    /// Tilt triggering the tilt gestures, in degrees.
+    pub tilt_angle: f32,
+    /// Forward tilt of the Vita at rest, in degrees from flat.
+    pub neutral_pitch: f32,
+    /// Time a shake or a tap keeps its action active, in milliseconds.
+    pub hold_time: u64,
+    /// Time after a shake or a tap during which it is not detected again, in milliseconds.
+    pub cooldown: u64,
+    pub bindings: Vec<(Gesture, TouchAction)>,
+}
+
+impl Default for GestureConfig {
+    fn default() -> Self {
+        GestureConfig {
+            shake_threshold: 1.0,
+            shake_count: 3,
+            shake_window: 600,
+            tap_threshold: 0.5,
+            tilt_angle: 30.0,
+            neutral_pitch: 0.0,
+            hold_time: 100,
+            cooldown: 500,
+            bindings: Vec::new(),
+        }
+    }
+}
+
+impl GestureConfig {
+    /// Returns the actions bound to the active gestures.
+    pub fn actions(&self, gestures: &[Gesture]) -> Vec<TouchAction> {
+        self.bindings
+            .iter()
+            .filter(|(gesture, _)| gestures.contains(gesture))
+            .map(|(_, action)| action.clone())
+            .collect()
+    }
+}
+
+/// Shake or tap detected at a timestamp.
+#[derive(Clone, Copy, Debug, Default)]
+struct Trigger {
+    /// End of the action.
+    until: u64,
+    /// End of the cooldown.
+    ready_at: u64,
+}
+
+impl Trigger {
+    #[inline]
+    fn ready(&self, timestamp: u64) -> bool {
+        timestamp >= self.ready_at
+    }
+
+    #[inline]
+    fn fire(&mut self, timestamp: u64, config: &GestureConfig) {
+        self.until = timestamp + config.hold_time * 1000;
+        self.ready_at = timestamp + config.cooldown * 1000;
+    }
+
+    #[inline]
+    fn active(&self, timestamp: u64) -> bool {
+        timestamp < self.until
+    }
+}
+
+/// Detects the motion gestures from the stream of motion samples.
+///
+/// Shakes and taps stay active for the hold time, so the detector has to be updated
+/// until the timestamp returned by [`GestureDetector::next_deadline`].
+#[derive(Clone, Debug, Default)]
+pub struct GestureDetector {
+    /// Timestamps of the recent shake movements.
+    shake_movements: VecDeque<u64>,
+    shaking: bool,
+    shake: Trigger,
+    /// Low pass filtered acceleration.
+    baseline: Option<Vector3>,
+    tap: Trigger,
+    tilts: Vec<TiltDirection>,
+    previous_timestamp: Option<u64>,
+}
+
+impl GestureDetector {
+    /// Processes a motion sample and returns the active gestures.
+    pub fn update(
+        &mut self,
+        motion: &MotionState,
+        timestamp: u64,
+        motion_config: &MotionConfig,
+        config: &GestureConfig,
+    ) -> Vec<Gesture> {
+        let dt = self.previous_timestamp.map_or(0.0, |previous| {
+            timestamp.saturating_sub(previous) as f32 / 1_000_000.0
+        });
+        self.previous_timestamp = Some(timestamp);
+
+        self.detect_shake(motion.accelerometer, timestamp, config);
+        self.detect_tap(motion.accelerometer, timestamp, dt, config);
+        self.detect_tilts(motion, motion_config, config);
+        self.active(timestamp)
+    }
+
+    /// Returns the gestures still active at `timestamp`, without a new motion sample.
+    pub fn active(&self, timestamp: u64) -> Vec<Gesture> {
+        let mut gestures: Vec<Gesture> = self.tilts.iter().copied().map(Gesture::Tilt).collect();
+        if self.shake.active(timestamp) {
+            gestures.push(Gesture::Shake);
+        }
+        if self.tap.active(timestamp) {
+            gestures.push(Gesture::BackTap);
+        }
+        gestures
+    }
+
+    /// Returns the next timestamp at which a shake or a tap ends.
+    pub fn next_deadline(&self, timestamp: u64) -> Option<u64> {
+        [self.shake.until, self.tap.until]
+            .into_iter()
+            .filter(|&until| until > timestamp)
+            .min()
+    }
+
+    fn detect_shake(&mut self, accelerometer: Vector3, timestamp: u64, config: &GestureConfig) {
+        // A movement is counted when the acceleration goes past the threshold
+        let strong = (length(accelerometer) - 1.0).abs() >= config.shake_threshold;
+        if strong && !self.shaking {
+            self.shake_movements.push_back(timestamp);
+        }
+        self.shaking = strong;
+
+        let window = config.shake_window * 1000;
+        while self
+            .shake_movements
+            .front()
+            .is_some_and(|&start| timestamp.saturating_sub(start) > window)
+        {
+            self.shake_movements.pop_front();
+        }
+
+        if self.shake_movements.len() >= config.shake_count as usize && self.shake.ready(timestamp)
+        {
+            self.shake.fire(timestamp, config);
+            self.shake_movements.clear();
+        }
+    }
+
+    fn detect_tap(
+        &mut self,
+        accelerometer: Vector3,
+        timestamp: u64,
+        dt: f32,
+        config: &GestureConfig,
+    ) {
+        let baseline = *self.baseline.get_or_insert(accelerometer);
+        let alpha = (dt / TAP_BASELINE_TIME).clamp(0.0, 1.0);
+        self.baseline = Some(Vector3 {
+            x: baseline.x + (accelerometer.x - baseline.x) * alpha,
+            y: baseline.y + (accelerometer.y - baseline.y) * alpha,
+            z: baseline.z + (accelerometer.z - baseline.z) * alpha,
+        });
+
+        // A tap is a spike across the screen, the other axes barely move
+        let across = (accelerometer.z - baseline.z).abs();
+        let along = ((accelerometer.x - baseline.x).powi(2)
+            + (accelerometer.y - baseline.y).powi(2))
+        .sqrt();
+        if across >= config.tap_threshold
+            && along < config.tap_threshold / 2.0
+            && self.shake_movements.is_empty()
+            && self.tap.ready(timestamp)
+        {
+            self.tap.fire(timestamp, config);
+        }
+    }
+
+    fn detect_tilts(
+        &mut self,
+        motion: &MotionState,
+        motion_config: &MotionConfig,
+        config: &GestureConfig,
+    ) {
+        let gravity = motion_config.controller_accelerometer(motion.gravity());
+        let norm = length(gravity);
+        if norm <= f32::EPSILON {
+            return;
+        }
+        let roll = (-gravity.x / norm).clamp(-1.0, 1.0).asin().to_degrees();
+        let pitch = gravity.z.atan2(gravity.y).to_degrees() - config.neutral_pitch;
+        let pitch = (pitch + 540.0).rem_euclid(360.0) - 180.0;
+
+        for (direction, angle) in [
+            (TiltDirection::Right, roll),
+            (TiltDirection::Left, -roll),
+            (TiltDirection::Forward, pitch),
+            (TiltDirection::Back, -pitch),
+        ] {
+            let active = self.tilts.contains(&direction);
+            let threshold = if active {
+                config.tilt_angle - TILT_HYSTERESIS
+            } else {
+                config.tilt_angle
+            };
+            if angle >= threshold && !active {
+                self.tilts.push(direction);
+            } else if angle < threshold && active {
+                self.tilts.retain(|&tilt| tilt != direction);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Samples every 5 ms, from accelerations in g.
+    fn stream(accelerations: &[(f32, f32, f32)]) -> Vec<(MotionState, u64)> {
+        accelerations
+            .iter()
+            .enumerate()
+            .map(|(i, &(x, y, z))| {
+                let motion = MotionState {
+                    accelerometer: Vector3 { x, y, z },
+                    ..Default::default()
+                };
+                (motion, i as u64 * 5_000)
+            })
+            .collect()
+    }
+
+    fn detect(samples: &[(MotionState, u64)], config: &GestureConfig) -> Vec<Vec<Gesture>> {
+        let mut detector = GestureDetector::default();
+        samples
+            .iter()
+            .map(|(motion, timestamp)| {
+                detector.update(motion, *timestamp, &MotionConfig::default(), config)
+            })
+            .collect()
+    }
+
+    const FLAT: (f32, f32, f32) = (0.0, 0.0, -1.0);
+
+    #[test]
+    fn test_shake() {
+        let config = GestureConfig::default();
+        let mut accelerations = vec![FLAT; 10];
+        for _ in 0..3 {
+            accelerations.extend([
+                (2.5, 0.0, -1.0),
+                (2.5, 0.0, -1.0),
+                FLAT,
+                (-2.0, 0.0, -1.0),
+                FLAT,
+            ]);
+        }
+        accelerations.extend([FLAT; 40]);
+        let detected = detect(&stream(&accelerations), &config);
+
+        let shakes: Vec<usize> = detected
+            .iter()
+            .enumerate()
+            .filter(|(_, gestures)| gestures.contains(&Gesture::Shake))
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(
+            shakes.first(),
+            Some(&15),
+            "Third movement should trigger the shake"
+        );
+        assert_eq!(
+            shakes.len(),
+            20,
+            "Shake should stay active for the hold time"
+        );
+        assert!(
+            detected
+                .iter()
+                .all(|gestures| !gestures.contains(&Gesture::BackTap)),
+            "Shake should not be taken for a tap"
+        );
+    }
+
+    #[test]
+    fn test_hold_between_samples() {
+        let config = GestureConfig::default();
+        let mut detector = GestureDetector::default();
+        for (motion, timestamp) in stream(&[FLAT, (0.0, 0.0, -1.8), FLAT]) {
+            detector.update(&motion, timestamp, &MotionConfig::default(), &config);
+        }
+
+        let end = detector.next_deadline(10_000).expect("Tap should be held");
+        assert_eq!(detector.active(end - 1), vec![Gesture::BackTap]);
+        assert!(
+            detector.active(end).is_empty(),
+            "Tap should end without a new sample"
+        );
+        assert_eq!(detector.next_deadline(end), None);
+    }
+
+    #[test]
+    fn test_back_tap_cooldown() {
+        let config = GestureConfig::default();
+        let mut accelerations = vec![FLAT; 20];
+        accelerations.extend([(0.0, 0.0, -1.8), FLAT]);
+        accelerations.extend([FLAT; 10]);
+        accelerations.extend([(0.0, 0.0, -1.8), FLAT]);
+        accelerations.extend([FLAT; 100]);
+        accelerations.extend([(0.0, 0.0, -1.8), FLAT]);
+        let detected = detect(&stream(&accelerations), &config);
+
+        let taps: Vec<usize> = detected
+            .iter()
+            .enumerate()
+            .filter(|(i, gestures)| {
+                gestures.contains(&Gesture::BackTap)
+                    && (*i == 0 || !detected[i - 1].contains(&Gesture::BackTap))
+            })
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(taps, [20, 134], "Second tap should be in the cooldown");
+    }
+
+    #[test]
+    fn test_tilt() {
+        let config = GestureConfig::default();
+        let tilted = |degrees: f32| {
+            let angle = degrees.to_radians();
+            (angle.sin(), 0.0, -angle.cos())
+        };
+        let detected = detect(
+            &stream(&[
+                FLAT,
+                tilted(35.0),
+                tilted(27.0),
+                tilted(20.0),
+                tilted(-40.0),
+            ]),
+            &config,
+        );
+
+        assert!(detected[0].is_empty());
+        assert_eq!(detected[1], [Gesture::Tilt(TiltDirection::Right)]);
+        assert_eq!(
+            detected[2],
+            [Gesture::Tilt(TiltDirection::Right)],
+            "Tilt should stay active close to the threshold"
+        );
+        assert!(detected[3].is_empty());
+        assert_eq!(detected[4], [Gesture::Tilt(TiltDirection::Left)]);
+    }
+}
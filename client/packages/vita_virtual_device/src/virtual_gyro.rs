@@ -0,0 +1,435 @@
+use serde::{Deserialize, Serialize};
+use vita_reports::{ButtonsData, MainReport, Vector3};
+
+use crate::virtual_button::VitaButton;
+use crate::virtual_motion::{length, MotionConfig, MotionState};
+use crate::virtual_mouse::{take_whole, MouseOutput};
+use crate::virtual_stick::{from_deflection, to_deflection, Stick};
+
+/// How much a world space yaw can grow to follow a tilted Vita in player space.
+const PLAYER_SPACE_RELAX: f32 = 1.41;
+
+/// Axes the rotations of the Vita are measured around.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum GyroSpace {
+    /// Around the axes of the controller, turning is done by rotating the Vita held flat.
+    #[default]
+    Local,
+    /// Around the gravity, turning is the same however the Vita is tilted.
+    World,
+    /// Around the gravity, but turning can also be done by rolling the Vita.
+    Player,
+}
+
+/// When the gyro is used.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum GyroActivation {
+    #[default]
+    Always,
+    /// Only while the button is held.
+    WhileHeld(VitaButton),
+    /// Except while the button is held, to move the Vita back without turning, like lifting a mouse.
+    OffWhileHeld(VitaButton),
+}
+
+impl GyroActivation {
+    /// Returns whether the gyro is used, the activation button does not reach the gamepad.
+    pub(crate) fn take(&self, buttons: &mut ButtonsData) -> bool {
+        match *self {
+            GyroActivation::Always => true,
+            GyroActivation::WhileHeld(button) => {
+                let pressed = button.is_pressed(buttons);
+                button.release(buttons);
+                pressed
+            }
+            GyroActivation::OffWhileHeld(button) => {
+                let pressed = button.is_pressed(buttons);
+                button.release(buttons);
+                !pressed
+            }
+        }
+    }
+}
+
+/// Yaw and pitch rates of the controller in the space, in degrees per second.
+pub(crate) fn turn_rates(
+    motion: &MotionState,
+    motion_config: &MotionConfig,
+    space: GyroSpace,
+) -> (f32, f32) {
+    let gyro = motion_config.controller_gyro(motion.gyro);
+    let pitch = gyro.x.to_degrees();
+
+    let gravity = motion_config.controller_accelerometer(motion.gravity());
+    let norm = length(gravity);
+    if space == GyroSpace::Local || norm <= f32::EPSILON {
+        return (gyro.y.to_degrees(), pitch);
+    }
+    let gravity = Vector3 {
+        x: gravity.x / norm,
+        y: gravity.y / norm,
+        z: gravity.z / norm,
+    };
+
+    let yaw = match space {
+        GyroSpace::World => gyro.x * gravity.x + gyro.y * gravity.y + gyro.z * gravity.z,
+        _ => {
+            let world_yaw = gyro.y * gravity.y + gyro.z * gravity.z;
+            let limit = (gyro.y * gyro.y + gyro.z * gyro.z).sqrt();
+            (world_yaw.abs() * PLAYER_SPACE_RELAX)
+                .min(limit)
+                .copysign(world_yaw)
+        }
+    };
+    (yaw.to_degrees(), pitch)
+}
+
+/// Configuration for aiming with the gyro as a mouse.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GyroMouseConfig {
+    /// Mouse units per degree of rotation, horizontally and vertically.
+    pub sensitivity: (f32, f32),
+    /// Rotation speed below which the movement is smoothed, in degrees per second, 0 disables it.
+    pub smoothing_threshold: f32,
+    /// Time slow movements are smoothed over, in milliseconds.
+    pub smoothing_time: u64,
+    pub activation: GyroActivation,
+    pub space: GyroSpace,
+    pub invert_x: bool,
+    pub invert_y: bool,
+}
+
+impl Default for GyroMouseConfig {
+    fn default() -> Self {
+        GyroMouseConfig {
+            sensitivity: (10.0, 10.0),
+            smoothing_threshold: 5.0,
+            smoothing_time: 100,
+            activation: GyroActivation::Always,
+            space: GyroSpace::Local,
+            invert_x: false,
+            invert_y: false,
+        }
+    }
+}
+
+/// Converts the rotation of the Vita into relative mouse output.
+#[derive(Clone, Debug, Default)]
+pub struct GyroMouse {
+    previous_timestamp: Option<u64>,
+    smoothed: (f32, f32),
+    remainder: (f32, f32),
+}
+
+impl GyroMouse {
+    pub fn update(
+        &mut self,
+        motion: &MotionState,
+        buttons: &mut ButtonsData,
+        timestamp: u64,
+        motion_config: &MotionConfig,
+        config: &GyroMouseConfig,
+    ) -> MouseOutput {
+        let active = config.activation.take(buttons);
+        let dt = self.previous_timestamp.map_or(0.0, |previous| {
+            timestamp.saturating_sub(previous) as f32 / 1_000_000.0
+        });
+        self.previous_timestamp = Some(timestamp);
+
+        if !active {
+            self.smoothed = (0.0, 0.0);
+            self.remainder = (0.0, 0.0);
+            return MouseOutput::default();
+        }
+
+        let (yaw, pitch) = turn_rates(motion, motion_config, config.space);
+        let (yaw, pitch) = self.smooth(yaw, pitch, dt, config);
+
+        // Turning left and tilting up move the pointer left and up
+        let dx = -yaw * dt * config.sensitivity.0;
+        let dy = -pitch * dt * config.sensitivity.1;
+        let dx = if config.invert_x { -dx } else { dx };
+        let dy = if config.invert_y { -dy } else { dy };
+
+        MouseOutput {
+            dx: take_whole(dx, &mut self.remainder.0),
+            dy: take_whole(dy, &mut self.remainder.1),
+            ..Default::default()
+        }
+    }
+
+    /// Smooths slow rotations to hide the gyro noise, fast rotations are kept as they are.
+    fn smooth(&mut self, yaw: f32, pitch: f32, dt: f32, config: &GyroMouseConfig) -> (f32, f32) {
+        let window = config.smoothing_time as f32 / 1000.0;
+        let alpha = if window > 0.0 {
+            (dt / window).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        self.smoothed.0 += (yaw - self.smoothed.0) * alpha;
+        self.smoothed.1 += (pitch - self.smoothed.1) * alpha;
+
+        let threshold = config.smoothing_threshold;
+        if threshold <= 0.0 {
+            return (yaw, pitch);
+        }
+        let speed = (yaw * yaw + pitch * pitch).sqrt();
+        let direct = ((speed - threshold / 2.0) / (threshold / 2.0)).clamp(0.0, 1.0);
+        (
+            yaw * direct + self.smoothed.0 * (1.0 - direct),
+            pitch * direct + self.smoothed.1 * (1.0 - direct),
+        )
+    }
+}
+
+/// Motion driving the gyro stick.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum GyroStickInput {
+    /// Tilt of the Vita for steering: rolling moves the stick horizontally, tilting forward
+    /// and back from the position at activation moves it vertically.
+    #[default]
+    Tilt,
+    /// Rotation speed, for aiming in games without motion or mouse support.
+    Rate,
+}
+
+/// How the gyro deflection is combined with the physical stick.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum StickMix {
+    /// The larger deflection of each axis is used.
+    #[default]
+    Max,
+    /// Both deflections are added.
+    Add,
+    /// The physical stick is ignored.
+    Replace,
+}
+
+/// Configuration for moving a stick with the motion of the Vita.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GyroStickConfig {
+    pub stick: Stick,
+    pub input: GyroStickInput,
+    /// Tilt giving a full deflection, in degrees.
+    pub max_angle: f32,
+    /// Rotation speed giving a full deflection, in degrees per second.
+    pub max_rate: f32,
+    /// Deflection below which the stick stays centered, from 0 to 1.
+    pub deadzone: f32,
+    /// Moves the stick horizontally.
+    pub horizontal: bool,
+    /// Moves the stick vertically.
+    pub vertical: bool,
+    pub mix: StickMix,
+    pub activation: GyroActivation,
+    /// Space of the rotation speed.
+    pub space: GyroSpace,
+    pub invert_x: bool,
+    pub invert_y: bool,
+}
+
+impl Default for GyroStickConfig {
+    fn default() -> Self {
+        GyroStickConfig {
+            stick: Stick::Right,
+            input: GyroStickInput::Tilt,
+            max_angle: 45.0,
+            max_rate: 360.0,
+            deadzone: 0.05,
+            horizontal: true,
+            vertical: true,
+            mix: StickMix::Max,
+            activation: GyroActivation::Always,
+            space: GyroSpace::Local,
+            invert_x: false,
+            invert_y: false,
+        }
+    }
+}
+
+/// Moves a stick with the tilt or the rotation speed of the Vita.
+#[derive(Clone, Debug, Default)]
+pub struct GyroStick {
+    /// Forward tilt at activation, in degrees.
+    neutral_pitch: Option<f32>,
+}
+
+impl GyroStick {
+    pub fn update(
+        &mut self,
+        motion: &MotionState,
+        report: &mut MainReport,
+        motion_config: &MotionConfig,
+        config: &GyroStickConfig,
+    ) {
+        if !config.activation.take(&mut report.buttons) {
+            self.neutral_pitch = None;
+            return;
+        }
+
+        let (x, y) = match config.input {
+            GyroStickInput::Tilt => {
+                let gravity = motion_config.controller_accelerometer(motion.gravity());
+                let norm = length(gravity);
+                if norm <= f32::EPSILON {
+                    return;
+                }
+                let max_angle = config.max_angle.max(f32::EPSILON);
+                let roll = (-gravity.x / norm).clamp(-1.0, 1.0).asin().to_degrees();
+                let pitch = gravity.z.atan2(gravity.y).to_degrees();
+                let neutral = *self.neutral_pitch.get_or_insert(pitch);
+                let pitch = (pitch - neutral + 540.0).rem_euclid(360.0) - 180.0;
+                (roll / max_angle, pitch / max_angle)
+            }
+            GyroStickInput::Rate => {
+                let (yaw, pitch) = turn_rates(motion, motion_config, config.space);
+                let max_rate = config.max_rate.max(f32::EPSILON);
+                (-yaw / max_rate, -pitch / max_rate)
+            }
+        };
+
+        // Radial deadzone, rescaled so the deflection starts from zero
+        let magnitude = (x * x + y * y).sqrt();
+        let (x, y) = if magnitude <= config.deadzone || magnitude <= f32::EPSILON {
+            (0.0, 0.0)
+        } else {
+            let live_range = (1.0 - config.deadzone).max(f32::EPSILON);
+            let scale = (magnitude - config.deadzone) / live_range / magnitude;
+            (x * scale, y * scale)
+        };
+        let x = if config.invert_x { -x } else { x };
+        let y = if config.invert_y { -y } else { y };
+
+        let mix = |physical: &mut u8, gyro: f32| {
+            let value = to_deflection(*physical);
+            let mixed = match config.mix {
+                StickMix::Max if value.abs() > gyro.abs() => value,
+                StickMix::Max | StickMix::Replace => gyro,
+                StickMix::Add => value + gyro,
+            };
+            *physical = from_deflection(mixed);
+        };
+        let (stick_x, stick_y) = config.stick.position(report);
+        if config.horizontal {
+            mix(stick_x, x);
+        }
+        if config.vertical {
+            mix(stick_y, y);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(gyro: (f32, f32, f32), accelerometer: (f32, f32, f32)) -> MotionState {
+        MotionState {
+            gyro: Vector3 {
+                x: gyro.0,
+                y: gyro.1,
+                z: gyro.2,
+            },
+            accelerometer: Vector3 {
+                x: accelerometer.0,
+                y: accelerometer.1,
+                z: accelerometer.2,
+            },
+            orientation: None,
+        }
+    }
+
+    #[test]
+    fn test_turn_spaces() {
+        let config = MotionConfig::default();
+        // Vita held flat, rotated around the gravity
+        let flat = state((0.0, 0.0, 1.0), (0.0, 0.0, -1.0));
+        let local = turn_rates(&flat, &config, GyroSpace::Local);
+        assert!((local.0 - 57.29578).abs() < 1e-3);
+        assert_eq!(turn_rates(&flat, &config, GyroSpace::World), local);
+        assert_eq!(turn_rates(&flat, &config, GyroSpace::Player), local);
+
+        // Vita standing upright, rotated around the gravity
+        let upright = state((0.0, -1.0, 0.0), (0.0, 1.0, 0.0));
+        assert!(turn_rates(&upright, &config, GyroSpace::Local).0.abs() < 1e-3);
+        let world = turn_rates(&upright, &config, GyroSpace::World);
+        assert!(
+            (world.0 - 57.29578).abs() < 1e-3,
+            "World space should turn around the gravity"
+        );
+    }
+
+    #[test]
+    fn test_gyro_stick() {
+        let motion_config = MotionConfig::default();
+        let mut gyro_stick = GyroStick::default();
+        let steering = GyroStickConfig {
+            stick: Stick::Left,
+            vertical: false,
+            ..Default::default()
+        };
+
+        // Vita held flat then rolled by 22.5 degrees
+        let mut report = MainReport {
+            lx: 128,
+            ly: 128,
+            ..Default::default()
+        };
+        let flat = state((0.0, 0.0, 0.0), (0.0, 0.0, -1.0));
+        gyro_stick.update(&flat, &mut report, &motion_config, &steering);
+        assert_eq!((report.lx, report.ly), (128, 128));
+
+        let angle = 22.5f32.to_radians();
+        let rolled = state((0.0, 0.0, 0.0), (angle.sin(), 0.0, -angle.cos()));
+        gyro_stick.update(&rolled, &mut report, &motion_config, &steering);
+        assert!(
+            (report.lx as i32 - 128).abs() > 50,
+            "Half of the maximum angle should deflect the stick"
+        );
+        assert_eq!(report.ly, 128, "Vertical axis should be left to the stick");
+
+        let aiming = GyroStickConfig {
+            input: GyroStickInput::Rate,
+            mix: StickMix::Add,
+            ..Default::default()
+        };
+        let mut report = MainReport {
+            rx: 0,
+            ry: 128,
+            ..Default::default()
+        };
+        let turning = state((0.0, 0.0, -2.0 * std::f32::consts::PI), (0.0, 0.0, -1.0));
+        gyro_stick.update(&turning, &mut report, &motion_config, &aiming);
+        assert_eq!(
+            report.rx, 128,
+            "Turning right should cancel the stick held left"
+        );
+    }
+
+    #[test]
+    fn test_gyro_mouse() {
+        let motion_config = MotionConfig::default();
+        let config = GyroMouseConfig {
+            smoothing_threshold: 0.0,
+            activation: GyroActivation::OffWhileHeld(VitaButton::L),
+            ..Default::default()
+        };
+        let mut mouse = GyroMouse::default();
+        let turning = state((0.0, 0.0, 1.0), (0.0, 0.0, -1.0));
+
+        let mut buttons = ButtonsData::default();
+        mouse.update(&turning, &mut buttons, 0, &motion_config, &config);
+        let output = mouse.update(&turning, &mut buttons, 10_000, &motion_config, &config);
+        // 57.3 degrees per second for 10 ms at 10 units per degree
+        assert_eq!(output.dx, -5);
+        assert_eq!(output.dy, 0);
+
+        let mut buttons = ButtonsData::default();
+        VitaButton::L.press(&mut buttons);
+        let output = mouse.update(&turning, &mut buttons, 20_000, &motion_config, &config);
+        assert!(output.is_empty(), "Ratchet button should stop the gyro");
+        assert!(!buttons.lt, "Ratchet button should not reach the gamepad");
+    }
+}
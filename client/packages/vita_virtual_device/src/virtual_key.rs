@@ -0,0 +1,381 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// Keyboard keys that can be emitted, independent of the platform.
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum KeyboardKey {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+    I,
+    J,
+    K,
+    L,
+    M,
+    N,
+    O,
+    P,
+    Q,
+    R,
+    S,
+    T,
+    U,
+    V,
+    W,
+    X,
+    Y,
+    Z,
+    Num0,
+    Num1,
+    Num2,
+    Num3,
+    Num4,
+    Num5,
+    Num6,
+    Num7,
+    Num8,
+    Num9,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    F13,
+    F14,
+    F15,
+    F16,
+    F17,
+    F18,
+    F19,
+    F20,
+    F21,
+    F22,
+    F23,
+    F24,
+    Escape,
+    Enter,
+    Tab,
+    Space,
+    Backspace,
+    Delete,
+    Insert,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Up,
+    Down,
+    Left,
+    Right,
+    Minus,
+    Equal,
+    LeftBracket,
+    RightBracket,
+    Backslash,
+    Semicolon,
+    Apostrophe,
+    Grave,
+    Comma,
+    Period,
+    Slash,
+    CapsLock,
+    PrintScreen,
+    ScrollLock,
+    Pause,
+    LeftCtrl,
+    RightCtrl,
+    LeftShift,
+    RightShift,
+    LeftAlt,
+    RightAlt,
+    LeftMeta,
+    RightMeta,
+    VolumeUp,
+    VolumeDown,
+    Mute,
+    PlayPause,
+    NextTrack,
+    PreviousTrack,
+    Stop,
+}
+
+impl KeyboardKey {
+    /// All keys, used to register them on the virtual keyboard.
+    pub const ALL: &'static [KeyboardKey] = {
+        use KeyboardKey::*;
+        &[
+            A,
+            B,
+            C,
+            D,
+            E,
+            F,
+            G,
+            H,
+            I,
+            J,
+            K,
+            L,
+            M,
+            N,
+            O,
+            P,
+            Q,
+            R,
+            S,
+            T,
+            U,
+            V,
+            W,
+            X,
+            Y,
+            Z,
+            Num0,
+            Num1,
+            Num2,
+            Num3,
+            Num4,
+            Num5,
+            Num6,
+            Num7,
+            Num8,
+            Num9,
+            F1,
+            F2,
+            F3,
+            F4,
+            F5,
+            F6,
+            F7,
+            F8,
+            F9,
+            F10,
+            F11,
+            F12,
+            F13,
+            F14,
+            F15,
+            F16,
+            F17,
+            F18,
+            F19,
+            F20,
+            F21,
+            F22,
+            F23,
+            F24,
+            Escape,
+            Enter,
+            Tab,
+            Space,
+            Backspace,
+            Delete,
+            Insert,
+            Home,
+            End,
+            PageUp,
+            PageDown,
+            Up,
+            Down,
+            Left,
+            Right,
+            Minus,
+            Equal,
+            LeftBracket,
+            RightBracket,
+            Backslash,
+            Semicolon,
+            Apostrophe,
+            Grave,
+            Comma,
+            Period,
+            Slash,
+            CapsLock,
+            PrintScreen,
+            ScrollLock,
+            Pause,
+            LeftCtrl,
+            RightCtrl,
+            LeftShift,
+            RightShift,
+            LeftAlt,
+            RightAlt,
+            LeftMeta,
+            RightMeta,
+            VolumeUp,
+            VolumeDown,
+            Mute,
+            PlayPause,
+            NextTrack,
+            PreviousTrack,
+            Stop,
+        ]
+    };
+}
+
+impl fmt::Display for KeyboardKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl FromStr for KeyboardKey {
+    type Err = String;
+
+    /// Parses a key by its name (case insensitive) or a common alias.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_ascii_lowercase();
+        let key = match name.as_str() {
+            "ctrl" | "control" => Some(KeyboardKey::LeftCtrl),
+            "shift" => Some(KeyboardKey::LeftShift),
+            "alt" => Some(KeyboardKey::LeftAlt),
+            "meta" | "super" | "win" => Some(KeyboardKey::LeftMeta),
+            "esc" => Some(KeyboardKey::Escape),
+            "return" => Some(KeyboardKey::Enter),
+            "del" => Some(KeyboardKey::Delete),
+            "pgup" => Some(KeyboardKey::PageUp),
+            "pgdn" => Some(KeyboardKey::PageDown),
+            "-" => Some(KeyboardKey::Minus),
+            "=" => Some(KeyboardKey::Equal),
+            _ if name.len() == 1 && name.as_bytes()[0].is_ascii_digit() => KeyboardKey::ALL
+                .iter()
+                .copied()
+                .find(|key| key.to_string() == format!("Num{name}")),
+            _ => KeyboardKey::ALL
+                .iter()
+                .copied()
+                .find(|key| key.to_string().to_ascii_lowercase() == name),
+        };
+        key.ok_or_else(|| format!("Unknown key: {s}"))
+    }
+}
+
+/// Keys pressed together, in order, and released in reverse order.
+///
+/// Written as key names joined by `+`, like `Ctrl+Shift+Z` or `PlayPause`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct KeyCombo(pub Vec<KeyboardKey>);
+
+impl fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<String> = self.0.iter().map(ToString::to_string).collect();
+        f.write_str(&names.join("+"))
+    }
+}
+
+impl FromStr for KeyCombo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let keys = s
+            .split('+')
+            .map(KeyboardKey::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(KeyCombo(keys))
+    }
+}
+
+impl TryFrom<String> for KeyCombo {
+    type Error = String;
+
+    #[inline]
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<KeyCombo> for String {
+    #[inline]
+    fn from(combo: KeyCombo) -> Self {
+        combo.to_string()
+    }
+}
+
+/// Tracks the held keys and computes the transitions for the keys held in this report.
+#[derive(Clone, Debug, Default)]
+pub struct HeldKeys {
+    held: Vec<KeyboardKey>,
+}
+
+impl HeldKeys {
+    /// Returns the keys to press and to release, both in the order they should be sent.
+    pub fn update(&mut self, keys: Vec<KeyboardKey>) -> (Vec<KeyboardKey>, Vec<KeyboardKey>) {
+        let mut held = Vec::with_capacity(keys.len());
+        for key in keys {
+            if !held.contains(&key) {
+                held.push(key);
+            }
+        }
+
+        let pressed = held
+            .iter()
+            .filter(|key| !self.held.contains(key))
+            .copied()
+            .collect();
+        let released = self
+            .held
+            .iter()
+            .rev()
+            .filter(|key| !held.contains(key))
+            .copied()
+            .collect();
+
+        self.held = held;
+        (pressed, released)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key_combo() {
+        let combo: KeyCombo = "ctrl+Shift+z".parse().unwrap();
+        assert_eq!(
+            combo.0,
+            [
+                KeyboardKey::LeftCtrl,
+                KeyboardKey::LeftShift,
+                KeyboardKey::Z
+            ]
+        );
+        assert_eq!(combo.to_string(), "LeftCtrl+LeftShift+Z");
+        assert_eq!("1".parse::<KeyboardKey>(), Ok(KeyboardKey::Num1));
+        assert!("Ctrl+Nope".parse::<KeyCombo>().is_err());
+    }
+
+    #[test]
+    fn test_held_keys_transitions() {
+        let mut held_keys = HeldKeys::default();
+
+        let (pressed, released) = held_keys.update(vec![KeyboardKey::LeftCtrl, KeyboardKey::C]);
+        assert_eq!(pressed, [KeyboardKey::LeftCtrl, KeyboardKey::C]);
+        assert!(released.is_empty());
+
+        let (pressed, released) = held_keys.update(vec![KeyboardKey::LeftCtrl, KeyboardKey::C]);
+        assert!(
+            pressed.is_empty() && released.is_empty(),
+            "Held keys should not repeat"
+        );
+
+        let (pressed, released) = held_keys.update(vec![]);
+        assert!(pressed.is_empty());
+        assert_eq!(released, [KeyboardKey::C, KeyboardKey::LeftCtrl]);
+    }
+}
@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use vita_reports::MainReport;
+
+use crate::virtual_button::VitaButton;
+use crate::virtual_touch::TouchAction;
+
+/// Center value of the Vita sticks.
+const STICK_CENTER: u8 = 128;
+
+/// Stick displacement below which a recorded stick is left to the player.
+const RECORD_STICK_DEADZONE: u8 = 16;
+
+/// Stick movement tolerated within a single recorded step.
+const RECORD_STICK_TOLERANCE: u8 = 8;
+
+/// Timed sequence of inputs played on top of the physical inputs.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct Macro {
+    pub steps: Vec<MacroStep>,
+}
+
+/// Inputs held during one step of a macro.
+///
+/// A step without buttons or sticks is a wait.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct MacroStep {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub buttons: Vec<VitaButton>,
+    /// Left stick position (x, y), the physical stick is used if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub left_stick: Option<(u8, u8)>,
+    /// Right stick position (x, y), the physical stick is used if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub right_stick: Option<(u8, u8)>,
+    /// Duration of the step in milliseconds.
+    pub duration: u64,
+}
+
+impl Macro {
+    /// Returns the step played `elapsed` milliseconds after the start,
+    /// or `None` once the macro is over.
+    pub fn step_at(&self, elapsed: u64) -> Option<&MacroStep> {
+        let mut end = 0;
+        self.steps.iter().find(|step| {
+            end += step.duration;
+            elapsed < end
+        })
+    }
+}
+
+impl MacroStep {
+    fn capture(report: &MainReport) -> Self {
+        let stick = |x: u8, y: u8| {
+            let displaced = |value: u8| value.abs_diff(STICK_CENTER) > RECORD_STICK_DEADZONE;
+            (displaced(x) || displaced(y)).then_some((x, y))
+        };
+
+        MacroStep {
+            buttons: VitaButton::ALL
+                .into_iter()
+                .filter(|button| button.is_pressed(&report.buttons))
+                .collect(),
+            left_stick: stick(report.lx, report.ly),
+            right_stick: stick(report.rx, report.ry),
+            duration: 0,
+        }
+    }
+
+    #[inline]
+    fn is_idle(&self) -> bool {
+        self.buttons.is_empty() && self.left_stick.is_none() && self.right_stick.is_none()
+    }
+
+    /// Whether both steps hold the same inputs, ignoring small stick movements.
+    fn same_inputs(&self, other: &MacroStep) -> bool {
+        let same_stick = |a: Option<(u8, u8)>, b: Option<(u8, u8)>| match (a, b) {
+            (Some(a), Some(b)) => {
+                a.0.abs_diff(b.0) <= RECORD_STICK_TOLERANCE
+                    && a.1.abs_diff(b.1) <= RECORD_STICK_TOLERANCE
+            }
+            (a, b) => a.is_none() && b.is_none(),
+        };
+
+        self.buttons == other.buttons
+            && same_stick(self.left_stick, other.left_stick)
+            && same_stick(self.right_stick, other.right_stick)
+    }
+
+    fn apply(&self, report: &mut MainReport) {
+        for button in &self.buttons {
+            button.press(&mut report.buttons);
+        }
+        if let Some((x, y)) = self.left_stick {
+            report.lx = x;
+            report.ly = y;
+        }
+        if let Some((x, y)) = self.right_stick {
+            report.rx = x;
+            report.ry = y;
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Playback {
+    name: String,
+    start: u64,
+}
+
+/// Plays the macros triggered by [`TouchAction::Macro`] actions.
+#[derive(Clone, Debug, Default)]
+pub struct MacroPlayer {
+    playbacks: Vec<Playback>,
+    /// Macros triggered in the previous report, a macro starts when its trigger is activated.
+    triggered: Vec<String>,
+}
+
+impl MacroPlayer {
+    /// Starts the newly triggered macros and applies the current step
+    /// of every playing macro to the report.
+    pub fn update(
+        &mut self,
+        actions: &[TouchAction],
+        report: &mut MainReport,
+        macros: &HashMap<String, Macro>,
+    ) {
+        let triggered: Vec<String> = actions
+            .iter()
+            .filter_map(|action| match action {
+                TouchAction::Macro(name) => Some(name.clone()),
+                _ => None,
+            })
+            .collect();
+
+        for name in &triggered {
+            let playing = self.playbacks.iter().any(|playback| &playback.name == name);
+            if !playing && !self.triggered.contains(name) {
+                self.playbacks.push(Playback {
+                    name: name.clone(),
+                    start: report.timestamp,
+                });
+            }
+        }
+        self.triggered = triggered;
+
+        self.playbacks.retain(|playback| {
+            let elapsed = report.timestamp.saturating_sub(playback.start) / 1000;
+            match macros
+                .get(&playback.name)
+                .and_then(|macro_| macro_.step_at(elapsed))
+            {
+                Some(step) => {
+                    step.apply(report);
+                    true
+                }
+                None => false,
+            }
+        });
+    }
+
+    /// Returns the timestamp at which the next step of a playing macro starts.
+    pub fn next_deadline(&self, timestamp: u64, macros: &HashMap<String, Macro>) -> Option<u64> {
+        self.playbacks
+            .iter()
+            .filter_map(|playback| {
+                let elapsed = timestamp.saturating_sub(playback.start) / 1000;
+                let mut end = 0;
+                macros.get(&playback.name)?.steps.iter().find_map(|step| {
+                    end += step.duration;
+                    (elapsed < end).then_some(playback.start + end * 1000)
+                })
+            })
+            .min()
+    }
+}
+
+/// Records a macro from the reports received from the Vita.
+///
+/// Recording starts with the first input, so the time before it is not part of the macro.
+#[derive(Clone, Debug, Default)]
+pub struct MacroRecorder {
+    steps: Vec<MacroStep>,
+    /// Step being recorded and the timestamp it started at.
+    current: Option<(MacroStep, u64)>,
+}
+
+impl MacroRecorder {
+    pub fn push(&mut self, report: &MainReport) {
+        let step = MacroStep::capture(report);
+
+        if let Some((current, start)) = &mut self.current {
+            let duration = report.timestamp.saturating_sub(*start) / 1000;
+            if current.same_inputs(&step) {
+                current.duration = duration;
+                return;
+            }
+            current.duration = duration;
+        } else if step.is_idle() {
+            return;
+        }
+
+        if let Some((previous, _)) = self.current.take() {
+            self.steps.push(previous);
+        }
+        self.current = Some((step, report.timestamp));
+    }
+
+    /// Returns the recorded macro.
+    ///
+    /// Trailing steps holding only `stop_buttons` are dropped,
+    /// so the combination used to stop the recording is not part of the macro.
+    pub fn finish(mut self, stop_buttons: &[VitaButton]) -> Macro {
+        if let Some((current, _)) = self.current.take() {
+            self.steps.push(current);
+        }
+
+        while self.steps.last().is_some_and(|step| {
+            step.left_stick.is_none()
+                && step.right_stick.is_none()
+                && step
+                    .buttons
+                    .iter()
+                    .all(|button| stop_buttons.contains(button))
+        }) {
+            self.steps.pop();
+        }
+
+        Macro { steps: self.steps }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(timestamp: u64, buttons: &[VitaButton], lx: u8) -> MainReport {
+        let mut report = MainReport {
+            lx,
+            ly: STICK_CENTER,
+            rx: STICK_CENTER,
+            ry: STICK_CENTER,
+            timestamp,
+            ..Default::default()
+        };
+        for button in buttons {
+            button.press(&mut report.buttons);
+        }
+        report
+    }
+
+    #[test]
+    fn test_record_macro() {
+        let mut recorder = MacroRecorder::default();
+        let reports = [
+            report(0, &[], STICK_CENTER),
+            report(10_000, &[VitaButton::Down], STICK_CENTER),
+            report(20_000, &[VitaButton::Down], STICK_CENTER),
+            report(60_000, &[VitaButton::Cross], 250),
+            report(70_000, &[VitaButton::Cross], 255),
+            report(100_000, &[], STICK_CENTER),
+            report(150_000, &[VitaButton::Select], STICK_CENTER),
+            report(
+                160_000,
+                &[VitaButton::Select, VitaButton::Start],
+                STICK_CENTER,
+            ),
+        ];
+        for report in &reports {
+            recorder.push(report);
+        }
+
+        let recorded = recorder.finish(&[VitaButton::Select, VitaButton::Start]);
+        assert_eq!(
+            recorded.steps,
+            [
+                MacroStep {
+                    buttons: vec![VitaButton::Down],
+                    duration: 50,
+                    ..Default::default()
+                },
+                MacroStep {
+                    buttons: vec![VitaButton::Cross],
+                    left_stick: Some((250, STICK_CENTER)),
+                    duration: 40,
+                    ..Default::default()
+                },
+            ],
+            "Idle time and stop combination should be trimmed"
+        );
+    }
+
+    #[test]
+    fn test_play_macro() {
+        let macros = HashMap::from([(
+            "combo".to_string(),
+            Macro {
+                steps: vec![
+                    MacroStep {
+                        buttons: vec![VitaButton::Down],
+                        duration: 20,
+                        ..Default::default()
+                    },
+                    MacroStep {
+                        duration: 20,
+                        ..Default::default()
+                    },
+                    MacroStep {
+                        buttons: vec![VitaButton::Square],
+                        right_stick: Some((0, 0)),
+                        duration: 20,
+                        ..Default::default()
+                    },
+                ],
+            },
+        )]);
+        let trigger = [TouchAction::Macro("combo".to_string())];
+        let mut player = MacroPlayer::default();
+
+        let mut first = report(1_000_000, &[], STICK_CENTER);
+        player.update(&trigger, &mut first, &macros);
+        assert!(first.buttons.down, "First step should play right away");
+
+        let mut wait = report(1_030_000, &[], STICK_CENTER);
+        player.update(&trigger, &mut wait, &macros);
+        assert_eq!(
+            wait,
+            report(1_030_000, &[], STICK_CENTER),
+            "Wait should not change inputs"
+        );
+
+        let mut last = report(1_050_000, &[VitaButton::Cross], STICK_CENTER);
+        player.update(&[], &mut last, &macros);
+        assert!(last.buttons.square && last.buttons.cross);
+        assert_eq!((last.rx, last.ry), (0, 0));
+
+        let mut after = report(1_070_000, &[], STICK_CENTER);
+        player.update(&[], &mut after, &macros);
+        assert!(
+            !after.buttons.down && !after.buttons.square,
+            "Macro should be over"
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use std::borrow::Cow;
+use std::mem::swap;
+
+use serde::{Deserialize, Serialize};
+use vita_reports::{MainReport, TouchReport};
+
+use crate::virtual_touch::Point;
+
+/// Transforms the controls of any profile for left-handed players.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MirrorConfig {
+    /// Swaps the left and right sticks.
+    pub swap_sticks: bool,
+    /// Swaps the face buttons with the D-Pad, mirrored horizontally:
+    /// Triangle and Up, Circle and Left, Cross and Down, Square and Right.
+    pub mirror_buttons: bool,
+    /// Swaps the L and R buttons.
+    pub swap_shoulders: bool,
+    /// Mirrors the touch zones horizontally.
+    pub mirror_touch: bool,
+}
+
+impl MirrorConfig {
+    /// All the transforms.
+    pub const LEFT_HANDED: MirrorConfig = MirrorConfig {
+        swap_sticks: true,
+        mirror_buttons: true,
+        swap_shoulders: true,
+        mirror_touch: true,
+    };
+
+    /// Swaps the sticks and buttons of the report.
+    pub fn apply(&self, report: &mut MainReport) {
+        if self.swap_sticks {
+            swap(&mut report.lx, &mut report.rx);
+            swap(&mut report.ly, &mut report.ry);
+        }
+
+        let buttons = &mut report.buttons;
+        if self.mirror_buttons {
+            swap(&mut buttons.triangle, &mut buttons.up);
+            swap(&mut buttons.circle, &mut buttons.left);
+            swap(&mut buttons.cross, &mut buttons.down);
+            swap(&mut buttons.square, &mut buttons.right);
+        }
+        if self.swap_shoulders {
+            swap(&mut buttons.lt, &mut buttons.rt);
+        }
+    }
+
+    /// Mirrors the touch reports of a panel horizontally, so they hit the mirrored touch zones.
+    pub fn touch_zone_reports<'a>(
+        &self,
+        touch_reports: &'a [TouchReport],
+        rect: (Point, Point),
+    ) -> Cow<'a, [TouchReport]> {
+        if !self.mirror_touch {
+            return Cow::Borrowed(touch_reports);
+        }
+
+        let (min_x, max_x) = (rect.0.x(), rect.1.x());
+        Cow::Owned(
+            touch_reports
+                .iter()
+                .map(|touch| TouchReport {
+                    x: (min_x + max_x - i32::from(touch.x)).clamp(min_x, max_x) as u16,
+                    ..touch.clone()
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_left_handed() {
+        let mut report = MainReport {
+            lx: 10,
+            ly: 20,
+            rx: 200,
+            ry: 210,
+            ..Default::default()
+        };
+        report.buttons.circle = true;
+        report.buttons.up = true;
+        report.buttons.lt = true;
+
+        MirrorConfig::LEFT_HANDED.apply(&mut report);
+        assert_eq!(
+            (report.lx, report.ly, report.rx, report.ry),
+            (200, 210, 10, 20)
+        );
+        assert!(report.buttons.left && !report.buttons.circle);
+        assert!(report.buttons.triangle && !report.buttons.up);
+        assert!(report.buttons.rt && !report.buttons.lt);
+
+        let touches = [TouchReport {
+            x: 100,
+            y: 50,
+            id: 1,
+            force: 0,
+            timestamp: 0,
+        }];
+        let mirrored = MirrorConfig::LEFT_HANDED
+            .touch_zone_reports(&touches, (Point(0, 0), Point(1920, 1087)));
+        assert_eq!((mirrored[0].x, mirrored[0].y), (1820, 50));
+    }
+}
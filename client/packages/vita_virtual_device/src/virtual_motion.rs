@@ -0,0 +1,658 @@
+use serde::{Deserialize, Serialize};
+use vita_reports::{MainReport, MotionData, Vector3};
+
+use crate::f32_to_i16;
+
+/// Filter estimating the orientation of the Vita from its motion sensors.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum OrientationFilter {
+    /// Orientation is not computed.
+    #[default]
+    None,
+    /// Integrates the gyro and slowly pulls the tilt towards gravity,
+    /// a higher `gain` trusts the accelerometer more.
+    Complementary { gain: f32 },
+    /// Madgwick filter, a higher `beta` corrects the gyro drift faster but is noisier.
+    Madgwick { beta: f32 },
+}
+
+/// Axis of the Vita motion sensors, possibly inverted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum SignedAxis {
+    X,
+    Y,
+    Z,
+    #[serde(rename = "-X")]
+    NegX,
+    #[serde(rename = "-Y")]
+    NegY,
+    #[serde(rename = "-Z")]
+    NegZ,
+}
+
+impl SignedAxis {
+    #[inline]
+    fn pick(self, vector: Vector3) -> f32 {
+        match self {
+            SignedAxis::X => vector.x,
+            SignedAxis::Y => vector.y,
+            SignedAxis::Z => vector.z,
+            SignedAxis::NegX => -vector.x,
+            SignedAxis::NegY => -vector.y,
+            SignedAxis::NegZ => -vector.z,
+        }
+    }
+}
+
+/// How the Vita is held, maps its motion axes to the axes of the virtual controller.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum MotionLayout {
+    /// Held like a gamepad, screen facing the player.
+    #[default]
+    Normal,
+    /// Held upside down, screen facing the player.
+    Flipped,
+    /// Held vertically with the right side of the Vita up.
+    Portrait,
+    /// Source axis of each controller axis (x, y, z).
+    Custom {
+        accelerometer: [SignedAxis; 3],
+        gyro: [SignedAxis; 3],
+    },
+}
+
+impl MotionLayout {
+    /// Returns the source axes of the accelerometer and gyro of the controller.
+    pub fn axes(&self) -> ([SignedAxis; 3], [SignedAxis; 3]) {
+        use SignedAxis::*;
+
+        match self {
+            MotionLayout::Normal => ([NegX, NegZ, Y], [X, Z, NegY]),
+            MotionLayout::Flipped => ([X, NegZ, NegY], [NegX, Z, Y]),
+            MotionLayout::Portrait => ([Y, NegZ, X], [NegY, Z, NegX]),
+            MotionLayout::Custom {
+                accelerometer,
+                gyro,
+            } => (*accelerometer, *gyro),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MotionConfig {
+    /// Estimates the gyro bias while the Vita is at rest and removes it from the rates.
+    ///
+    /// Off by default, the rates of the Vita are then sent unchanged.
+    pub gyro_calibration: bool,
+    /// Gyro rate below which the Vita may be at rest, in rad/s.
+    pub rest_gyro_threshold: f32,
+    /// Difference between the acceleration and 1 g below which the Vita may be at rest, in g.
+    pub rest_accel_threshold: f32,
+    /// Time the Vita has to stay at rest before the bias is updated, in milliseconds.
+    pub rest_time: u64,
+    pub filter: OrientationFilter,
+    pub layout: MotionLayout,
+    /// Acceleration reaching the end of the controller range, in g.
+    pub accelerometer_range: f32,
+    /// Angular rate reaching the end of the controller range, in rad/s.
+    pub gyro_range: f32,
+}
+
+impl Default for MotionConfig {
+    fn default() -> Self {
+        MotionConfig {
+            gyro_calibration: false,
+            rest_gyro_threshold: 0.05,
+            rest_accel_threshold: 0.05,
+            rest_time: 1000,
+            filter: OrientationFilter::None,
+            layout: MotionLayout::Normal,
+            accelerometer_range: 4.0,
+            gyro_range: 35.0,
+        }
+    }
+}
+
+#[inline]
+fn map_axes(axes: [SignedAxis; 3], vector: Vector3) -> Vector3 {
+    Vector3 {
+        x: axes[0].pick(vector),
+        y: axes[1].pick(vector),
+        z: axes[2].pick(vector),
+    }
+}
+
+impl MotionConfig {
+    /// Converts an acceleration of the Vita to the axes of the virtual controller.
+    #[inline]
+    pub fn controller_accelerometer(&self, accelerometer: Vector3) -> Vector3 {
+        map_axes(self.layout.axes().0, accelerometer)
+    }
+
+    /// Converts angular rates of the Vita to the axes of the virtual controller.
+    #[inline]
+    pub fn controller_gyro(&self, gyro: Vector3) -> Vector3 {
+        map_axes(self.layout.axes().1, gyro)
+    }
+
+    /// Converts the motion of the Vita to the accelerometer and gyro axes (x, y, z)
+    /// of the virtual controller, scaled to the full `i16` range.
+    pub fn controller_axes(&self, motion: &MotionData) -> ([i16; 3], [i16; 3]) {
+        let scale = |vector: Vector3, range: f32| {
+            let range = range.abs().max(f32::EPSILON);
+            [vector.x, vector.y, vector.z].map(|value| f32_to_i16(value, -range, range))
+        };
+
+        (
+            scale(
+                self.controller_accelerometer(motion.accelerometer),
+                self.accelerometer_range,
+            ),
+            scale(self.controller_gyro(motion.gyro), self.gyro_range),
+        )
+    }
+}
+
+/// Rotation as a unit quaternion.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quaternion {
+    pub w: f32,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Default for Quaternion {
+    #[inline]
+    fn default() -> Self {
+        Quaternion::IDENTITY
+    }
+}
+
+impl Quaternion {
+    pub const IDENTITY: Quaternion = Quaternion {
+        w: 1.0,
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+    };
+
+    fn normalized(self) -> Self {
+        let norm = (self.w * self.w + self.x * self.x + self.y * self.y + self.z * self.z).sqrt();
+        if norm <= f32::EPSILON {
+            return Quaternion::IDENTITY;
+        }
+        Quaternion {
+            w: self.w / norm,
+            x: self.x / norm,
+            y: self.y / norm,
+            z: self.z / norm,
+        }
+    }
+
+    /// Derivative of the rotation for the angular rates of the Vita.
+    fn derivative(self, rates: Vector3) -> Quaternion {
+        let q = self;
+        Quaternion {
+            w: 0.5 * (-q.x * rates.x - q.y * rates.y - q.z * rates.z),
+            x: 0.5 * (q.w * rates.x + q.y * rates.z - q.z * rates.y),
+            y: 0.5 * (q.w * rates.y - q.x * rates.z + q.z * rates.x),
+            z: 0.5 * (q.w * rates.z + q.x * rates.y - q.y * rates.x),
+        }
+    }
+
+    fn step(self, derivative: Quaternion, dt: f32) -> Quaternion {
+        Quaternion {
+            w: self.w + derivative.w * dt,
+            x: self.x + derivative.x * dt,
+            y: self.y + derivative.y * dt,
+            z: self.z + derivative.z * dt,
+        }
+        .normalized()
+    }
+
+    /// Direction of the world up axis in the frame of the Vita.
+    pub fn up(self) -> Vector3 {
+        let q = self;
+        Vector3 {
+            x: 2.0 * (q.x * q.z - q.w * q.y),
+            y: 2.0 * (q.w * q.x + q.y * q.z),
+            z: q.w * q.w - q.x * q.x - q.y * q.y + q.z * q.z,
+        }
+    }
+}
+
+/// Motion of the Vita after processing.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MotionState {
+    /// Angular rates with the gyro bias removed, in rad/s.
+    pub gyro: Vector3,
+    /// Acceleration in g.
+    pub accelerometer: Vector3,
+    /// Orientation of the Vita, if a filter is enabled.
+    pub orientation: Option<Quaternion>,
+}
+
+impl MotionState {
+    /// Direction of the gravity as measured by the accelerometer at rest,
+    /// from the orientation if a filter is enabled.
+    pub fn gravity(&self) -> Vector3 {
+        match self.orientation {
+            Some(orientation) => orientation.up(),
+            None => self.accelerometer,
+        }
+    }
+}
+
+#[inline]
+pub(crate) fn length(vector: Vector3) -> f32 {
+    (vector.x * vector.x + vector.y * vector.y + vector.z * vector.z).sqrt()
+}
+
+/// Corrects the gyro and estimates the orientation of the Vita.
+#[derive(Clone, Debug, Default)]
+pub struct MotionProcessor {
+    bias: Vector3,
+    /// Timestamp the Vita came to rest at.
+    rest_since: Option<u64>,
+    rest_sum: Vector3,
+    rest_samples: u32,
+    orientation: Quaternion,
+    previous_timestamp: Option<u64>,
+}
+
+impl MotionProcessor {
+    /// Current estimate of the gyro bias, in rad/s.
+    #[inline]
+    pub fn bias(&self) -> Vector3 {
+        self.bias
+    }
+
+    /// Processes a motion sample taken at `timestamp`, in microseconds.
+    ///
+    /// A sample not newer than the previous one, like a replayed sample,
+    /// is neither integrated nor counted in the gyro bias.
+    pub fn update(
+        &mut self,
+        motion: &MotionData,
+        timestamp: u64,
+        config: &MotionConfig,
+    ) -> MotionState {
+        let raw = motion.gyro;
+        let accelerometer = motion.accelerometer;
+        let new_sample = self
+            .previous_timestamp
+            .map_or(true, |previous| timestamp > previous);
+
+        if config.gyro_calibration && new_sample {
+            self.update_bias(raw, accelerometer, timestamp, config);
+        }
+        let gyro = if config.gyro_calibration {
+            Vector3 {
+                x: raw.x - self.bias.x,
+                y: raw.y - self.bias.y,
+                z: raw.z - self.bias.z,
+            }
+        } else {
+            raw
+        };
+
+        let dt = self.previous_timestamp.map_or(0.0, |previous| {
+            timestamp.saturating_sub(previous) as f32 / 1_000_000.0
+        });
+        if new_sample {
+            self.previous_timestamp = Some(timestamp);
+        }
+
+        let orientation = match config.filter {
+            OrientationFilter::None => None,
+            _ if !new_sample => Some(self.orientation),
+            OrientationFilter::Complementary { gain } => {
+                Some(self.complementary(gyro, accelerometer, gain, dt))
+            }
+            OrientationFilter::Madgwick { beta } => {
+                Some(self.madgwick(gyro, accelerometer, beta, dt))
+            }
+        };
+
+        MotionState {
+            gyro,
+            accelerometer,
+            orientation,
+        }
+    }
+
+    /// Processes the batched motion samples of a report, oldest first, then its latest one.
+    ///
+    /// The gyro bias is removed from the motion of the report. Only the samples not
+    /// processed yet are kept in it, so a replayed report has none left.
+    pub fn update_report(&mut self, report: &mut MainReport, config: &MotionConfig) -> MotionState {
+        let previous = self.previous_timestamp;
+        let latest = report.motion.timestamp;
+        let mut samples = std::mem::take(&mut report.motion_samples);
+        samples.retain(|sample| {
+            sample.timestamp < latest
+                && previous.map_or(true, |previous| sample.timestamp > previous)
+        });
+        for sample in &mut samples {
+            sample.gyro = self.update(sample, sample.timestamp, config).gyro;
+        }
+
+        let state = self.update(&report.motion, latest, config);
+        report.motion.gyro = state.gyro;
+        if previous.map_or(true, |previous| latest > previous) {
+            samples.push(report.motion.clone());
+        }
+        report.motion_samples = samples;
+        state
+    }
+
+    /// Averages the gyro rates while the Vita stays at rest.
+    fn update_bias(
+        &mut self,
+        raw: Vector3,
+        accelerometer: Vector3,
+        timestamp: u64,
+        config: &MotionConfig,
+    ) {
+        let rate = length(Vector3 {
+            x: raw.x - self.bias.x,
+            y: raw.y - self.bias.y,
+            z: raw.z - self.bias.z,
+        });
+        let still = (length(accelerometer) - 1.0).abs() < config.rest_accel_threshold;
+
+        if rate >= config.rest_gyro_threshold || !still {
+            self.rest_since = None;
+            self.rest_sum = Vector3::default();
+            self.rest_samples = 0;
+            return;
+        }
+
+        let rest_since = *self.rest_since.get_or_insert(timestamp);
+        self.rest_sum.x += raw.x;
+        self.rest_sum.y += raw.y;
+        self.rest_sum.z += raw.z;
+        self.rest_samples += 1;
+
+        if timestamp.saturating_sub(rest_since) >= config.rest_time * 1000 {
+            let samples = self.rest_samples as f32;
+            self.bias = Vector3 {
+                x: self.rest_sum.x / samples,
+                y: self.rest_sum.y / samples,
+                z: self.rest_sum.z / samples,
+            };
+        }
+    }
+
+    fn complementary(
+        &mut self,
+        gyro: Vector3,
+        accelerometer: Vector3,
+        gain: f32,
+        dt: f32,
+    ) -> Quaternion {
+        let mut rates = gyro;
+
+        // Rotate towards the measured gravity by the error between both up directions
+        let norm = length(accelerometer);
+        if norm > f32::EPSILON {
+            let a = Vector3 {
+                x: accelerometer.x / norm,
+                y: accelerometer.y / norm,
+                z: accelerometer.z / norm,
+            };
+            let up = self.orientation.up();
+            rates.x += gain * (a.y * up.z - a.z * up.y);
+            rates.y += gain * (a.z * up.x - a.x * up.z);
+            rates.z += gain * (a.x * up.y - a.y * up.x);
+        }
+
+        self.orientation = self
+            .orientation
+            .step(self.orientation.derivative(rates), dt);
+        self.orientation
+    }
+
+    fn madgwick(
+        &mut self,
+        gyro: Vector3,
+        accelerometer: Vector3,
+        beta: f32,
+        dt: f32,
+    ) -> Quaternion {
+        let q = self.orientation;
+        let mut derivative = q.derivative(gyro);
+
+        let norm = length(accelerometer);
+        if norm > f32::EPSILON {
+            let (ax, ay, az) = (
+                accelerometer.x / norm,
+                accelerometer.y / norm,
+                accelerometer.z / norm,
+            );
+            let (q0, q1, q2, q3) = (q.w, q.x, q.y, q.z);
+
+            // Gradient descent step of the error between measured and estimated gravity
+            let gradient = Quaternion {
+                w: 4.0 * q0 * q2 * q2 + 2.0 * q2 * ax + 4.0 * q0 * q1 * q1 - 2.0 * q1 * ay,
+                x: 4.0 * q1 * q3 * q3 - 2.0 * q3 * ax + 4.0 * q0 * q0 * q1
+                    - 2.0 * q0 * ay
+                    - 4.0 * q1
+                    + 8.0 * q1 * q1 * q1
+                    + 8.0 * q1 * q2 * q2
+                    + 4.0 * q1 * az,
+                y: 4.0 * q0 * q0 * q2 + 2.0 * q0 * ax + 4.0 * q2 * q3 * q3
+                    - 2.0 * q3 * ay
+                    - 4.0 * q2
+                    + 8.0 * q2 * q1 * q1
+                    + 8.0 * q2 * q2 * q2
+                    + 4.0 * q2 * az,
+                z: 4.0 * q1 * q1 * q3 - 2.0 * q1 * ax + 4.0 * q2 * q2 * q3 - 2.0 * q2 * ay,
+            };
+            let gradient_norm = (gradient.w * gradient.w
+                + gradient.x * gradient.x
+                + gradient.y * gradient.y
+                + gradient.z * gradient.z)
+                .sqrt();
+            if gradient_norm > f32::EPSILON {
+                let step = beta / gradient_norm;
+                derivative.w -= step * gradient.w;
+                derivative.x -= step * gradient.x;
+                derivative.y -= step * gradient.y;
+                derivative.z -= step * gradient.z;
+            }
+        }
+
+        self.orientation = q.step(derivative, dt);
+        self.orientation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layouts() {
+        let data = motion((1.0, 2.0, 3.0), (0.5, -1.0, 0.25));
+        let config = MotionConfig::default();
+        let (accelerometer, gyro) = config.controller_axes(&data);
+        assert_eq!(
+            accelerometer,
+            [-4096, -2048, -8192],
+            "Normal layout should match the previous fixed mapping"
+        );
+        assert_eq!(gyro, [936, 2809, -1872]);
+
+        let flipped = MotionConfig {
+            layout: MotionLayout::Flipped,
+            ..Default::default()
+        };
+        assert_eq!(flipped.controller_axes(&data).0, [4096, -2048, 8192]);
+
+        let custom = MotionConfig {
+            layout: MotionLayout::Custom {
+                accelerometer: [SignedAxis::X, SignedAxis::Y, SignedAxis::NegZ],
+                gyro: [SignedAxis::Z, SignedAxis::NegY, SignedAxis::X],
+            },
+            gyro_range: 2.0,
+            ..Default::default()
+        };
+        let (accelerometer, gyro) = custom.controller_axes(&data);
+        assert_eq!(accelerometer, [4096, -8192, -2048]);
+        assert_eq!(gyro, [32767, -32768, 16384]);
+    }
+
+    fn motion(gyro: (f32, f32, f32), accelerometer: (f32, f32, f32)) -> MotionData {
+        MotionData {
+            gyro: Vector3 {
+                x: gyro.0,
+                y: gyro.1,
+                z: gyro.2,
+            },
+            accelerometer: Vector3 {
+                x: accelerometer.0,
+                y: accelerometer.1,
+                z: accelerometer.2,
+            },
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn test_batched_samples() {
+        let config = MotionConfig {
+            filter: OrientationFilter::Complementary { gain: 0.0 },
+            ..Default::default()
+        };
+        let sample = |timestamp| MotionData {
+            timestamp,
+            ..motion((0.0, 0.0, 1.0), (0.0, 0.0, 1.0))
+        };
+        let mut report = MainReport {
+            motion: sample(30_000),
+            motion_samples: vec![sample(10_000), sample(20_000), sample(30_000)],
+            ..Default::default()
+        };
+
+        let mut processor = MotionProcessor::default();
+        processor.update(&sample(0), 0, &config);
+        let state = processor.update_report(&mut report, &config);
+        let orientation = state.orientation.unwrap();
+        let yaw = 2.0 * orientation.z.atan2(orientation.w);
+        assert!(
+            (yaw - 0.03).abs() < 1e-4,
+            "Every sample should be integrated: {yaw}"
+        );
+        let timestamps: Vec<u64> = report.motion_samples.iter().map(|s| s.timestamp).collect();
+        assert_eq!(timestamps, [10_000, 20_000, 30_000]);
+        assert_eq!(report.motion.gyro, state.gyro);
+
+        // Replayed without new samples
+        let mut replayed = MainReport {
+            motion_samples: vec![sample(10_000), sample(20_000), sample(30_000)],
+            ..report.clone()
+        };
+        processor.update_report(&mut replayed, &config);
+        assert!(replayed.motion_samples.is_empty());
+
+        // Server without batched samples
+        let mut latest = MainReport {
+            motion: sample(40_000),
+            ..Default::default()
+        };
+        processor.update_report(&mut latest, &config);
+        assert_eq!(latest.motion_samples, [latest.motion.clone()]);
+    }
+
+    #[test]
+    fn test_gyro_bias_at_rest() {
+        let config = MotionConfig {
+            gyro_calibration: true,
+            ..Default::default()
+        };
+        let mut processor = MotionProcessor::default();
+        let resting = motion((0.02, -0.01, 0.03), (0.0, 0.0, 1.0));
+
+        for i in 0..=100 {
+            processor.update(&resting, i * 10_000, &config);
+        }
+        let state = processor.update(&resting, 1_010_000, &config);
+        assert!(length(state.gyro) < 1e-4, "Bias should be removed at rest");
+
+        let moving = motion((1.02, -0.01, 0.03), (0.0, 0.0, 1.0));
+        let state = processor.update(&moving, 1_020_000, &config);
+        assert!((state.gyro.x - 1.0).abs() < 1e-4, "Motion should be kept");
+        assert_eq!(processor.rest_since, None);
+    }
+
+    #[test]
+    fn test_duplicate_samples() {
+        let config = MotionConfig {
+            gyro_calibration: true,
+            filter: OrientationFilter::Complementary { gain: 0.0 },
+            ..Default::default()
+        };
+        let mut processor = MotionProcessor::default();
+        let resting = motion((0.02, -0.01, 0.03), (0.0, 0.0, 1.0));
+        let drifted = motion((0.03, -0.01, 0.03), (0.0, 0.0, 1.0));
+
+        for i in 0..=100 {
+            processor.update(&resting, i * 10_000, &config);
+            processor.update(&resting, i * 10_000, &config);
+            processor.update(&drifted, i * 10_000, &config);
+        }
+        assert_eq!(processor.rest_samples, 101);
+        assert!(
+            (processor.bias().x - 0.02).abs() < 1e-6,
+            "Duplicate samples should not skew the bias: {:?}",
+            processor.bias()
+        );
+        let state = processor.update(&drifted, 1_010_000, &config);
+        let duplicate = processor.update(&drifted, 1_010_000, &config);
+        assert_eq!(
+            duplicate.orientation, state.orientation,
+            "Duplicate sample should not be integrated"
+        );
+    }
+
+    #[test]
+    fn test_orientation_filters() {
+        for filter in [
+            OrientationFilter::Complementary { gain: 0.5 },
+            OrientationFilter::Madgwick { beta: 0.1 },
+        ] {
+            let config = MotionConfig {
+                filter,
+                ..Default::default()
+            };
+            let mut processor = MotionProcessor::default();
+
+            // Turn around the vertical axis at 1 rad/s for one second
+            let turning = motion((0.0, 0.0, 1.0), (0.0, 0.0, 1.0));
+            let mut state = MotionState::default();
+            for i in 0..=100 {
+                state = processor.update(&turning, i * 10_000, &config);
+            }
+            let orientation = state.orientation.unwrap();
+            let yaw = 2.0 * orientation.z.atan2(orientation.w);
+            assert!(
+                (yaw - 1.0).abs() < 0.02,
+                "{filter:?} yaw should follow the gyro: {yaw}"
+            );
+
+            // Gravity pulls the orientation back when the gyro is wrong
+            let tilted = motion((0.0, 0.0, 0.0), (0.0, 1.0, 0.0));
+            for i in 101..=2100 {
+                state = processor.update(&tilted, i * 10_000, &config);
+            }
+            let up = state.orientation.unwrap().up();
+            assert!(
+                up.y > 0.9,
+                "{filter:?} should converge towards gravity: {up:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use serde::{Deserialize, Serialize};
+use vita_reports::delta::TouchDelta;
+use vita_reports::TouchReport;
+
+use crate::virtual_config::TouchMouseConfig;
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+}
+
+/// Relative mouse output accumulated for a single report.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MouseOutput {
+    pub dx: i32,
+    pub dy: i32,
+    pub wheel: i32,
+    pub hwheel: i32,
+    /// Buttons to press and release right away.
+    pub clicks: Vec<MouseButton>,
+}
+
+impl MouseOutput {
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.dx == 0 && self.dy == 0 && self.wheel == 0 && self.hwheel == 0 && self.clicks.is_empty()
+    }
+
+    /// Adds the output of another source to this one.
+    pub fn merge(&mut self, other: MouseOutput) {
+        self.dx += other.dx;
+        self.dy += other.dy;
+        self.wheel += other.wheel;
+        self.hwheel += other.hwheel;
+        self.clicks.extend(other.clicks);
+    }
+}
+
+/// Splits a fractional movement into whole units, keeping the rest for the next report.
+#[inline]
+pub(crate) fn take_whole(value: f32, remainder: &mut f32) -> i32 {
+    let total = value + *remainder;
+    let whole = total.trunc();
+    *remainder = total - whole;
+    whole as i32
+}
+
+/// Contact session used for tap detection, from the first finger down to the last finger up.
+#[derive(Clone, Debug)]
+struct Gesture {
+    start: u64,
+    max_fingers: usize,
+    travel: f32,
+}
+
+/// Converts contacts of a touch panel into relative mouse output,
+/// like a laptop trackpad.
+#[derive(Clone, Debug, Default)]
+pub struct TouchMouse {
+    previous_touches: Vec<TouchReport>,
+    previous_timestamp: u64,
+    gesture: Option<Gesture>,
+    motion_remainder: (f32, f32),
+    scroll_remainder: (f32, f32),
+}
+
+impl TouchMouse {
+    pub fn update(
+        &mut self,
+        touch_reports: &[TouchReport],
+        timestamp: u64,
+        config: &TouchMouseConfig,
+    ) -> MouseOutput {
+        let mut output = MouseOutput::default();
+
+        // Average movement of the contacts present in both reports
+        let delta = TouchDelta::between(&self.previous_touches, touch_reports);
+        let kept = touch_reports.len() - delta.began.len();
+        let (dx, dy) = if kept == 0 {
+            (0.0, 0.0)
+        } else {
+            let (mut sum_x, mut sum_y) = (0.0, 0.0);
+            for (previous, touch) in &delta.moved {
+                sum_x += touch.x as f32 - previous.x as f32;
+                sum_y += touch.y as f32 - previous.y as f32;
+            }
+            (sum_x / kept as f32, sum_y / kept as f32)
+        };
+
+        // After a multi-finger contact, the pointer stays still until every finger is lifted
+        let multi_finger = self
+            .gesture
+            .as_ref()
+            .is_some_and(|gesture| gesture.max_fingers > 1);
+
+        match touch_reports.len() {
+            0 => {}
+            1 if multi_finger => {}
+            1 => {
+                let elapsed_ms =
+                    (timestamp.saturating_sub(self.previous_timestamp) as f32 / 1000.0).max(1.0);
+                let speed = (dx * dx + dy * dy).sqrt() / elapsed_ms;
+                let gain = config.sensitivity
+                    * (1.0 + config.acceleration * speed).min(config.max_acceleration.max(1.0));
+
+                output.dx = take_whole(dx * gain, &mut self.motion_remainder.0);
+                output.dy = take_whole(dy * gain, &mut self.motion_remainder.1);
+            }
+            _ => {
+                // Moving fingers up scrolls up, unless natural scrolling is enabled
+                let direction = if config.natural_scroll { 1.0 } else { -1.0 };
+                output.wheel = take_whole(
+                    dy * config.scroll_speed * direction,
+                    &mut self.scroll_remainder.1,
+                );
+                output.hwheel = take_whole(
+                    -dx * config.scroll_speed * direction,
+                    &mut self.scroll_remainder.0,
+                );
+            }
+        }
+
+        // Tap detection
+        if !touch_reports.is_empty() {
+            let gesture = self.gesture.get_or_insert(Gesture {
+                start: timestamp,
+                max_fingers: 0,
+                travel: 0.0,
+            });
+            gesture.max_fingers = gesture.max_fingers.max(touch_reports.len());
+            gesture.travel += (dx * dx + dy * dy).sqrt();
+        } else if let Some(gesture) = self.gesture.take() {
+            let duration_ms = timestamp.saturating_sub(gesture.start) / 1000;
+            if duration_ms <= config.tap_time && gesture.travel <= config.tap_distance {
+                match gesture.max_fingers {
+                    1 => output.clicks.push(MouseButton::Left),
+                    2 => output.clicks.push(MouseButton::Right),
+                    3 => output.clicks.push(MouseButton::Middle),
+                    _ => {}
+                }
+            }
+            self.motion_remainder = (0.0, 0.0);
+            self.scroll_remainder = (0.0, 0.0);
+        }
+
+        self.previous_touches = touch_reports.to_vec();
+        self.previous_timestamp = timestamp;
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn touch(id: u8, x: u16, y: u16) -> TouchReport {
+        TouchReport {
+            x,
+            y,
+            id,
+            force: 50,
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn test_tap_clicks() {
+        let config = TouchMouseConfig::default();
+        let mut mouse = TouchMouse::default();
+
+        mouse.update(&[touch(1, 500, 500)], 0, &config);
+        let output = mouse.update(&[], 50_000, &config);
+        assert_eq!(output.clicks, [MouseButton::Left], "Single finger tap should left click");
+
+        mouse.update(&[touch(1, 500, 500), touch(2, 700, 500)], 100_000, &config);
+        let output = mouse.update(&[], 150_000, &config);
+        assert_eq!(output.clicks, [MouseButton::Right], "Two finger tap should right click");
+
+        mouse.update(&[touch(1, 500, 500)], 200_000, &config);
+        let output = mouse.update(&[], 600_000, &config);
+        assert!(output.clicks.is_empty(), "Long press should not click");
+    }
+
+    #[test]
+    fn test_fingers_lifted_one_by_one() {
+        let config = TouchMouseConfig::default();
+        let mut mouse = TouchMouse::default();
+
+        mouse.update(&[touch(1, 500, 500), touch(2, 700, 500)], 0, &config);
+        let output = mouse.update(&[touch(2, 705, 502)], 30_000, &config);
+        assert_eq!(
+            (output.dx, output.dy),
+            (0, 0),
+            "Remaining finger should not move the pointer"
+        );
+        let output = mouse.update(&[touch(2, 710, 504)], 40_000, &config);
+        assert_eq!((output.dx, output.dy), (0, 0));
+
+        let output = mouse.update(&[], 60_000, &config);
+        assert_eq!(output.clicks, [MouseButton::Right]);
+
+        mouse.update(&[touch(1, 500, 500)], 100_000, &config);
+        let output = mouse.update(&[touch(1, 520, 500)], 110_000, &config);
+        assert!(output.dx > 0, "Next contact should move the pointer again");
+    }
+
+    #[test]
+    fn test_motion_and_scroll() {
+        let config = TouchMouseConfig::default();
+        let mut mouse = TouchMouse::default();
+
+        mouse.update(&[touch(1, 500, 500)], 0, &config);
+        let output = mouse.update(&[touch(1, 520, 500)], 10_000, &config);
+        assert!(output.dx > 0 && output.dy == 0, "Moving right should move the pointer right");
+
+        mouse.update(&[], 20_000, &config);
+        mouse.update(&[touch(1, 500, 500), touch(2, 700, 500)], 30_000, &config);
+        let output = mouse.update(&[touch(1, 500, 400), touch(2, 700, 400)], 40_000, &config);
+        assert_eq!(output.wheel, 2, "Moving two fingers up should scroll up");
+        assert_eq!((output.dx, output.dy), (0, 0), "Scrolling should not move the pointer");
+    }
+}
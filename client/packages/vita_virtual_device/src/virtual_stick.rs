@@ -0,0 +1,330 @@
+use serde::{Deserialize, Serialize};
+use vita_reports::MainReport;
+
+/// Center of the stick range, between the values 127 and 128.
+const STICK_HALF_RANGE: f32 = 127.5;
+
+/// Converts a stick value to a deflection from -1 to 1.
+#[inline]
+pub(crate) fn to_deflection(value: u8) -> f32 {
+    (value as f32 - STICK_HALF_RANGE) / STICK_HALF_RANGE
+}
+
+/// Converts a deflection from -1 to 1 to a stick value.
+#[inline]
+pub(crate) fn from_deflection(deflection: f32) -> u8 {
+    (STICK_HALF_RANGE + deflection.clamp(-1.0, 1.0) * STICK_HALF_RANGE).round() as u8
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Stick {
+    Left,
+    #[default]
+    Right,
+}
+
+impl Stick {
+    /// Returns the position (x, y) of the stick in the report.
+    #[inline]
+    pub(crate) fn position(self, report: &mut MainReport) -> (&mut u8, &mut u8) {
+        match self {
+            Stick::Left => (&mut report.lx, &mut report.ly),
+            Stick::Right => (&mut report.rx, &mut report.ry),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum DeadzoneShape {
+    /// Deadzone applied to the distance from the center, keeps the direction.
+    #[default]
+    Radial,
+    /// Deadzone applied to each axis separately, makes cardinal directions easier to hold.
+    Axial,
+}
+
+/// Response curve applied to the stick deflection after the deadzones.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum StickCurve {
+    #[default]
+    Linear,
+    /// Deflection raised to this power, values above 1 give more precision near the center.
+    Exponential(f32),
+    /// Points `(input, output)` from 0 to 1 sorted by input, linearly interpolated.
+    /// The points `(0, 0)` and `(1, 1)` are implied.
+    Custom(Vec<(f32, f32)>),
+}
+
+impl StickCurve {
+    fn apply(&self, value: f32) -> f32 {
+        match self {
+            StickCurve::Linear => value,
+            StickCurve::Exponential(exponent) => value.powf(*exponent),
+            StickCurve::Custom(points) => {
+                let mut previous = (0.0, 0.0);
+                for &point in points.iter().chain(std::iter::once(&(1.0, 1.0))) {
+                    if value <= point.0 {
+                        let span = point.0 - previous.0;
+                        if span <= f32::EPSILON {
+                            return point.1;
+                        }
+                        let t = (value - previous.0) / span;
+                        return previous.1 + t * (point.1 - previous.1);
+                    }
+                    previous = point;
+                }
+                previous.1
+            }
+        }
+    }
+}
+
+/// Processing applied to a stick before it reaches the virtual device.
+///
+/// Deflections are from 0 (centered) to 1 (fully deflected).
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct StickConfig {
+    /// Deflection below which the stick is centered.
+    pub inner_deadzone: f32,
+    /// Deflection cut at the edge, so the stick reaches the full output before the end of its range.
+    pub outer_deadzone: f32,
+    pub deadzone_shape: DeadzoneShape,
+    /// Smallest output deflection out of the inner deadzone, to skip the deadzone of games.
+    pub anti_deadzone: f32,
+    pub curve: StickCurve,
+    pub invert_x: bool,
+    pub invert_y: bool,
+}
+
+impl Default for StickConfig {
+    fn default() -> Self {
+        StickConfig {
+            inner_deadzone: 0.1,
+            outer_deadzone: 0.05,
+            deadzone_shape: DeadzoneShape::Radial,
+            anti_deadzone: 0.0,
+            curve: StickCurve::Linear,
+            invert_x: false,
+            invert_y: false,
+        }
+    }
+}
+
+impl StickConfig {
+    /// Maps a deflection through the deadzones, curve and anti-deadzone.
+    fn scale(&self, deflection: f32) -> f32 {
+        if deflection <= self.inner_deadzone {
+            return 0.0;
+        }
+        let live_range = (1.0 - self.outer_deadzone - self.inner_deadzone).max(f32::EPSILON);
+        let value = ((deflection - self.inner_deadzone) / live_range).clamp(0.0, 1.0);
+        let value = self.curve.apply(value).clamp(0.0, 1.0);
+        self.anti_deadzone + (1.0 - self.anti_deadzone) * value
+    }
+
+    /// Processes the raw position of a stick.
+    pub fn apply(&self, x: u8, y: u8) -> (u8, u8) {
+        let (x, y) = (to_deflection(x), to_deflection(y));
+
+        let (x, y) = match self.deadzone_shape {
+            DeadzoneShape::Radial => {
+                let magnitude = (x * x + y * y).sqrt();
+                if magnitude <= f32::EPSILON {
+                    (0.0, 0.0)
+                } else {
+                    let scale = self.scale(magnitude) / magnitude;
+                    (x * scale, y * scale)
+                }
+            }
+            DeadzoneShape::Axial => (
+                self.scale(x.abs()).copysign(x),
+                self.scale(y.abs()).copysign(y),
+            ),
+        };
+
+        let x = if self.invert_x { -x } else { x };
+        let y = if self.invert_y { -y } else { y };
+        (from_deflection(x), from_deflection(y))
+    }
+}
+
+/// Smallest range of each axis accepted by the calibration.
+const MIN_CALIBRATION_RANGE: u8 = 64;
+
+/// Measured center and range of a stick, used to rescale it to the full range.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct StickCalibration {
+    /// Position of the stick at rest (x, y).
+    pub center: (u8, u8),
+    /// Lowest values reached (x, y).
+    pub min: (u8, u8),
+    /// Highest values reached (x, y).
+    pub max: (u8, u8),
+}
+
+impl StickCalibration {
+    /// Rescales the raw position of a stick, so the center becomes 128
+    /// and the measured range becomes 0 to 255.
+    pub fn apply(&self, x: u8, y: u8) -> (u8, u8) {
+        let rescale = |value: u8, min: u8, center: u8, max: u8| {
+            let (value, center) = (value as f32, center as f32);
+            let normalized = if value < center {
+                (value - center) / (center - min as f32).max(1.0)
+            } else {
+                (value - center) / (max as f32 - center).max(1.0)
+            };
+            from_deflection(normalized)
+        };
+
+        (
+            rescale(x, self.min.0, self.center.0, self.max.0),
+            rescale(y, self.min.1, self.center.1, self.max.1),
+        )
+    }
+}
+
+/// Measures the center and range of a stick from its raw positions.
+#[derive(Clone, Debug, Default)]
+pub struct StickCalibrator {
+    center_sum: (u64, u64),
+    center_samples: u64,
+    range: Option<((u8, u8), (u8, u8))>,
+}
+
+impl StickCalibrator {
+    /// Adds a position of the stick left at rest.
+    pub fn add_center_sample(&mut self, x: u8, y: u8) {
+        self.center_sum.0 += u64::from(x);
+        self.center_sum.1 += u64::from(y);
+        self.center_samples += 1;
+    }
+
+    /// Adds a position of the stick moved along its edges.
+    pub fn add_range_sample(&mut self, x: u8, y: u8) {
+        let ((min_x, min_y), (max_x, max_y)) = self.range.get_or_insert(((x, y), (x, y)));
+        *min_x = (*min_x).min(x);
+        *min_y = (*min_y).min(y);
+        *max_x = (*max_x).max(x);
+        *max_y = (*max_y).max(y);
+    }
+
+    /// Returns the calibration, or `None` if the stick has not been measured
+    /// or not moved far enough.
+    pub fn finish(&self) -> Option<StickCalibration> {
+        if self.center_samples == 0 {
+            return None;
+        }
+        let center = (
+            (self.center_sum.0 as f64 / self.center_samples as f64).round() as u8,
+            (self.center_sum.1 as f64 / self.center_samples as f64).round() as u8,
+        );
+
+        let (min, max) = self.range?;
+        let covers = |min: u8, center: u8, max: u8| {
+            min < center && center < max && max - min >= MIN_CALIBRATION_RANGE
+        };
+        if !covers(min.0, center.0, max.0) || !covers(min.1, center.1, max.1) {
+            return None;
+        }
+
+        Some(StickCalibration { center, min, max })
+    }
+}
+
+/// Applies the calibration and the stick processing of the configuration to a stick of the report.
+#[inline]
+pub fn process_stick(
+    x: &mut u8,
+    y: &mut u8,
+    calibration: &Option<StickCalibration>,
+    config: &Option<StickConfig>,
+) {
+    if let Some(calibration) = calibration {
+        (*x, *y) = calibration.apply(*x, *y);
+    }
+    if let Some(config) = config {
+        (*x, *y) = config.apply(*x, *y);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deadzones() {
+        let config = StickConfig::default();
+
+        assert_eq!(
+            config.apply(136, 122),
+            (128, 128),
+            "Drift should be centered"
+        );
+        assert_eq!(config.apply(255, 128).0, 255);
+        assert_eq!(
+            config.apply(250, 128).0,
+            255,
+            "Outer deadzone should reach the maximum"
+        );
+        assert_eq!(config.apply(0, 128).0, 0);
+
+        let (x, _) = config.apply(192, 128);
+        assert!(x > 128 && x < 192, "Inner deadzone should be rescaled");
+    }
+
+    #[test]
+    fn test_axial_anti_deadzone_and_inversion() {
+        let config = StickConfig {
+            deadzone_shape: DeadzoneShape::Axial,
+            anti_deadzone: 0.2,
+            invert_y: true,
+            ..Default::default()
+        };
+
+        let (x, y) = config.apply(255, 138);
+        assert_eq!(x, 255);
+        assert_eq!(y, 128, "Small deflection on one axis should stay centered");
+
+        let (_, y) = config.apply(128, 160);
+        assert!(
+            y < 128 - 25,
+            "Anti-deadzone should start past the game deadzone"
+        );
+    }
+
+    #[test]
+    fn test_calibration() {
+        let mut calibrator = StickCalibrator::default();
+        for (x, y) in [(133, 120), (135, 122)] {
+            calibrator.add_center_sample(x, y);
+        }
+        assert_eq!(calibrator.finish(), None, "Range should be required");
+
+        for (x, y) in [(20, 121), (134, 10), (230, 121), (134, 240)] {
+            calibrator.add_range_sample(x, y);
+        }
+        let calibration = calibrator.finish().unwrap();
+        assert_eq!(calibration.center, (134, 121));
+
+        assert_eq!(calibration.apply(134, 121), (128, 128));
+        assert_eq!(calibration.apply(20, 240), (0, 255));
+        assert_eq!(calibration.apply(230, 10), (255, 0));
+        assert_eq!(
+            calibration.apply(77, 121).0,
+            64,
+            "Half range should be half deflection"
+        );
+    }
+
+    #[test]
+    fn test_curves() {
+        let exponential = StickCurve::Exponential(2.0);
+        assert_eq!(exponential.apply(0.5), 0.25);
+
+        let custom = StickCurve::Custom(vec![(0.5, 0.25), (0.75, 0.75)]);
+        assert_eq!(custom.apply(0.25), 0.125);
+        assert_eq!(custom.apply(0.625), 0.5);
+        assert_eq!(custom.apply(0.875), 0.875);
+    }
+}
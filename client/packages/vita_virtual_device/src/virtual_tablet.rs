@@ -0,0 +1,221 @@
+use serde::{Deserialize, Serialize};
+use vita_reports::{ButtonsData, TouchReport};
+
+use crate::virtual_config::TabletConfig;
+use crate::virtual_touch::Point;
+
+/// Highest pressure value reported by the tablet.
+pub const TABLET_MAX_PRESSURE: i32 = 1023;
+
+/// Physical size of the front touch panel in millimeters.
+pub const FRONT_TOUCHPAD_SIZE_MM: (i32, i32) = (110, 63);
+
+/// Highest force reported by the Vita touch panels.
+const MAX_FORCE: f32 = 128.0;
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum TabletButton {
+    Stylus,
+    Stylus2,
+    /// Switches the tool to the eraser while held.
+    Eraser,
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum TabletTool {
+    Pen,
+    Eraser,
+}
+
+/// State of the emulated pen for a single report.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TabletState {
+    /// Tool in proximity of the tablet, `None` if nothing touches the panel.
+    pub tool: Option<TabletTool>,
+    pub x: i32,
+    pub y: i32,
+    pub pressure: i32,
+    pub touching: bool,
+    pub stylus: bool,
+    pub stylus2: bool,
+}
+
+/// Computes the pen state from the first contact of the panel.
+///
+/// Buttons mapped to the tablet are released in `buttons`,
+/// so they do not reach the gamepad.
+pub fn compute_tablet_state(
+    touch_reports: &[TouchReport],
+    buttons: &mut ButtonsData,
+    panel: (Point, Point),
+    config: &TabletConfig,
+) -> TabletState {
+    let mut state = TabletState::default();
+    let mut eraser = false;
+
+    for &(vita_button, tablet_button) in &config.buttons {
+        if !vita_button.is_pressed(buttons) {
+            continue;
+        }
+        match tablet_button {
+            TabletButton::Stylus => state.stylus = true,
+            TabletButton::Stylus2 => state.stylus2 = true,
+            TabletButton::Eraser => eraser = true,
+        }
+        vita_button.release(buttons);
+    }
+
+    let Some(touch) = touch_reports.first() else {
+        return state;
+    };
+
+    let (width, height) = config.screen_size;
+    let region = config
+        .region
+        .unwrap_or((Point(0, 0), Point(width - 1, height - 1)));
+    let scale = |value: u16, panel_min: i32, panel_max: i32, min: i32, max: i32| {
+        let normalized = (value as f32 - panel_min as f32) / (panel_max - panel_min).max(1) as f32;
+        (min as f32 + normalized.clamp(0.0, 1.0) * (max - min) as f32).round() as i32
+    };
+
+    state.tool = Some(if eraser {
+        TabletTool::Eraser
+    } else {
+        TabletTool::Pen
+    });
+    state.x = scale(
+        touch.x,
+        panel.0.x(),
+        panel.1.x(),
+        region.0.x(),
+        region.1.x(),
+    );
+    state.y = scale(
+        touch.y,
+        panel.0.y(),
+        panel.1.y(),
+        region.0.y(),
+        region.1.y(),
+    );
+    state.touching = touch.force > config.touch_threshold;
+    if state.touching {
+        let force = (touch.force as f32 / MAX_FORCE).clamp(0.0, 1.0);
+        state.pressure =
+            (force.powf(config.pressure_curve) * TABLET_MAX_PRESSURE as f32).round() as i32;
+    }
+
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::virtual_button::VitaButton;
+    use crate::FRONT_TOUCHPAD_RECT;
+
+    fn touch(x: u16, y: u16, force: u8) -> TouchReport {
+        TouchReport {
+            x,
+            y,
+            force,
+            ..Default::default()
+        }
+    }
+
+    fn tablet_state(touches: &[TouchReport], config: &TabletConfig) -> TabletState {
+        compute_tablet_state(
+            touches,
+            &mut ButtonsData::default(),
+            FRONT_TOUCHPAD_RECT,
+            config,
+        )
+    }
+
+    #[test]
+    fn test_mapping() {
+        let config = TabletConfig::default();
+        let state = tablet_state(&[touch(0, 0, 64)], &config);
+        assert_eq!((state.x, state.y), (0, 0));
+        let state = tablet_state(&[touch(1920, 1087, 64), touch(0, 0, 64)], &config);
+        assert_eq!(
+            (state.x, state.y),
+            (1919, 1079),
+            "First contact should cover the whole screen"
+        );
+
+        let config = TabletConfig {
+            region: Some((Point(100, 100), Point(499, 299))),
+            ..Default::default()
+        };
+        let state = tablet_state(&[touch(960, 1087, 64)], &config);
+        assert_eq!((state.x, state.y), (300, 299));
+        let state = tablet_state(&[touch(2000, 1200, 64)], &config);
+        assert_eq!(
+            (state.x, state.y),
+            (499, 299),
+            "Position should stay in the region"
+        );
+    }
+
+    #[test]
+    fn test_pressure() {
+        let config = TabletConfig {
+            touch_threshold: 10,
+            ..Default::default()
+        };
+        let state = tablet_state(&[touch(0, 0, 10)], &config);
+        assert_eq!(state.tool, Some(TabletTool::Pen));
+        assert!(!state.touching, "Light contact should only hover");
+        assert_eq!(state.pressure, 0);
+
+        let state = tablet_state(&[touch(0, 0, 128)], &config);
+        assert!(state.touching);
+        assert_eq!(state.pressure, TABLET_MAX_PRESSURE);
+
+        let config = TabletConfig {
+            pressure_curve: 2.0,
+            ..Default::default()
+        };
+        let state = tablet_state(&[touch(0, 0, 64)], &config);
+        assert_eq!(state.pressure, 256);
+
+        let state = tablet_state(&[], &config);
+        assert_eq!(state, TabletState::default());
+    }
+
+    #[test]
+    fn test_buttons() {
+        let config = TabletConfig {
+            buttons: vec![
+                (VitaButton::Square, TabletButton::Stylus),
+                (VitaButton::Triangle, TabletButton::Eraser),
+            ],
+            ..Default::default()
+        };
+        let mut buttons = ButtonsData {
+            square: true,
+            triangle: true,
+            cross: true,
+            ..Default::default()
+        };
+
+        let state = compute_tablet_state(&[], &mut buttons, FRONT_TOUCHPAD_RECT, &config);
+        assert!(state.stylus && !state.stylus2);
+        assert_eq!(state.tool, None);
+        assert!(
+            !buttons.square && !buttons.triangle,
+            "Tablet buttons should not reach the gamepad"
+        );
+        assert!(buttons.cross);
+
+        buttons.triangle = true;
+        let state = compute_tablet_state(
+            &[touch(0, 0, 64)],
+            &mut buttons,
+            FRONT_TOUCHPAD_RECT,
+            &config,
+        );
+        assert_eq!(state.tool, Some(TabletTool::Eraser));
+        assert!(!state.stylus);
+    }
+}
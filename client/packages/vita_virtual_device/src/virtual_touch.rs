@@ -2,6 +2,7 @@ use rstar::{primitives::Rectangle, AABB};
 use serde::{Deserialize, Serialize};
 
 use crate::virtual_button::{Button, DpadDirection};
+use crate::virtual_key::KeyCombo;
 
 /// Point in 2D space (x, y).
 #[derive(Clone, Debug, Copy, PartialEq, Eq, Deserialize, Serialize)]
@@ -49,11 +50,15 @@ impl rstar::Point for Point {
     }
 }
 
-/// Represents an action triggered by a touch input.
-#[derive(Clone, Debug, Copy, Deserialize, Serialize)]
+/// Represents an action triggered by a touch input or a bound button.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub enum TouchAction {
     Dpad(DpadDirection),
     Button(Button),
+    /// Keys held on the virtual keyboard while the action is active.
+    Key(KeyCombo),
+    /// Plays the macro with this name from the configuration.
+    Macro(String),
 }
 
 /// Defines a touch zone and the action it triggers.
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+
+use vita_reports::TouchReport;
+
+use crate::virtual_config::TouchRejectionConfig;
+use crate::virtual_touch::Point;
+
+/// State of a contact tracked by the filter.
+#[derive(Clone, Debug)]
+struct Contact {
+    /// Vita timestamp of the first report containing the contact.
+    first_seen: u64,
+    origin: Point,
+    moved: bool,
+    rejected: bool,
+}
+
+/// Removes unintended contacts from touch reports before they reach
+/// touch zones or the touchpad.
+///
+/// A rejected contact stays rejected until it is lifted, so a palm sliding
+/// into the active area does not start firing inputs.
+#[derive(Clone, Debug, Default)]
+pub struct TouchFilter {
+    contacts: HashMap<u8, Contact>,
+    initialized: bool,
+}
+
+impl TouchFilter {
+    /// Filters the reports of one panel, or passes them through if the
+    /// panel has no rejection configured.
+    pub fn apply(
+        &mut self,
+        touch_reports: Vec<TouchReport>,
+        timestamp: u64,
+        config: &Option<TouchRejectionConfig>,
+        rect: (Point, Point),
+    ) -> Vec<TouchReport> {
+        match config {
+            Some(config) => self.filter(&touch_reports, timestamp, config, rect),
+            None => touch_reports,
+        }
+    }
+
+    /// Returns the reports of the contacts that are not rejected.
+    pub fn filter(
+        &mut self,
+        touch_reports: &[TouchReport],
+        timestamp: u64,
+        config: &TouchRejectionConfig,
+        rect: (Point, Point),
+    ) -> Vec<TouchReport> {
+        let initial = !self.initialized;
+        self.initialized = true;
+
+        // Forget contacts that have been lifted
+        self.contacts
+            .retain(|id, _| touch_reports.iter().any(|touch| touch.id == *id));
+
+        let mut accepted = Vec::with_capacity(touch_reports.len());
+        for touch in touch_reports {
+            let point = Point(touch.x.into(), touch.y.into());
+            let contact = self.contacts.entry(touch.id).or_insert_with(|| Contact {
+                first_seen: timestamp,
+                origin: point,
+                moved: false,
+                rejected: (initial && config.ignore_initial_contacts)
+                    || config.edges.contains(rect, point),
+            });
+
+            if contact.rejected {
+                continue;
+            }
+
+            let radius = i32::from(config.stationary_radius);
+            let (dx, dy) = (point.x() - contact.origin.x(), point.y() - contact.origin.y());
+            if dx * dx + dy * dy > radius * radius {
+                contact.moved = true;
+            }
+
+            let palm = config.max_force.is_some_and(|max| touch.force > max);
+            let resting = config.stationary_timeout.is_some_and(|timeout| {
+                !contact.moved && timestamp.saturating_sub(contact.first_seen) >= timeout * 1000
+            });
+
+            if palm || resting {
+                contact.rejected = true;
+                continue;
+            }
+
+            accepted.push(touch.clone());
+        }
+
+        accepted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::virtual_config::EdgeBands;
+    use crate::REAR_TOUCHPAD_RECT;
+
+    fn touch(id: u8, x: u16, y: u16, force: u8) -> TouchReport {
+        TouchReport {
+            x,
+            y,
+            id,
+            force,
+            timestamp: 0,
+        }
+    }
+
+    fn config() -> TouchRejectionConfig {
+        TouchRejectionConfig {
+            edges: EdgeBands {
+                left: 100,
+                top: 50,
+                right: 100,
+                bottom: 50,
+            },
+            max_force: Some(100),
+            stationary_timeout: Some(500),
+            stationary_radius: 30,
+            ignore_initial_contacts: true,
+        }
+    }
+
+    #[test]
+    fn test_initial_contacts_rejected_until_lifted() {
+        let mut filter = TouchFilter::default();
+        let config = config();
+
+        let reports = [touch(1, 900, 400, 50)];
+        assert!(
+            filter.filter(&reports, 0, &config, REAR_TOUCHPAD_RECT).is_empty(),
+            "Contact present at connection time should be rejected"
+        );
+        assert!(
+            filter.filter(&reports, 1000, &config, REAR_TOUCHPAD_RECT).is_empty(),
+            "Contact should stay rejected while held"
+        );
+
+        filter.filter(&[], 2000, &config, REAR_TOUCHPAD_RECT);
+        assert_eq!(
+            filter.filter(&reports, 3000, &config, REAR_TOUCHPAD_RECT),
+            reports,
+            "New contact after release should be accepted"
+        );
+    }
+
+    #[test]
+    fn test_edge_palm_and_stationary_rejection() {
+        let mut filter = TouchFilter::default();
+        let config = config();
+        filter.filter(&[], 0, &config, REAR_TOUCHPAD_RECT);
+
+        let reports = [
+            touch(1, 20, 400, 50),
+            touch(2, 900, 400, 120),
+            touch(3, 900, 400, 50),
+        ];
+        assert_eq!(
+            filter.filter(&reports, 1000, &config, REAR_TOUCHPAD_RECT),
+            [touch(3, 900, 400, 50)],
+            "Edge and palm contacts should be rejected"
+        );
+
+        let moved = [touch(1, 900, 400, 50), touch(3, 910, 400, 50)];
+        assert!(
+            filter
+                .filter(&moved, 600_000, &config, REAR_TOUCHPAD_RECT)
+                .is_empty(),
+            "Edge contact moving inwards and resting contact should be rejected"
+        );
+    }
+}
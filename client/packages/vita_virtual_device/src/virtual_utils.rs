@@ -1,20 +1,26 @@
-use crate::virtual_button::{Button, DpadDirection};
-use crate::virtual_config::TriggerConfig;
-use vita_reports::ButtonsData;
+use std::time::{Duration, Instant};
+
+use crate::virtual_button::{Button, DpadDirection, VitaButton};
+use crate::virtual_config::{TouchConfig, TriggerConfig};
+use crate::virtual_touch::{Point, TouchAction};
+use vita_reports::{ButtonsData, MainReport, VitaButtons};
 
 /// Computes the D-Pad direction based on the button states.
 pub fn compute_dpad_direction(buttons: &ButtonsData) -> DpadDirection {
-    match (buttons.up, buttons.down, buttons.left, buttons.right) {
-        (true, false, false, false) => DpadDirection::North,
-        (true, false, true, false) => DpadDirection::NorthWest,
-        (true, false, false, true) => DpadDirection::NorthEast,
-        (false, true, false, false) => DpadDirection::South,
-        (false, true, true, false) => DpadDirection::SouthWest,
-        (false, true, false, true) => DpadDirection::SouthEast,
-        (false, false, true, false) => DpadDirection::West,
-        (false, false, false, true) => DpadDirection::East,
-        _ => DpadDirection::None,
-    }
+    let dpad = VitaButtons::from(buttons) & VitaButtons::DPAD;
+    [
+        (VitaButtons::UP, DpadDirection::North),
+        (VitaButtons::UP | VitaButtons::LEFT, DpadDirection::NorthWest),
+        (VitaButtons::UP | VitaButtons::RIGHT, DpadDirection::NorthEast),
+        (VitaButtons::DOWN, DpadDirection::South),
+        (VitaButtons::DOWN | VitaButtons::LEFT, DpadDirection::SouthWest),
+        (VitaButtons::DOWN | VitaButtons::RIGHT, DpadDirection::SouthEast),
+        (VitaButtons::LEFT, DpadDirection::West),
+        (VitaButtons::RIGHT, DpadDirection::East),
+    ]
+    .into_iter()
+    .find(|(pressed, _)| *pressed == dpad)
+    .map_or(DpadDirection::None, |(_, direction)| direction)
 }
 
 /// Retrieves the list of pressed buttons based on the report and trigger configuration.
@@ -22,30 +28,117 @@ pub fn get_pressed_buttons(
     report_buttons: &ButtonsData,
     trigger_config: TriggerConfig,
 ) -> Vec<Button> {
-    let mut buttons = vec![
-        (report_buttons.circle, Button::Circle),
-        (report_buttons.square, Button::Square),
-        (report_buttons.cross, Button::Cross),
-        (report_buttons.triangle, Button::Triangle),
-        (report_buttons.start, Button::Options),
-        (report_buttons.select, Button::Share),
-        (report_buttons.ps, Button::PSButton),
-    ];
-
     // Trigger processing depending on the configuration
-    match trigger_config {
-        TriggerConfig::Shoulder => {
-            buttons.push((report_buttons.lt, Button::ShoulderLeft));
-            buttons.push((report_buttons.rt, Button::ShoulderRight));
+    let (left, right) = match trigger_config {
+        TriggerConfig::Shoulder => (Button::ShoulderLeft, Button::ShoulderRight),
+        TriggerConfig::Trigger => (Button::TriggerLeft, Button::TriggerRight),
+    };
+
+    let pressed = VitaButtons::from(report_buttons);
+    [
+        (VitaButtons::CIRCLE, Button::Circle),
+        (VitaButtons::SQUARE, Button::Square),
+        (VitaButtons::CROSS, Button::Cross),
+        (VitaButtons::TRIANGLE, Button::Triangle),
+        (VitaButtons::START, Button::Options),
+        (VitaButtons::SELECT, Button::Share),
+        (VitaButtons::PS, Button::PSButton),
+        (VitaButtons::L, left),
+        (VitaButtons::R, right),
+    ]
+    .into_iter()
+    .filter(|(vita_button, _)| pressed.contains(*vita_button))
+    .map(|(_, button)| button)
+    .collect()
+}
+
+/// Processes touch reports and returns a list of touch actions.
+pub fn process_touch_reports(
+    touch_reports: &[vita_reports::TouchReport],
+    touch_config: &Option<TouchConfig>,
+) -> Vec<TouchAction> {
+    let mut actions = Vec::new();
+    if let Some(TouchConfig::Zones(zones)) = touch_config {
+        for touch in touch_reports {
+            if let Some(zone) = zones.locate_at_point(&Point(touch.x.into(), touch.y.into())) {
+                if let Some(action) = &zone.action {
+                    actions.push(action.clone());
+                }
+            }
         }
-        TriggerConfig::Trigger => {
-            buttons.push((report_buttons.lt, Button::TriggerLeft));
-            buttons.push((report_buttons.rt, Button::TriggerRight));
+    }
+    actions
+}
+
+/// Returns the actions of the pressed bound buttons.
+///
+/// Bound buttons are released in `buttons`, so they do not keep their default function.
+pub fn take_binding_actions(
+    buttons: &mut ButtonsData,
+    bindings: &[(VitaButton, TouchAction)],
+) -> Vec<TouchAction> {
+    let mut actions = Vec::new();
+    for (button, action) in bindings {
+        if button.is_pressed(buttons) {
+            actions.push(action.clone());
         }
     }
+    for (button, _) in bindings {
+        button.release(buttons);
+    }
+    actions
+}
 
-    buttons
-        .into_iter()
-        .filter_map(|(pressed, button)| if pressed { Some(button) } else { None })
-        .collect()
+/// Last report received from the Vita, after the processing done once per report,
+/// used to update the outputs that change over time between reports.
+#[derive(Clone, Debug, Default)]
+pub struct LastReport {
+    report: Option<(MainReport, Instant)>,
+}
+
+impl LastReport {
+    #[inline]
+    pub fn set(&mut self, report: &MainReport) {
+        self.report = Some((report.clone(), Instant::now()));
+    }
+
+    /// Returns the last report with its timestamp moved to the current time.
+    pub fn now(&self) -> Option<MainReport> {
+        self.report.as_ref().map(|(report, received)| {
+            let mut report = report.clone();
+            report.timestamp += received.elapsed().as_micros() as u64;
+            report
+        })
+    }
+
+    /// Returns the time left until a Vita timestamp.
+    pub fn until(&self, deadline: u64) -> Duration {
+        let now = self.now().map_or(0, |report| report.timestamp);
+        Duration::from_micros(deadline.saturating_sub(now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pressed_buttons() {
+        let buttons = ButtonsData {
+            cross: true,
+            lt: true,
+            up: true,
+            vol_up: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            get_pressed_buttons(&buttons, TriggerConfig::Shoulder),
+            [Button::Cross, Button::ShoulderLeft]
+        );
+        assert_eq!(
+            get_pressed_buttons(&buttons, TriggerConfig::Trigger),
+            [Button::Cross, Button::TriggerLeft]
+        );
+    }
 }
@@ -7,15 +7,31 @@ use vigem_client::{
 };
 
 use windows::Win32::UI::Input::KeyboardAndMouse::{
-    SendInput, INPUT, INPUT_0, INPUT_KEYBOARD, KEYBDINPUT, KEYBD_EVENT_FLAGS, KEYEVENTF_KEYUP,
-    VIRTUAL_KEY, VK_VOLUME_DOWN, VK_VOLUME_UP,
+    SendInput, INPUT, INPUT_0, INPUT_KEYBOARD, INPUT_MOUSE, KEYBDINPUT, KEYBD_EVENT_FLAGS,
+    KEYEVENTF_KEYUP, MOUSEEVENTF_HWHEEL, MOUSEEVENTF_LEFTDOWN, MOUSEEVENTF_LEFTUP,
+    MOUSEEVENTF_MIDDLEDOWN, MOUSEEVENTF_MIDDLEUP, MOUSEEVENTF_MOVE, MOUSEEVENTF_RIGHTDOWN,
+    MOUSEEVENTF_RIGHTUP, MOUSEEVENTF_WHEEL, MOUSEINPUT, MOUSE_EVENT_FLAGS, VIRTUAL_KEY,
+    VK_VOLUME_DOWN, VK_VOLUME_UP,
 };
 
 use crate::virtual_button::{Button, DpadDirection};
 use crate::virtual_config::{Config, ConfigBuilder, TouchConfig};
-use crate::virtual_touch::{Point, TouchAction};
-use crate::virtual_utils::{compute_dpad_direction, get_pressed_buttons};
-use crate::{f32_to_i16, VitaVirtualDevice, FRONT_TOUCHPAD_RECT, REAR_TOUCHPAD_RECT};
+use crate::virtual_button_mode::ButtonModes;
+use crate::virtual_flick::FlickStick;
+use crate::virtual_gesture::GestureDetector;
+use crate::virtual_gyro::{GyroMouse, GyroStick};
+use crate::virtual_key::{HeldKeys, KeyboardKey};
+use crate::virtual_macro::MacroPlayer;
+use crate::virtual_motion::MotionProcessor;
+use crate::virtual_mouse::{MouseButton, MouseOutput, TouchMouse};
+use crate::virtual_stick::process_stick;
+use crate::virtual_touch::TouchAction;
+use crate::virtual_touch_filter::TouchFilter;
+use crate::virtual_utils::{
+    compute_dpad_direction, get_pressed_buttons, process_touch_reports, take_binding_actions,
+    LastReport,
+};
+use crate::{VitaVirtualDevice, FRONT_TOUCHPAD_RECT, REAR_TOUCHPAD_RECT};
 
 unsafe fn simulate_key_press(vk: VIRTUAL_KEY) -> windows::core::Result<()> {
     let inputs = &mut [
@@ -64,6 +80,216 @@ fn change_volume_by_key(delta: f32) -> windows::core::Result<()> {
     }
 }
 
+fn map_keyboard_key(key: KeyboardKey) -> VIRTUAL_KEY {
+    use windows::Win32::UI::Input::KeyboardAndMouse::*;
+
+    match key {
+        KeyboardKey::A => VK_A,
+        KeyboardKey::B => VK_B,
+        KeyboardKey::C => VK_C,
+        KeyboardKey::D => VK_D,
+        KeyboardKey::E => VK_E,
+        KeyboardKey::F => VK_F,
+        KeyboardKey::G => VK_G,
+        KeyboardKey::H => VK_H,
+        KeyboardKey::I => VK_I,
+        KeyboardKey::J => VK_J,
+        KeyboardKey::K => VK_K,
+        KeyboardKey::L => VK_L,
+        KeyboardKey::M => VK_M,
+        KeyboardKey::N => VK_N,
+        KeyboardKey::O => VK_O,
+        KeyboardKey::P => VK_P,
+        KeyboardKey::Q => VK_Q,
+        KeyboardKey::R => VK_R,
+        KeyboardKey::S => VK_S,
+        KeyboardKey::T => VK_T,
+        KeyboardKey::U => VK_U,
+        KeyboardKey::V => VK_V,
+        KeyboardKey::W => VK_W,
+        KeyboardKey::X => VK_X,
+        KeyboardKey::Y => VK_Y,
+        KeyboardKey::Z => VK_Z,
+        KeyboardKey::Num0 => VK_0,
+        KeyboardKey::Num1 => VK_1,
+        KeyboardKey::Num2 => VK_2,
+        KeyboardKey::Num3 => VK_3,
+        KeyboardKey::Num4 => VK_4,
+        KeyboardKey::Num5 => VK_5,
+        KeyboardKey::Num6 => VK_6,
+        KeyboardKey::Num7 => VK_7,
+        KeyboardKey::Num8 => VK_8,
+        KeyboardKey::Num9 => VK_9,
+        KeyboardKey::F1 => VK_F1,
+        KeyboardKey::F2 => VK_F2,
+        KeyboardKey::F3 => VK_F3,
+        KeyboardKey::F4 => VK_F4,
+        KeyboardKey::F5 => VK_F5,
+        KeyboardKey::F6 => VK_F6,
+        KeyboardKey::F7 => VK_F7,
+        KeyboardKey::F8 => VK_F8,
+        KeyboardKey::F9 => VK_F9,
+        KeyboardKey::F10 => VK_F10,
+        KeyboardKey::F11 => VK_F11,
+        KeyboardKey::F12 => VK_F12,
+        KeyboardKey::F13 => VK_F13,
+        KeyboardKey::F14 => VK_F14,
+        KeyboardKey::F15 => VK_F15,
+        KeyboardKey::F16 => VK_F16,
+        KeyboardKey::F17 => VK_F17,
+        KeyboardKey::F18 => VK_F18,
+        KeyboardKey::F19 => VK_F19,
+        KeyboardKey::F20 => VK_F20,
+        KeyboardKey::F21 => VK_F21,
+        KeyboardKey::F22 => VK_F22,
+        KeyboardKey::F23 => VK_F23,
+        KeyboardKey::F24 => VK_F24,
+        KeyboardKey::Escape => VK_ESCAPE,
+        KeyboardKey::Enter => VK_RETURN,
+        KeyboardKey::Tab => VK_TAB,
+        KeyboardKey::Space => VK_SPACE,
+        KeyboardKey::Backspace => VK_BACK,
+        KeyboardKey::Delete => VK_DELETE,
+        KeyboardKey::Insert => VK_INSERT,
+        KeyboardKey::Home => VK_HOME,
+        KeyboardKey::End => VK_END,
+        KeyboardKey::PageUp => VK_PRIOR,
+        KeyboardKey::PageDown => VK_NEXT,
+        KeyboardKey::Up => VK_UP,
+        KeyboardKey::Down => VK_DOWN,
+        KeyboardKey::Left => VK_LEFT,
+        KeyboardKey::Right => VK_RIGHT,
+        KeyboardKey::Minus => VK_OEM_MINUS,
+        KeyboardKey::Equal => VK_OEM_PLUS,
+        KeyboardKey::LeftBracket => VK_OEM_4,
+        KeyboardKey::RightBracket => VK_OEM_6,
+        KeyboardKey::Backslash => VK_OEM_5,
+        KeyboardKey::Semicolon => VK_OEM_1,
+        KeyboardKey::Apostrophe => VK_OEM_7,
+        KeyboardKey::Grave => VK_OEM_3,
+        KeyboardKey::Comma => VK_OEM_COMMA,
+        KeyboardKey::Period => VK_OEM_PERIOD,
+        KeyboardKey::Slash => VK_OEM_2,
+        KeyboardKey::CapsLock => VK_CAPITAL,
+        KeyboardKey::PrintScreen => VK_SNAPSHOT,
+        KeyboardKey::ScrollLock => VK_SCROLL,
+        KeyboardKey::Pause => VK_PAUSE,
+        KeyboardKey::LeftCtrl => VK_LCONTROL,
+        KeyboardKey::RightCtrl => VK_RCONTROL,
+        KeyboardKey::LeftShift => VK_LSHIFT,
+        KeyboardKey::RightShift => VK_RSHIFT,
+        KeyboardKey::LeftAlt => VK_LMENU,
+        KeyboardKey::RightAlt => VK_RMENU,
+        KeyboardKey::LeftMeta => VK_LWIN,
+        KeyboardKey::RightMeta => VK_RWIN,
+        KeyboardKey::VolumeUp => VK_VOLUME_UP,
+        KeyboardKey::VolumeDown => VK_VOLUME_DOWN,
+        KeyboardKey::Mute => VK_VOLUME_MUTE,
+        KeyboardKey::PlayPause => VK_MEDIA_PLAY_PAUSE,
+        KeyboardKey::NextTrack => VK_MEDIA_NEXT_TRACK,
+        KeyboardKey::PreviousTrack => VK_MEDIA_PREV_TRACK,
+        KeyboardKey::Stop => VK_MEDIA_STOP,
+    }
+}
+
+unsafe fn send_key_input(vk: VIRTUAL_KEY, release: bool) -> windows::core::Result<()> {
+    let flags = if release {
+        KEYEVENTF_KEYUP
+    } else {
+        KEYBD_EVENT_FLAGS(0)
+    };
+    let inputs = &mut [INPUT {
+        r#type: INPUT_KEYBOARD,
+        Anonymous: INPUT_0 {
+            ki: KEYBDINPUT {
+                wVk: vk,
+                wScan: 0,
+                dwFlags: flags,
+                time: 0,
+                dwExtraInfo: 0,
+            },
+        },
+    }];
+
+    let result = SendInput(inputs, std::mem::size_of::<INPUT>() as i32);
+    if result == 0 {
+        Err(windows::core::Error::from_win32())
+    } else {
+        Ok(())
+    }
+}
+
+/// Releases keys that are no longer held, then presses the new ones.
+fn send_key_transitions(
+    pressed: &[KeyboardKey],
+    released: &[KeyboardKey],
+) -> windows::core::Result<()> {
+    unsafe {
+        for &key in released {
+            send_key_input(map_keyboard_key(key), true)?;
+        }
+        for &key in pressed {
+            send_key_input(map_keyboard_key(key), false)?;
+        }
+        Ok(())
+    }
+}
+
+/// Wheel movement of a single notch.
+const WHEEL_DELTA: i32 = 120;
+
+unsafe fn send_mouse_input(
+    dx: i32,
+    dy: i32,
+    data: i32,
+    flags: MOUSE_EVENT_FLAGS,
+) -> windows::core::Result<()> {
+    let inputs = &mut [INPUT {
+        r#type: INPUT_MOUSE,
+        Anonymous: INPUT_0 {
+            mi: MOUSEINPUT {
+                dx,
+                dy,
+                mouseData: data as _,
+                dwFlags: flags,
+                time: 0,
+                dwExtraInfo: 0,
+            },
+        },
+    }];
+
+    let result = SendInput(inputs, std::mem::size_of::<INPUT>() as i32);
+    if result == 0 {
+        Err(windows::core::Error::from_win32())
+    } else {
+        Ok(())
+    }
+}
+
+fn send_mouse_output(output: &MouseOutput) -> windows::core::Result<()> {
+    unsafe {
+        if output.dx != 0 || output.dy != 0 {
+            send_mouse_input(output.dx, output.dy, 0, MOUSEEVENTF_MOVE)?;
+        }
+        if output.wheel != 0 {
+            send_mouse_input(0, 0, output.wheel * WHEEL_DELTA, MOUSEEVENTF_WHEEL)?;
+        }
+        if output.hwheel != 0 {
+            send_mouse_input(0, 0, output.hwheel * WHEEL_DELTA, MOUSEEVENTF_HWHEEL)?;
+        }
+        for button in &output.clicks {
+            let (down, up) = match button {
+                MouseButton::Left => (MOUSEEVENTF_LEFTDOWN, MOUSEEVENTF_LEFTUP),
+                MouseButton::Right => (MOUSEEVENTF_RIGHTDOWN, MOUSEEVENTF_RIGHTUP),
+                MouseButton::Middle => (MOUSEEVENTF_MIDDLEDOWN, MOUSEEVENTF_MIDDLEUP),
+            };
+            send_mouse_input(0, 0, 0, down)?;
+            send_mouse_input(0, 0, 0, up)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Failed to connect to the client")]
@@ -74,6 +300,23 @@ pub enum Error {
     SendReportFailed(#[source] vigem_client::Error),
     #[error("Invalid configuration: {0}")]
     InvalidConfig(String),
+    #[error("Failed to send mouse input")]
+    MouseInputFailed(#[source] windows::core::Error),
+    #[error("Failed to send keyboard input")]
+    KeyboardInputFailed(#[source] windows::core::Error),
+}
+
+/// Rejects the parts of the configuration this backend can not output.
+fn check_config(config: &Config) -> Result<(), Error> {
+    let tablet =
+        |touch_config: &Option<TouchConfig>| matches!(touch_config, Some(TouchConfig::Tablet(_)));
+    if tablet(&config.front_touch_config) || tablet(&config.rear_touch_config) {
+        return Err(Error::InvalidConfig(
+            "tablet mode is only supported on Linux".to_string(),
+        ));
+    }
+
+    Ok(())
 }
 
 fn map_button_to_ds4(button: Button) -> u16 {
@@ -108,40 +351,38 @@ fn map_dpad_direction_to_ds4(dpad: DpadDirection) -> VigemDpadDirection {
     }
 }
 
-fn process_touch_reports(
-    touch_reports: &[vita_reports::TouchReport],
-    touch_config: &Option<TouchConfig>,
-    buttons: &mut DS4Buttons,
-) {
-    if let Some(TouchConfig::Zones(zones)) = touch_config {
-        for touch in touch_reports {
-            if let Some(zone) = zones.locate_at_point(&Point(touch.x.into(), touch.y.into())) {
-                if let Some(action) = zone.action {
-                    match action {
-                        TouchAction::Button(button) => {
-                            let ds4_button = map_button_to_ds4(button);
-                            *buttons |= ds4_button;
-                        }
-                        TouchAction::Dpad(direction) => {
-                            let ds4_dpad = map_dpad_direction_to_ds4(direction);
-                            *buttons = buttons.dpad(ds4_dpad);
-                        }
-                    }
-                }
-            }
-        }
-    }
-}
-
 pub struct VitaDevice {
     ds4_target: DualShock4Wired<Client>,
     config: Config,
+    front_touch_filter: TouchFilter,
+    rear_touch_filter: TouchFilter,
+    front_touch_mouse: TouchMouse,
+    rear_touch_mouse: TouchMouse,
     touch_state: bool,
     touch_start_time: Option<Instant>,
+    held_keys: HeldKeys,
+    macro_player: MacroPlayer,
+    button_modes: ButtonModes,
+    last_report: LastReport,
+    motion_processor: MotionProcessor,
+    gyro_mouse: GyroMouse,
+    gyro_stick: GyroStick,
+    flick_stick: FlickStick,
+    gesture_detector: GestureDetector,
 }
 
 impl VitaDevice {
     pub fn create(config_name: &str) -> crate::Result<Self> {
+        // Select the configuration depending on the name
+        let config = Config::from_name(config_name)
+            .ok_or_else(|| Error::InvalidConfig(config_name.to_string()))?;
+
+        Self::create_with_config(config)
+    }
+
+    pub fn create_with_config(config: Config) -> crate::Result<Self> {
+        check_config(&config)?;
+
         let client = Client::connect().map_err(Error::ConnectionFailed)?;
         let mut ds4_target = DualShock4Wired::new(client, TargetId::DUALSHOCK4_WIRED);
 
@@ -150,24 +391,24 @@ impl VitaDevice {
         // Wait for the device to be ready, because the ioctl doesn't seem to work
         std::thread::sleep(Duration::from_millis(100));
 
-        // Select the configuration depending on the name
-        let config = match config_name {
-            "standart" => Config::rear_rl2_front_rl3(),
-            "alt_triggers" => Config::rear_rl1_front_rl3_vitatriggers_rl2(),
-            "rear_touchpad" => Config::front_top_rl2_bottom_rl3_rear_touchpad(),
-            "front_touchpad" => Config::rear_top_rl2_bottom_rl3_front_touchpad(),
-            _ => {
-                return Err(crate::Error::Windows(Error::InvalidConfig(
-                    config_name.to_string(),
-                )))
-            }
-        };
-
         Ok(VitaDevice {
             ds4_target,
             config: config,
+            front_touch_filter: TouchFilter::default(),
+            rear_touch_filter: TouchFilter::default(),
+            front_touch_mouse: TouchMouse::default(),
+            rear_touch_mouse: TouchMouse::default(),
             touch_state: false,
             touch_start_time: None,
+            held_keys: HeldKeys::default(),
+            macro_player: MacroPlayer::default(),
+            button_modes: ButtonModes::default(),
+            last_report: LastReport::default(),
+            motion_processor: MotionProcessor::default(),
+            gyro_mouse: GyroMouse::default(),
+            gyro_stick: GyroStick::default(),
+            flick_stick: FlickStick::default(),
+            gesture_detector: GestureDetector::default(),
         })
     }
 
@@ -212,44 +453,99 @@ impl VitaDevice {
             None
         }
     }
-}
 
-impl VitaVirtualDevice<&ConfigBuilder> for VitaDevice {
-    type Config = Config;
+    fn process_report(&mut self, mut report: vita_reports::MainReport) -> crate::Result<()> {
+        // Drop unintended contacts before any touch processing
+        report.front_touch.reports = self.front_touch_filter.apply(
+            report.front_touch.reports,
+            report.timestamp,
+            &self.config.front_touch_rejection,
+            FRONT_TOUCHPAD_RECT,
+        );
+        report.back_touch.reports = self.rear_touch_filter.apply(
+            report.back_touch.reports,
+            report.timestamp,
+            &self.config.rear_touch_rejection,
+            REAR_TOUCHPAD_RECT,
+        );
 
-    fn identifiers(&self) -> Option<&[OsString]> {
-        None
-    }
+        // Left-handed transforms, the calibrations stay with the physical sticks
+        self.config.mirror.apply(&mut report);
+        let (left_calibration, right_calibration) = if self.config.mirror.swap_sticks {
+            (
+                &self.config.right_stick_calibration,
+                &self.config.left_stick_calibration,
+            )
+        } else {
+            (
+                &self.config.left_stick_calibration,
+                &self.config.right_stick_calibration,
+            )
+        };
 
-    #[inline]
-    fn get_config(&self) -> &Self::Config {
-        &self.config
-    }
+        // Calibration, deadzones and curves of the sticks, before anything sets their position
+        process_stick(
+            &mut report.lx,
+            &mut report.ly,
+            left_calibration,
+            &self.config.left_stick,
+        );
+        process_stick(
+            &mut report.rx,
+            &mut report.ry,
+            right_calibration,
+            &self.config.right_stick,
+        );
 
-    #[inline]
-    fn set_config(&mut self, config: &ConfigBuilder) -> crate::Result<()> {
-        if let Some(front_touch_config) = &config.front_touch_config {
-            self.config.front_touch_config = front_touch_config.clone();
-        }
+        // Motion outputs use the gyro rates without their bias
+        let motion = self
+            .motion_processor
+            .update_report(&mut report, &self.config.motion);
+
+        // Gyro aiming, before its activation button reaches the other outputs
+        let mut aim_output = match &self.config.gyro_mouse {
+            Some(gyro_mouse_config) => self.gyro_mouse.update(
+                &motion,
+                &mut report.buttons,
+                report.motion.timestamp,
+                &self.config.motion,
+                gyro_mouse_config,
+            ),
+            None => MouseOutput::default(),
+        };
 
-        if let Some(rear_touch_config) = &config.rear_touch_config {
-            self.config.rear_touch_config = rear_touch_config.clone();
+        // Gyro stick, mixed with the physical stick
+        if let Some(gyro_stick_config) = &self.config.gyro_stick {
+            self.gyro_stick.update(
+                &motion,
+                &mut report,
+                &self.config.motion,
+                gyro_stick_config,
+            );
         }
 
-        if let Some(trigger_config) = config.trigger_config {
-            self.config.trigger_config = trigger_config;
+        // Flick stick, combined with the gyro aiming
+        if let Some(flick_stick_config) = &self.config.flick_stick {
+            aim_output.merge(self.flick_stick.update(&mut report, flick_stick_config));
         }
 
-        Ok(())
-    }
+        // Motion gestures trigger their actions like bound buttons
+        let gesture_actions = if self.config.gestures.bindings.is_empty() {
+            Vec::new()
+        } else {
+            let gestures = self.gesture_detector.update(
+                &motion,
+                report.timestamp,
+                &self.config.motion,
+                &self.config.gestures,
+            );
+            self.config.gestures.actions(&gestures)
+        };
 
-    fn send_report(&mut self, report: vita_reports::MainReport) -> crate::Result<()> {
-        // Calculate the direction of the D-Pad
-        let dpad_direction = compute_dpad_direction(&report.buttons);
-        let ds4_dpad = map_dpad_direction_to_ds4(dpad_direction);
+        // The outputs that change over time are updated from this report until the next one
+        self.last_report.set(&report);
+        let buttons = self.update_buttons(&mut report, gesture_actions)?;
 
-        // Get the pressed buttons
-        let pressed_buttons = get_pressed_buttons(&report.buttons, self.config.trigger_config);
         if report.buttons.vol_up {
             change_volume_by_key(0.02).expect("Failed to increase volume");
         }
@@ -257,25 +553,25 @@ impl VitaVirtualDevice<&ConfigBuilder> for VitaDevice {
             change_volume_by_key(-0.02).expect("Failed to increase volume");
         }
 
-        // Create DS4Buttons object
-        let mut buttons = DS4Buttons::new().dpad(ds4_dpad);
-
-        for button in pressed_buttons {
-            let ds4_button = map_button_to_ds4(button);
-            buttons |= ds4_button;
+        // Handle touch panels used as a mouse
+        let mut mouse_output = aim_output;
+        if let Some(TouchConfig::Mouse(mouse_config)) = &self.config.front_touch_config {
+            mouse_output.merge(self.front_touch_mouse.update(
+                &report.front_touch.reports,
+                report.timestamp,
+                mouse_config,
+            ));
+        }
+        if let Some(TouchConfig::Mouse(mouse_config)) = &self.config.rear_touch_config {
+            mouse_output.merge(self.rear_touch_mouse.update(
+                &report.back_touch.reports,
+                report.timestamp,
+                mouse_config,
+            ));
+        }
+        if !mouse_output.is_empty() {
+            send_mouse_output(&mouse_output).map_err(Error::MouseInputFailed)?;
         }
-
-        // Process touch reports
-        process_touch_reports(
-            &report.front_touch.reports,
-            &self.config.front_touch_config,
-            &mut buttons,
-        );
-        process_touch_reports(
-            &report.back_touch.reports,
-            &self.config.rear_touch_config,
-            &mut buttons,
-        );
 
         // Handling special touchpad buttons
         let is_touching = match (
@@ -287,37 +583,40 @@ impl VitaVirtualDevice<&ConfigBuilder> for VitaDevice {
             _ => false,
         };
 
-        let mut special_buttons = DS4SpecialButtons::new();
-
         // Touch click emulation
+        let mut touch_click = false;
         if is_touching && !self.touch_state {
             self.touch_start_time = Some(Instant::now());
         } else if !is_touching && self.touch_state {
             if let Some(start_time) = self.touch_start_time {
                 let duration = Instant::now().duration_since(start_time);
-                if duration < Duration::from_millis(150) {
-                    special_buttons = special_buttons.touchpad(true);
-                }
+                touch_click = duration < Duration::from_millis(150);
             }
             self.touch_start_time = None;
         }
 
-        special_buttons = special_buttons.ps_home(report.buttons.ps);
-
         self.touch_state = is_touching;
 
-        // Создаем touchpad report
-        let touchpad = self.create_touchpad_report(&report);
+        self.send_ds4_report(&report, buttons, touch_click)
+    }
 
-        // Convert the vita accel range [-4.0, 4.0] to the dualshock 4 range [-32768, 32768]
-        let accel_x_i16 = f32_to_i16(-report.motion.accelerometer.x, -4.0, 4.0); //inverted
-        let accel_y_i16 = f32_to_i16(report.motion.accelerometer.y, -4.0, 4.0);
-        let accel_z_i16 = f32_to_i16(-report.motion.accelerometer.z, -4.0, 4.0); //inverted
+    /// Sends the state of the gamepad to the virtual DualShock 4.
+    fn send_ds4_report(
+        &mut self,
+        report: &vita_reports::MainReport,
+        buttons: DS4Buttons,
+        touch_click: bool,
+    ) -> crate::Result<()> {
+        let special_buttons = DS4SpecialButtons::new()
+            .touchpad(touch_click)
+            .ps_home(report.buttons.ps);
+
+        // Создаем touchpad report
+        let touchpad = self.create_touchpad_report(report);
 
-        // Convert the vita gyro range [-35.0, 35.0] to the dualshock 4 range [-32768, 32768]
-        let gyro_x_i16 = f32_to_i16(report.motion.gyro.x, -35.0, 35.0);
-        let gyro_y_i16 = f32_to_i16(-report.motion.gyro.y, -35.0, 35.0); //inverted
-        let gyro_z_i16 = f32_to_i16(report.motion.gyro.z, -35.0, 35.0);
+        // Convert the vita motion to the dualshock 4 axes and range [-32768, 32768]
+        let ([accel_x, accel_y, accel_z], [gyro_x, gyro_y, gyro_z]) =
+            self.config.motion.controller_axes(&report.motion);
 
         // Trigger processing for Trigger configuration
         let (pwr_trigger_l, pwr_trigger_r) = (
@@ -340,12 +639,12 @@ impl VitaVirtualDevice<&ConfigBuilder> for VitaDevice {
             .thumb_ry(report.ry)
             .buttons(buttons)
             .touch_reports(touchpad, None, None)
-            .gyro_x(gyro_x_i16)
-            .gyro_y(gyro_z_i16)
-            .gyro_z(gyro_y_i16)
-            .accel_x(accel_x_i16)
-            .accel_y(accel_z_i16)
-            .accel_z(accel_y_i16)
+            .gyro_x(gyro_x)
+            .gyro_y(gyro_y)
+            .gyro_z(gyro_z)
+            .accel_x(accel_x)
+            .accel_y(accel_y)
+            .accel_z(accel_z)
             .trigger_l(pwr_trigger_l)
             .trigger_r(pwr_trigger_r)
             .status(DS4Status::with_battery_status(BatteryStatus::Charging(
@@ -360,4 +659,161 @@ impl VitaVirtualDevice<&ConfigBuilder> for VitaDevice {
 
         Ok(())
     }
+
+    /// Applies the button modes, bindings, touch zones and macros to the buttons of the report,
+    /// sends the keys that changed and returns the gamepad buttons.
+    fn update_buttons(
+        &mut self,
+        report: &mut vita_reports::MainReport,
+        gesture_actions: Vec<TouchAction>,
+    ) -> crate::Result<DS4Buttons> {
+        // Turbo, toggle and long press buttons, before they reach their bindings
+        let mode_actions = self.button_modes.update(
+            &mut report.buttons,
+            report.timestamp,
+            &self.config.button_modes,
+        );
+
+        // Bound buttons are replaced by their actions
+        let binding_actions =
+            take_binding_actions(&mut report.buttons, &self.config.button_bindings);
+
+        // Process touch reports
+        let front_touch_actions = process_touch_reports(
+            &self
+                .config
+                .mirror
+                .touch_zone_reports(&report.front_touch.reports, FRONT_TOUCHPAD_RECT),
+            &self.config.front_touch_config,
+        );
+        let rear_touch_actions = process_touch_reports(
+            &self
+                .config
+                .mirror
+                .touch_zone_reports(&report.back_touch.reports, REAR_TOUCHPAD_RECT),
+            &self.config.rear_touch_config,
+        );
+        let actions: Vec<TouchAction> = front_touch_actions
+            .into_iter()
+            .chain(rear_touch_actions)
+            .chain(mode_actions)
+            .chain(binding_actions)
+            .chain(gesture_actions)
+            .collect();
+
+        // Playing macros press their inputs on top of the physical ones
+        self.macro_player.update(&actions, report, &self.config.macros);
+
+        // Calculate the direction of the D-Pad
+        let dpad_direction = compute_dpad_direction(&report.buttons);
+        let ds4_dpad = map_dpad_direction_to_ds4(dpad_direction);
+
+        // Get the pressed buttons
+        let pressed_buttons = get_pressed_buttons(&report.buttons, self.config.trigger_config);
+
+        // Create DS4Buttons object
+        let mut buttons = DS4Buttons::new().dpad(ds4_dpad);
+
+        for button in pressed_buttons {
+            let ds4_button = map_button_to_ds4(button);
+            buttons |= ds4_button;
+        }
+
+        let mut keys = Vec::new();
+        for action in actions {
+            match action {
+                TouchAction::Button(button) => {
+                    let ds4_button = map_button_to_ds4(button);
+                    buttons |= ds4_button;
+                }
+                TouchAction::Dpad(direction) => {
+                    let ds4_dpad = map_dpad_direction_to_ds4(direction);
+                    buttons = buttons.dpad(ds4_dpad);
+                }
+                TouchAction::Key(combo) => keys.extend(combo.0),
+                // Already handled by the macro player
+                TouchAction::Macro(_) => {}
+            }
+        }
+
+        let (keys_to_press, keys_to_release) = self.held_keys.update(keys);
+        send_key_transitions(&keys_to_press, &keys_to_release)
+            .map_err(Error::KeyboardInputFailed)?;
+
+        Ok(buttons)
+    }
+
+    /// Updates the outputs that change over time between reports.
+    ///
+    /// Only the gamepad state, the changes of the keys and the mouse motion are sent,
+    /// the outputs of the report itself were sent when it was received.
+    fn process_tick(&mut self, mut report: vita_reports::MainReport) -> crate::Result<()> {
+        let aim_output = match &self.config.flick_stick {
+            Some(flick_stick_config) => self.flick_stick.tick(report.timestamp, flick_stick_config),
+            None => MouseOutput::default(),
+        };
+        let gesture_actions = self
+            .config
+            .gestures
+            .actions(&self.gesture_detector.active(report.timestamp));
+
+        let buttons = self.update_buttons(&mut report, gesture_actions)?;
+        if !aim_output.is_empty() {
+            send_mouse_output(&aim_output).map_err(Error::MouseInputFailed)?;
+        }
+
+        self.send_ds4_report(&report, buttons, false)
+    }
+}
+
+impl VitaVirtualDevice<&ConfigBuilder> for VitaDevice {
+    type Config = Config;
+
+    fn identifiers(&self) -> Option<&[OsString]> {
+        None
+    }
+
+    #[inline]
+    fn get_config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    #[inline]
+    fn set_config(&mut self, config: &ConfigBuilder) -> crate::Result<()> {
+        let mut new_config = self.config.clone();
+        new_config.apply(config);
+        check_config(&new_config)?;
+
+        self.config = new_config;
+
+        Ok(())
+    }
+
+    fn send_report(&mut self, report: vita_reports::MainReport) -> crate::Result<()> {
+        self.process_report(report)
+    }
+
+    fn next_tick(&self) -> Option<Duration> {
+        let timestamp = self.last_report.now()?.timestamp;
+        let deadline = [
+            self.button_modes.next_deadline(timestamp, &self.config.button_modes),
+            self.macro_player.next_deadline(timestamp, &self.config.macros),
+            self.config
+                .flick_stick
+                .as_ref()
+                .and_then(|config| self.flick_stick.next_deadline(config)),
+            self.gesture_detector.next_deadline(timestamp),
+        ]
+        .into_iter()
+        .flatten()
+        .min()?;
+        Some(self.last_report.until(deadline))
+    }
+
+    fn tick(&mut self) -> crate::Result<()> {
+        match self.last_report.now() {
+            Some(report) => self.process_tick(report),
+            None => Ok(()),
+        }
+    }
 }
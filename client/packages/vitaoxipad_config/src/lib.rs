@@ -5,6 +5,7 @@ use color_eyre::eyre::eyre;
 use std::fs;
 use std::env;
 use home::home_dir;  // Import home crate
+use vita_virtual_device::{ConfigBuilder, Macro, StickCalibration};
 
 #[derive(Deserialize)]
 pub struct Config {
@@ -13,6 +14,8 @@ pub struct Config {
     pub configuration: Option<String>,
     pub polling_interval: Option<u64>,
     pub debug: Option<bool>,
+    /// Overrides applied on top of the selected configuration
+    pub profile: Option<ConfigBuilder>,
 }
 
 impl Default for Config {
@@ -23,6 +26,7 @@ impl Default for Config {
             configuration: Some("standart".to_string()),
             polling_interval: Some(6000),
             debug: Some(false),
+            profile: None,
         }
     }
 }
@@ -68,25 +72,21 @@ fn get_config_file_paths() -> Vec<PathBuf> {
 }
 
 
+/// Returns the first existing config file.
+fn find_config_file() -> Option<PathBuf> {
+    get_config_file_paths().into_iter().find(|path| path.exists())
+}
+
 pub fn load_config() -> color_eyre::Result<Config> {
     let mut settings = ConfigLoader::builder();
 
-    let config_paths = get_config_file_paths();
-    let mut config_file_found = false;
-
     // Check each possible config file path
-    for path in config_paths {
-        if path.exists() {
-            // Validate the TOML file
-            validate_toml(path.to_str().unwrap())?;
-            println!("Using config file: {}", path.display());
-            settings = settings.add_source(File::from(path));
-            config_file_found = true;
-            break;
-        }
-    }
-
-    if !config_file_found {
+    if let Some(path) = find_config_file() {
+        // Validate the TOML file
+        validate_toml(path.to_str().unwrap())?;
+        println!("Using config file: {}", path.display());
+        settings = settings.add_source(File::from(path));
+    } else {
         println!("No config file found. Using default configuration.");
     }
 
@@ -104,6 +104,133 @@ pub fn load_config() -> color_eyre::Result<Config> {
     Ok(config)
 }
 
+/// Returns the path and content of the config file in use,
+/// or of a new `config.toml` if there is none.
+fn read_config_file() -> color_eyre::Result<(PathBuf, String)> {
+    let path = find_config_file().unwrap_or_else(|| PathBuf::from("config.toml"));
+    let content = if path.exists() {
+        fs::read_to_string(&path).map_err(|e| eyre!("Failed to read config file: {}", e))?
+    } else {
+        String::new()
+    };
+    Ok((path, content))
+}
+
+/// Returns the `[profile]` section of the config file in use as TOML,
+/// empty if there is none.
+pub fn read_profile() -> color_eyre::Result<String> {
+    let (_, content) = read_config_file()?;
+    let profile = toml::from_str::<toml::Table>(&content)
+        .map_err(|e| eyre!("TOML validation error: {}", e))?
+        .remove("profile");
+    match profile {
+        Some(toml::Value::Table(profile)) => toml::to_string(&profile).map_err(|e| eyre!(e)),
+        _ => Ok(String::new()),
+    }
+}
+
+/// Checks that the edited config is still valid before writing it.
+fn write_config_file(path: &Path, content: &str) -> color_eyre::Result<()> {
+    toml::from_str::<Config>(content)
+        .map_err(|e| eyre!("Failed to update the config file: {}", e))?;
+    fs::write(path, content).map_err(|e| eyre!("Failed to write config file: {}", e))
+}
+
+/// Saves a macro in the `[profile.macros]` section of the config file in use.
+///
+/// The macro is appended to the file, so existing comments are kept.
+pub fn save_macro(name: &str, recorded: &Macro) -> color_eyre::Result<PathBuf> {
+    let (path, mut content) = read_config_file()?;
+
+    let exists = toml::from_str::<toml::Table>(&content)
+        .map_err(|e| eyre!("TOML validation error: {}", e))?
+        .get("profile")
+        .and_then(|profile| profile.get("macros"))
+        .and_then(|macros| macros.get(name))
+        .is_some();
+    if exists {
+        return Err(eyre!("Macro {name:?} already exists in {}", path.display()));
+    }
+
+    // Bare keys only allow ASCII letters, digits, `_` and `-`
+    let key = if !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        name.to_string()
+    } else {
+        toml::Value::String(name.to_string()).to_string()
+    };
+
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push_str(&format!("\n[profile.macros.{key}]\nsteps = [\n"));
+    for step in &recorded.steps {
+        let step = toml::Value::try_from(step).map_err(|e| eyre!(e))?;
+        content.push_str(&format!("    {step},\n"));
+    }
+    content.push_str("]\n");
+
+    write_config_file(&path, &content)?;
+    Ok(path)
+}
+
+/// Saves the stick calibrations in the `[profile]` section of the config file in use,
+/// replacing the previous ones.
+pub fn save_stick_calibration(
+    left: &StickCalibration,
+    right: &StickCalibration,
+) -> color_eyre::Result<PathBuf> {
+    let (path, content) = read_config_file()?;
+    let entries = [
+        ("left_stick_calibration", left),
+        ("right_stick_calibration", right),
+    ];
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut profile_header = None;
+    for line in content.lines() {
+        let line_start = line.trim_start();
+        let replaced = entries.iter().any(|(key, _)| {
+            line_start
+                .strip_prefix(key)
+                .is_some_and(|rest| rest.trim_start().starts_with('='))
+        });
+        if replaced {
+            continue;
+        }
+        if line_start.starts_with("[profile]") {
+            profile_header = Some(lines.len());
+        }
+        lines.push(line.to_string());
+    }
+
+    let mut values = Vec::new();
+    for (key, calibration) in entries {
+        let value = toml::Value::try_from(calibration).map_err(|e| eyre!(e))?;
+        values.push(format!("{key} = {value}"));
+    }
+    match profile_header {
+        Some(index) => {
+            lines.splice(index + 1..index + 1, values);
+        }
+        None => {
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines.push("[profile]".to_string());
+            lines.extend(values);
+        }
+    }
+
+    let mut content = lines.join("\n");
+    content.push('\n');
+    write_config_file(&path, &content)?;
+    Ok(path)
+}
+
 pub fn print_sample_config() {
     println!(r#"# Sample Configuration file for the VitaOxiPad
 # Refer ReadMe for all available options
@@ -126,5 +253,64 @@ configuration = "standart"
 polling_interval = 6000
 
 # Enable or disable debug mode
-debug = false"#);
+debug = false
+
+# Overrides for the selected configuration (optional)
+# [profile]
+# button_bindings = [["Select", {{ Key = "Ctrl+Z" }}], ["R", {{ Macro = "combo" }}]]
+#
+# button_modes = [["Cross", {{ Turbo = {{ rate = 10.0 }} }}], ["R", "Toggle"]]
+#
+# [profile.macros.combo]
+# steps = [{{ buttons = ["Down"], duration = 50 }}, {{ buttons = ["Square"], duration = 50 }}]
+#
+# [profile.left_stick]
+# inner_deadzone = 0.1
+# outer_deadzone = 0.05
+# curve = "Linear"
+#
+# [profile.motion]
+# gyro_calibration = true
+# filter = {{ Madgwick = {{ beta = 0.1 }} }}
+# layout = "Normal"  # "Flipped", "Portrait"
+#
+# Aim with the gyro as a mouse
+# [profile.gyro_mouse]
+# sensitivity = [10.0, 10.0]
+# activation = {{ OffWhileHeld = "L" }}
+# space = "Player"
+#
+# Steer with the left stick by tilting the Vita
+# [profile.gyro_stick]
+# stick = "Left"
+# input = "Tilt"
+# vertical = false
+#
+# Turn the camera with flicks of the right stick
+# [profile.flick_stick]
+# mouse_per_degree = 10.0
+# flick_time = 100
+#
+# [profile.gestures]
+# bindings = [["Shake", {{ Key = "R" }}], [{{ Tilt = "Left" }}, {{ Key = "Q" }}]]
+#
+# Left-handed layout
+# [profile.mirror]
+# swap_sticks = true
+# mirror_buttons = true
+# swap_shoulders = true
+# mirror_touch = true
+#
+# [profile.rear_touch_rejection]
+# ignore_initial_contacts = true  # ignore fingers resting on the panel at connection
+# max_force = 100                 # ignore palm-sized contacts
+# stationary_timeout = 2000       # ignore contacts not moving for this many milliseconds
+# stationary_radius = 30
+# edges = {{ left = 150, top = 0, right = 150, bottom = 0 }}
+#
+# Use the front touch panel as a mouse
+# [profile.front_touch_config.Mouse]
+# sensitivity = 0.5
+# acceleration = 0.5
+# natural_scroll = false"#);
 }
@@ -2,6 +2,8 @@
 #define EPOLL_HPP
 
 #include <arpa/inet.h>
+#include <psp2/kernel/rng.h>
+#include <psp2/kernel/threadmgr.h>
 #include <psp2/libdbg.h>
 #include <psp2/net/net.h>
 #include <psp2/rtc.h>
@@ -65,6 +67,11 @@ public:
     unsigned int addrlen = sizeof(clientaddr);
     sceNetGetpeername(fd, reinterpret_cast<SceNetSockaddr *>(&clientaddr), &addrlen);
     sceNetInetNtop(SCE_NET_AF_INET, &(clientaddr.sin_addr), ip_, INET_ADDRSTRLEN);
+
+    // 0 is left for servers without sessions
+    sceKernelGetRandomNumber(&session_, sizeof(session_));
+    if (session_ == 0)
+      session_ = 1;
   }
 
   int ctrl_fd() const { return sock_.fd(); }
@@ -86,6 +93,15 @@ public:
     return sent_data_time_helper_.elapsed_time_micros();
   }
   void update_sent_data_time() { sent_data_time_helper_.update(); }
+  /**
+   * @brief Random id of the connection, to tell the packets of a new server apart
+   */
+  uint32_t session() const { return session_; }
+  /**
+   * @brief Returns the sequence number of the next packet sent
+   */
+  uint32_t next_sequence() { return sequence_++; }
+
   bool is_polling_time_elapsed() const { return time_since_last_sent_data() > polling_time_; }
   uint64_t remaining_polling_time() const {
     return std::clamp(polling_time_ - time_since_last_sent_data(), static_cast<uint64_t>(0),
@@ -111,6 +127,7 @@ public:
     const std::unordered_map<NetProtocol::PacketContent, BufferHandler> handlers = {
         {NetProtocol::PacketContent::Handshake, &Client::handle_handshake},
         {NetProtocol::PacketContent::Config, &Client::handle_config},
+        {NetProtocol::PacketContent::Ping, &Client::handle_ping},
     };
 
     auto handler_entry = handlers.find(data->content_type());
@@ -162,6 +179,25 @@ public:
     }
   }
 
+  void handle_ping(const void *buffer) {
+    auto ping = static_cast<NetProtocol::Ping const *>(buffer);
+    SCE_DBG_LOG_TRACE("Received ping from %s", ip());
+
+    // Same clock as the pad and motion timestamps
+    uint64_t server_time = sceKernelGetSystemTimeWide();
+
+    flatbuffers::FlatBufferBuilder builder(64);
+    auto pong = NetProtocol::CreatePong(builder, ping->client_time(), server_time);
+    auto packet =
+        NetProtocol::CreatePacket(builder, NetProtocol::PacketContent::Pong, pong.Union());
+    builder.FinishSizePrefixed(packet);
+
+    int sent = sceNetSend(ctrl_fd(), builder.GetBufferPointer(), builder.GetSize(), 0);
+    if (sent < 0) {
+      SCE_DBG_LOG_ERROR("Failed to send pong to %s: 0x%08X", ip(), sent);
+    }
+  }
+
   bool handle_heartbeat() {
     if (buffer_.size() < heartbeat_magic.size() ||
         !std::equal(heartbeat_magic.begin(), heartbeat_magic.end(), buffer_.begin()))
@@ -192,6 +228,8 @@ private:
    * @brief Time in microseconds between polling for data
    */
   uint64_t polling_time_ = MIN_POLLING_INTERVAL_MICROS;
+  uint32_t session_ = 0;
+  uint32_t sequence_ = 0;
 
   State state_ = State::WaitingForHandshake;
   std::vector<uint8_t> buffer_;
@@ -7,8 +7,11 @@
 
 void get_ctrl(SceCtrlData *pad, SceMotionState *motion_data, SceTouchData *touch_data_front,
               SceTouchData *touch_data_back);
+void add_motion_samples(std::vector<NetProtocol::MotionSample> &samples);
 void ctrl_as_netprotocol(SceCtrlData *pad, SceMotionState *motion_data,
                          SceTouchData *touch_data_front, SceTouchData *touch_data_back,
+                         const std::vector<NetProtocol::MotionSample> &motion_samples,
+                         uint32_t session, uint32_t sequence,
                          flatbuffers::FlatBufferBuilder &builder, int battery_level);
 
 #endif // __CTRL_H__
@@ -5,6 +5,25 @@
 #include "ctrl.hpp"
 #include "kctrl-kernel.h"
 
+// Oldest samples are dropped past this, when packets are not sent
+constexpr size_t MAX_MOTION_SAMPLES = 32;
+// Records of the sensor history read at once, the most sceMotionGetSensorState returns
+constexpr int MOTION_SENSOR_RECORDS = 64;
+
+NetProtocol::MotionData convert_motion_data(const SceMotionState &data) {
+  NetProtocol::Vector3 accel(data.acceleration.x, data.acceleration.y, data.acceleration.z);
+  NetProtocol::Vector3 gyro(data.angularVelocity.x, data.angularVelocity.y,
+                            data.angularVelocity.z);
+  return NetProtocol::MotionData(gyro, accel);
+}
+
+// Raw readings of the sensor, without the bias and tilt corrections of sceMotionGetState
+NetProtocol::MotionData convert_sensor_data(const SceMotionSensorState &data) {
+  NetProtocol::Vector3 accel(data.accelerometer.x, data.accelerometer.y, data.accelerometer.z);
+  NetProtocol::Vector3 gyro(data.gyro.x, data.gyro.y, data.gyro.z);
+  return NetProtocol::MotionData(gyro, accel);
+}
+
 NetProtocol::ButtonsData convert_pad_data(const SceCtrlData &data) {
   return NetProtocol::ButtonsData(
       (data.buttons & SCE_CTRL_SELECT) > 0, (data.buttons & SCE_CTRL_START) > 0,
@@ -25,7 +44,7 @@ convert_touch_data(flatbuffers::FlatBufferBuilder &builder, const SceTouchData &
                  [](const SceTouchReport &report) {
                    return NetProtocol::TouchReport(report.force, report.id, report.x, report.y);
                  });
-  return NetProtocol::CreateTouchDataDirect(builder, &reports);
+  return NetProtocol::CreateTouchDataDirect(builder, &reports, data.timeStamp);
 }
 
 void get_ctrl(SceCtrlData *pad, SceMotionState *motion_data, SceTouchData *touch_data_front,
@@ -50,24 +69,51 @@ void get_ctrl(SceCtrlData *pad, SceMotionState *motion_data, SceTouchData *touch
   last_ts = pad->timeStamp;
 }
 
+void add_motion_samples(std::vector<NetProtocol::MotionSample> &samples) {
+  static SceULong64 last_ts = 0;
+  static SceMotionSensorState records[MOTION_SENSOR_RECORDS];
+
+  // The sensor runs faster than the main loop, its history has every sample since the last call
+  int res = sceMotionGetSensorState(records, MOTION_SENSOR_RECORDS);
+  if (res < 0) {
+    SCE_DBG_LOG_ERROR("sceMotionGetSensorState failed: 0x%08X", res);
+    return;
+  }
+
+  std::vector<SceMotionSensorState> new_records;
+  std::copy_if(records, records + MOTION_SENSOR_RECORDS, std::back_inserter(new_records),
+               [](const SceMotionSensorState &record) { return record.hostTimestamp > last_ts; });
+  std::sort(new_records.begin(), new_records.end(),
+            [](const SceMotionSensorState &a, const SceMotionSensorState &b) {
+              return a.hostTimestamp < b.hostTimestamp;
+            });
+
+  for (const auto &record : new_records) {
+    if (samples.size() >= MAX_MOTION_SAMPLES)
+      samples.erase(samples.begin());
+    samples.emplace_back(convert_sensor_data(record), record.hostTimestamp);
+    last_ts = record.hostTimestamp;
+  }
+}
+
 void ctrl_as_netprotocol(SceCtrlData *pad, SceMotionState *motion_data,
                          SceTouchData *touch_data_front, SceTouchData *touch_data_back,
+                         const std::vector<NetProtocol::MotionSample> &motion_samples,
+                         uint32_t session, uint32_t sequence,
                          flatbuffers::FlatBufferBuilder &builder, int battery_level) {
   builder.Clear();
 
   auto buttons = convert_pad_data(*pad);
   auto data_front = convert_touch_data(builder, *touch_data_front);
   auto data_back = convert_touch_data(builder, *touch_data_back);
+  auto samples = builder.CreateVectorOfStructs(motion_samples);
 
-  NetProtocol::Vector3 accel(motion_data->acceleration.x, motion_data->acceleration.y,
-                             motion_data->acceleration.z);
-  NetProtocol::Vector3 gyro(motion_data->angularVelocity.x, motion_data->angularVelocity.y,
-                            motion_data->angularVelocity.z);
-  NetProtocol::MotionData motion(gyro, accel);
+  auto motion = convert_motion_data(*motion_data);
 
   auto content =
       NetProtocol::CreatePad(builder, &buttons, pad->lx, pad->ly, pad->rx, pad->ry, data_front,
-                             data_back, &motion, pad->timeStamp, battery_level);
+                             data_back, &motion, pad->timeStamp, battery_level, samples,
+                             session, sequence, motion_data->hostTimestamp);
 
   auto packet =
       NetProtocol::CreatePacket(builder, NetProtocol::PacketContent::Pad, content.Union());
@@ -200,6 +200,8 @@ int net_thread(__attribute__((unused)) unsigned int arglen, void *argp) {
   SceCtrlData pad;
   SceMotionState motion_data;
   SceTouchData touch_data_front, touch_data_back;
+  // Motion samples taken since the last packet sent
+  std::vector<NetProtocol::MotionSample> motion_samples;
 
   // Main loop of the network
   // Ends if there was a sceNetEpollWait error or the thread was asked to stop
@@ -208,6 +210,7 @@ int net_thread(__attribute__((unused)) unsigned int arglen, void *argp) {
     // Power tick for sleep disabling, update battery
     sceKernelPowerTick(SCE_KERNEL_POWER_TICK_DISABLE_AUTO_SUSPEND);
     get_ctrl(&pad, &motion_data, &touch_data_front, &touch_data_back);
+    add_motion_samples(motion_samples);
     shared_data->pad_data = pad;
 
     // Receiving TCP events
@@ -334,8 +337,10 @@ int net_thread(__attribute__((unused)) unsigned int arglen, void *argp) {
     if (client->state() == Client::State::Connected && client->is_polling_time_elapsed() &&
         shared_data->pad_mode) {
       if (server_udp_fd >= 0) {
-        ctrl_as_netprotocol(&pad, &motion_data, &touch_data_front, &touch_data_back, pad_data,
+        ctrl_as_netprotocol(&pad, &motion_data, &touch_data_front, &touch_data_back,
+                            motion_samples, client->session(), client->next_sequence(), pad_data,
                             shared_data->battery_level);
+        motion_samples.clear();
         client->update_sent_data_time();
         auto client_addr = client->data_conn_info();
         SceNetSockaddr *need_client_addr = reinterpret_cast<SceNetSockaddr *>(&client_addr);